@@ -0,0 +1,118 @@
+use phoenix_lib::vault::Vault;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::exit;
+
+const APP_IDENTIFIER: &str = "phoenix.auth.v1";
+const USAGE: &str =
+    "Usage: phoenix-cli <list | code <name|id> | add <otpauth-url> | export | import [file]>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.is_empty() {
+        usage();
+    }
+
+    let vault = open_vault();
+
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            for account in vault.get_all_accounts("") {
+                println!("{}\t{}", account.id, account.name);
+            }
+        }
+        Some("code") => {
+            let query = args.get(1).unwrap_or_else(|| usage());
+            let account = vault.find_account(query).unwrap_or_else(|e| fail(&e));
+            let otp = vault
+                .get_one_time_password(account.id as u32)
+                .unwrap_or_else(|e| fail(&e));
+
+            println!("{}", otp.code);
+            eprintln!(
+                "{}: {} seconds remaining",
+                account.name, otp.seconds_remaining
+            );
+        }
+        Some("add") => {
+            let url = args.get(1).unwrap_or_else(|| usage());
+            let account = vault.import_url(url).unwrap_or_else(|e| fail(&e));
+
+            println!("Imported account called: {}", account.name);
+        }
+        Some("export") => print!("{}", vault.export_accounts().unwrap_or_else(|e| fail(&e))),
+        Some("import") => {
+            let urls = match args.get(1).map(String::as_str) {
+                None | Some("-") => {
+                    let mut urls = String::new();
+                    io::stdin()
+                        .read_to_string(&mut urls)
+                        .unwrap_or_else(|e| fail(&format!("Could not read stdin: {}", e)));
+                    urls
+                }
+                Some(path) => fs::read_to_string(path)
+                    .unwrap_or_else(|e| fail(&format!("Could not read {}: {}", path, e))),
+            };
+            let mut failed = 0;
+
+            for url in urls.lines().map(str::trim).filter(|url| !url.is_empty()) {
+                match vault.import_url(url) {
+                    Ok(account) => println!("Imported account called: {}", account.name),
+                    Err(e) => {
+                        eprintln!("Skipped {}: {}", url, e);
+                        failed += 1;
+                    }
+                }
+            }
+
+            if failed > 0 {
+                exit(1);
+            }
+        }
+        _ => usage(),
+    }
+}
+
+fn open_vault() -> Vault {
+    let data_directory = match env::var("PHOENIX_DATA_DIR") {
+        Ok(directory) if !directory.is_empty() => PathBuf::from(directory),
+        _ => app_data_directory().unwrap_or_else(|| fail("Could not find the app data directory")),
+    };
+
+    if !Vault::exists(&data_directory) {
+        fail(&format!(
+            "No Phoenix vault found in {}",
+            data_directory.display()
+        ));
+    }
+
+    Vault::open(data_directory).unwrap_or_else(|e| fail(&format!("Could not open vault: {}", e)))
+}
+
+// Mirrors the directory Tauri resolves for the app identifier
+fn app_data_directory() -> Option<PathBuf> {
+    let data_directory = if cfg!(target_os = "windows") {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env::var_os("HOME")?).join("Library/Application Support")
+    } else {
+        match env::var_os("XDG_DATA_HOME") {
+            Some(directory) if !directory.is_empty() => PathBuf::from(directory),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+        }
+    };
+
+    Some(data_directory.join(APP_IDENTIFIER))
+}
+
+fn usage() -> ! {
+    fail(USAGE)
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    exit(1);
+}
@@ -1,15 +1,21 @@
+use crate::encryption;
 use crate::sync_api::Record;
 use libotp::HOTPAlgorithm;
 use rusqlite::types::Value;
 use rusqlite::{named_params, Connection};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::PathBuf;
 use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
-const SQLITE_NAME: &str = "Phoenix.sqlite";
-const CURRENT_DB_VERSION: u32 = 9;
+pub const SQLITE_NAME: &str = "Phoenix.sqlite";
+const CURRENT_DB_VERSION: u32 = 22;
+const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+const DEFAULT_SYNC_INTERVAL_MINUTES: u32 = 15;
+const SECONDS_IN_DAY: u64 = 24 * 60 * 60;
+const DATABASE_BUSY_TIMEOUT_MILLISECONDS: u64 = 5000;
 
 mod m2024_03_31_account_creation;
 mod m2024_04_01_account_timeout_algorithm;
@@ -24,6 +30,19 @@ mod tests;
 mod m2025_01_22_migrate_encryption;
 mod m2025_02_08_settings;
 mod m2025_02_18_account_colours;
+mod m2026_10_18_sync_account_token;
+mod m2026_10_18_sync_account_type;
+mod m2026_10_18_account_sync_target;
+mod m2026_10_18_trash_retention;
+mod m2026_10_18_account_revisions;
+mod m2026_10_18_structured_sync_logs;
+mod m2026_10_18_sync_interval;
+mod m2026_10_18_sync_outbox;
+mod m2026_10_18_sync_failures;
+mod m2026_10_18_manifest_cursor;
+mod m2026_10_18_local_api;
+mod m2026_10_18_webdav_encryption;
+mod m2026_10_18_local_api_tokens;
 
 #[derive(Serialize, Deserialize)]
 pub struct Account {
@@ -38,6 +57,7 @@ pub struct Account {
     pub external_last_updated: Option<u64>,
     pub external_hash: Option<String>,
     pub deleted_at: Option<u64>,
+    pub sync_account_id: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -106,17 +126,71 @@ pub struct SyncAccount {
     pub password: String,
     pub url: String,
     pub token: Option<String>,
+    pub sync_type: SyncType,
+    pub passphrase: Option<String>,
+    pub auth_type: SyncAuthType,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum SyncType {
+    PHOENIX,
+    FOLDER,
+    WEBDAV,
+}
+
+impl SyncType {
+    pub fn num_to_sync_type(num: i8) -> SyncType {
+        match num {
+            1 => SyncType::FOLDER,
+            2 => SyncType::WEBDAV,
+            _ => SyncType::PHOENIX,
+        }
+    }
+
+    pub fn sync_type_to_num(&self) -> i8 {
+        match *self {
+            SyncType::PHOENIX => 0,
+            SyncType::FOLDER => 1,
+            SyncType::WEBDAV => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum SyncAuthType {
+    BASIC,
+    BEARER,
+}
+
+impl SyncAuthType {
+    pub fn num_to_auth_type(num: i8) -> SyncAuthType {
+        match num {
+            1 => SyncAuthType::BEARER,
+            _ => SyncAuthType::BASIC,
+        }
+    }
+
+    pub fn auth_type_to_num(&self) -> i8 {
+        match *self {
+            SyncAuthType::BASIC => 0,
+            SyncAuthType::BEARER => 1,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub enum SyncLogType {
     ERROR = 1,
+    INFO = 2,
+    WARN = 3,
 }
 
 impl SyncLogType {
     pub fn u16_to_sync_log(sync_log: u16) -> Option<SyncLogType> {
         match sync_log {
             1 => Option::from(SyncLogType::ERROR),
+            2 => Option::from(SyncLogType::INFO),
+            3 => Option::from(SyncLogType::WARN),
             _ => None,
         }
     }
@@ -124,6 +198,49 @@ impl SyncLogType {
     pub fn sync_log_to_u16(sync_log_type: SyncLogType) -> Option<u16> {
         match sync_log_type {
             SyncLogType::ERROR => Option::from(1),
+            SyncLogType::INFO => Option::from(2),
+            SyncLogType::WARN => Option::from(3),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum SyncOperation {
+    CONNECT,
+    MANIFEST,
+    CREATE,
+    UPDATE,
+    DELETE,
+    PULL,
+    TRASH,
+    SUMMARY,
+}
+
+impl SyncOperation {
+    pub fn num_to_operation(num: i8) -> Option<SyncOperation> {
+        match num {
+            0 => Some(SyncOperation::CONNECT),
+            1 => Some(SyncOperation::MANIFEST),
+            2 => Some(SyncOperation::CREATE),
+            3 => Some(SyncOperation::UPDATE),
+            4 => Some(SyncOperation::DELETE),
+            5 => Some(SyncOperation::PULL),
+            6 => Some(SyncOperation::TRASH),
+            7 => Some(SyncOperation::SUMMARY),
+            _ => None,
+        }
+    }
+
+    pub fn operation_to_num(&self) -> i8 {
+        match *self {
+            SyncOperation::CONNECT => 0,
+            SyncOperation::MANIFEST => 1,
+            SyncOperation::CREATE => 2,
+            SyncOperation::UPDATE => 3,
+            SyncOperation::DELETE => 4,
+            SyncOperation::PULL => 5,
+            SyncOperation::TRASH => 6,
+            SyncOperation::SUMMARY => 7,
         }
     }
 }
@@ -134,12 +251,154 @@ pub struct SyncLog {
     pub log: String,
     pub log_type: SyncLogType,
     pub timestamp: u64,
+    pub session_id: Option<String>,
+    pub sync_account_id: Option<i32>,
+    pub account_id: Option<i32>,
+    pub external_id: Option<i32>,
+    pub operation: Option<SyncOperation>,
+}
+
+#[derive(Default, Clone)]
+pub struct SyncLogContext {
+    pub session_id: Option<String>,
+    pub sync_account_id: Option<i32>,
+    pub account_id: Option<i32>,
+    pub external_id: Option<i32>,
+    pub operation: Option<SyncOperation>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncLogFilter {
+    pub page: u32,
+    pub per_page: u32,
+    pub log_type: Option<SyncLogType>,
+    pub session_id: Option<String>,
+    pub account_id: Option<i32>,
+}
+
+impl Default for SyncLogFilter {
+    fn default() -> Self {
+        SyncLogFilter {
+            page: 1,
+            per_page: 10,
+            log_type: None,
+            session_id: None,
+            account_id: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SyncLogPage {
+    pub logs: Vec<SyncLog>,
+    pub total: usize,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum OutboxOperation {
+    CREATE,
+    UPDATE,
+    DELETE,
+}
+
+impl OutboxOperation {
+    pub fn num_to_operation(num: i8) -> OutboxOperation {
+        match num {
+            1 => OutboxOperation::UPDATE,
+            2 => OutboxOperation::DELETE,
+            _ => OutboxOperation::CREATE,
+        }
+    }
+
+    pub fn operation_to_num(&self) -> i8 {
+        match *self {
+            OutboxOperation::CREATE => 0,
+            OutboxOperation::UPDATE => 1,
+            OutboxOperation::DELETE => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OutboxEntry {
+    pub id: i32,
+    pub account_id: i32,
+    pub operation: OutboxOperation,
+    pub payload_hash: String,
+    pub idempotency_key: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SyncFailure {
+    pub id: i32,
+    pub sync_account_id: i32,
+    pub account_id: Option<i32>,
+    pub external_id: Option<i32>,
+    pub operation: SyncOperation,
+    pub error: String,
+    pub attempts: u32,
+    pub last_attempt_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum RevisionOrigin {
+    LOCAL,
+    SYNC,
+    IMPORT,
+}
+
+impl RevisionOrigin {
+    pub fn num_to_origin(num: i8) -> RevisionOrigin {
+        match num {
+            1 => RevisionOrigin::SYNC,
+            2 => RevisionOrigin::IMPORT,
+            _ => RevisionOrigin::LOCAL,
+        }
+    }
+
+    pub fn origin_to_num(&self) -> i8 {
+        match *self {
+            RevisionOrigin::LOCAL => 0,
+            RevisionOrigin::SYNC => 1,
+            RevisionOrigin::IMPORT => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AccountRevision {
+    pub id: i32,
+    pub account_id: i32,
+    pub name: String,
+    pub secret: String,
+    pub totp_step: i32,
+    pub otp_digits: i32,
+    pub colour: String,
+    pub algorithm: Option<AccountAlgorithm>,
+    pub origin: RevisionOrigin,
+    pub created_at: u64,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Setting {
     pub id: i32,
     pub theme: Theme,
+    pub trash_retention_days: u32,
+    pub sync_interval_minutes: u32,
+    pub local_api_enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LocalApiClient {
+    pub id: i32,
+    pub name: String,
+    pub created_at: u64,
 }
 
 pub fn initialize_prod_database(database_path: PathBuf, encryption_path: PathBuf) -> Result<Connection, rusqlite::Error> {
@@ -149,8 +408,10 @@ pub fn initialize_prod_database(database_path: PathBuf, encryption_path: PathBuf
     initialize_database(sqlite_path, encryption_path)
 }
 
-fn initialize_database(database_location: PathBuf, encryption_path: PathBuf) -> Result<Connection, rusqlite::Error> {
+pub fn initialize_database(database_location: PathBuf, encryption_path: PathBuf) -> Result<Connection, rusqlite::Error> {
     let mut db = Connection::open(database_location)?;
+    // The app and the CLI can hold the same file open, wait out the other's write instead of failing
+    db.busy_timeout(Duration::from_millis(DATABASE_BUSY_TIMEOUT_MILLISECONDS))?;
     rusqlite::vtab::array::load_module(&db)?;
 
     let mut user_pragma = db.prepare("PRAGMA user_version")?;
@@ -169,17 +430,18 @@ pub fn create_new_account(
     step: &i32,
     colour: &str,
     algorithm: &str,
+    sync_account_id: Option<i32>,
     db: &Connection,
 ) -> Result<Account, rusqlite::Error> {
-    let mut insert_statement = db.prepare("INSERT INTO accounts (name, secret, totp_step, otp_digits, colour, totp_algorithm) VALUES (@name, @secret, @step, @digits, @colour, @algorithm)")?;
-    let mut get_statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm FROM accounts WHERE name = @name AND secret = @secret")?;
+    let mut insert_statement = db.prepare("INSERT INTO accounts (name, secret, totp_step, otp_digits, colour, totp_algorithm, sync_account_id) VALUES (@name, @secret, @step, @digits, @colour, @algorithm, @sync_account_id)")?;
+    let mut get_statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm, sync_account_id FROM accounts WHERE name = @name AND secret = @secret")?;
     let mut final_algorithm = None::<&str>;
 
     if !algorithm.is_empty() {
         final_algorithm = Some(algorithm);
     }
 
-    insert_statement.execute(named_params! { "@name": name, "@secret": secret, "@step": step, "@digits": digits, "@colour": colour, "@algorithm": final_algorithm })?;
+    insert_statement.execute(named_params! { "@name": name, "@secret": secret, "@step": step, "@digits": digits, "@colour": colour, "@algorithm": final_algorithm, "@sync_account_id": sync_account_id })?;
     let mut rows = get_statement.query(named_params! {"@name": name, "@secret": secret})?;
 
     match rows.next()? {
@@ -201,6 +463,7 @@ pub fn create_new_account(
                 external_last_updated: None,
                 external_hash: None,
                 deleted_at: None,
+                sync_account_id: row.get("sync_account_id")?,
             })
         }
         _ => {
@@ -217,17 +480,25 @@ pub fn update_existing_account(
     step: i32,
     colour: &str,
     algorithm: &str,
+    origin: RevisionOrigin,
+    local_updated_at: Option<u64>,
+    now: u64,
     db: &Connection,
 ) -> Result<Account, rusqlite::Error> {
-    let mut update_statement = db.prepare("UPDATE accounts SET name = @name, secret = @secret, totp_step = @step, otp_digits = @digits, colour = @colour, totp_algorithm = @algorithm WHERE id = @id")?;
-    let mut get_statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm FROM accounts WHERE id = @id")?;
     let mut final_algorithm = None::<&str>;
 
     if !algorithm.is_empty() {
         final_algorithm = Some(algorithm);
     }
 
-    update_statement.execute(named_params! { "@id": id, "@name": name, "@secret": secret, "@step": step, "@digits": digits, "@colour": colour, "@algorithm": final_algorithm })?;
+    // The revision and the local change time are only kept if the update they belong to also lands
+    let tx = db.unchecked_transaction()?;
+
+    create_account_revision(id, origin, now, &tx)?;
+    tx.execute("UPDATE accounts SET name = @name, secret = @secret, totp_step = @step, otp_digits = @digits, colour = @colour, totp_algorithm = @algorithm, external_last_updated = COALESCE(@updated, external_last_updated) WHERE id = @id", named_params! { "@id": id, "@name": name, "@secret": secret, "@step": step, "@digits": digits, "@colour": colour, "@algorithm": final_algorithm, "@updated": local_updated_at })?;
+    tx.commit()?;
+
+    let mut get_statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm, sync_account_id FROM accounts WHERE id = @id")?;
     let mut rows = get_statement.query(named_params! {"@id": id })?;
 
     match rows.next()? {
@@ -249,6 +520,7 @@ pub fn update_existing_account(
                 external_last_updated: None,
                 external_hash: None,
                 deleted_at: None,
+                sync_account_id: row.get("sync_account_id")?,
             })
         }
         _ => {
@@ -257,8 +529,57 @@ pub fn update_existing_account(
     }
 }
 
+fn create_account_revision(account_id: &i32, origin: RevisionOrigin, now: u64, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("INSERT INTO account_revisions (account_id, name, secret, totp_step, otp_digits, colour, totp_algorithm, origin, created_at) SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm, @origin, @created_at FROM accounts WHERE id = @id")?;
+    let affected_rows = statement.execute(named_params! {"@origin": origin.origin_to_num(), "@created_at": now, "@id": account_id})?;
+
+    Ok(affected_rows == 1)
+}
+
+pub fn get_account_revisions(account_id: i32, db: &Connection) -> Result<Vec<AccountRevision>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, account_id, name, secret, totp_step, otp_digits, colour, totp_algorithm, origin, created_at FROM account_revisions WHERE account_id = ? ORDER BY created_at DESC, id DESC")?;
+    let mut rows = statement.query([account_id])?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        items.push(row_to_account_revision(row)?);
+    }
+
+    Ok(items)
+}
+
+pub fn get_account_revision(id: i32, db: &Connection) -> Result<Option<AccountRevision>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, account_id, name, secret, totp_step, otp_digits, colour, totp_algorithm, origin, created_at FROM account_revisions WHERE id = ?")?;
+    let mut rows = statement.query([id])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(row_to_account_revision(row)?)),
+        _ => Ok(None),
+    }
+}
+
+fn row_to_account_revision(row: &rusqlite::Row) -> Result<AccountRevision, rusqlite::Error> {
+    let algorithm = match row.get("totp_algorithm")? {
+        Some(string_algorithm) => AccountAlgorithm::string_to_algorithm(string_algorithm),
+        None => None,
+    };
+
+    Ok(AccountRevision {
+        id: row.get("id")?,
+        account_id: row.get("account_id")?,
+        name: row.get("name")?,
+        secret: row.get("secret")?,
+        totp_step: row.get("totp_step")?,
+        otp_digits: row.get("otp_digits")?,
+        colour: row.get("colour")?,
+        algorithm,
+        origin: RevisionOrigin::num_to_origin(row.get("origin")?),
+        created_at: row.get("created_at")?,
+    })
+}
+
 pub fn get_all_accounts(db: &Connection, filter: &str) -> Result<Vec<Account>, rusqlite::Error> {
-    let mut statement = db.prepare("SELECT id, name, totp_step, otp_digits, colour, external_id, external_last_updated, external_hash FROM accounts WHERE name LIKE ? AND deleted_at IS NULL ORDER BY name ASC")?;
+    let mut statement = db.prepare("SELECT id, name, totp_step, otp_digits, colour, external_id, external_last_updated, external_hash, sync_account_id FROM accounts WHERE name LIKE ? AND deleted_at IS NULL ORDER BY name ASC")?;
     let mut rows = statement.query(["%".to_owned() + filter + "%"])?;
     let mut items = Vec::new();
 
@@ -275,6 +596,7 @@ pub fn get_all_accounts(db: &Connection, filter: &str) -> Result<Vec<Account>, r
             external_last_updated: row.get("external_last_updated")?,
             external_hash: row.get("external_hash")?,
             deleted_at: None,
+            sync_account_id: row.get("sync_account_id")?,
         };
 
         items.push(title);
@@ -284,7 +606,7 @@ pub fn get_all_accounts(db: &Connection, filter: &str) -> Result<Vec<Account>, r
 }
 
 pub fn get_account_details_by_id(id: u32, db: &Connection) -> Result<Account, rusqlite::Error> {
-    let mut statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm, external_id, external_last_updated, external_hash FROM accounts WHERE id = ?")?;
+    let mut statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm, external_id, external_last_updated, external_hash, deleted_at, sync_account_id FROM accounts WHERE id = ?")?;
     let mut rows = statement.query([id])?;
 
     match rows.next()? {
@@ -305,7 +627,8 @@ pub fn get_account_details_by_id(id: u32, db: &Connection) -> Result<Account, ru
                 external_id: row.get("external_id")?,
                 external_last_updated: row.get("external_last_updated")?,
                 external_hash: row.get("external_hash")?,
-                deleted_at: None,
+                deleted_at: row.get("deleted_at")?,
+                sync_account_id: row.get("sync_account_id")?,
             })
         }
         _ => Ok(Account {
@@ -320,13 +643,17 @@ pub fn get_account_details_by_id(id: u32, db: &Connection) -> Result<Account, ru
             external_last_updated: None,
             external_hash: None,
             deleted_at: None,
+            sync_account_id: None,
         }),
     }
 }
 
-pub fn get_accounts_without_external_id(db: &Connection) -> Result<Vec<Account>, rusqlite::Error> {
-    let mut statement = db.prepare("SELECT id, name, totp_step, otp_digits, colour, external_id, external_last_updated, external_hash FROM accounts WHERE external_id IS NULL ORDER BY name ASC")?;
-    let mut rows = statement.query([])?;
+pub fn get_accounts_without_external_id(
+    sync_account_id: &i32,
+    db: &Connection,
+) -> Result<Vec<Account>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, name, totp_step, otp_digits, colour, external_id, external_last_updated, external_hash, sync_account_id FROM accounts WHERE external_id IS NULL AND deleted_at IS NULL AND sync_account_id = ? ORDER BY name ASC")?;
+    let mut rows = statement.query([sync_account_id])?;
     let mut items = Vec::new();
 
     while let Some(row) = rows.next()? {
@@ -342,6 +669,7 @@ pub fn get_accounts_without_external_id(db: &Connection) -> Result<Vec<Account>,
             external_last_updated: row.get("external_last_updated")?,
             external_hash: row.get("external_hash")?,
             deleted_at: None,
+            sync_account_id: row.get("sync_account_id")?,
         };
 
         items.push(title);
@@ -350,77 +678,212 @@ pub fn get_accounts_without_external_id(db: &Connection) -> Result<Vec<Account>,
     Ok(items)
 }
 
-pub fn get_account_by_external_id(
-    id: &i32,
+pub fn get_accounts_by_external_ids(
+    ids: Vec<i32>,
+    sync_account_id: &i32,
     db: &Connection,
-) -> Result<Option<Account>, rusqlite::Error> {
-    let mut statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm, external_id, external_last_updated, external_hash FROM accounts WHERE external_id = ?")?;
-    let mut rows = statement.query([id])?;
+) -> Result<Vec<Account>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm, external_id, external_last_updated, external_hash, sync_account_id FROM accounts WHERE external_id IN rarray(@ids) AND sync_account_id = @sync_account_id")?;
 
-    match rows.next()? {
-        Some(row) => {
-            let algorithm = match row.get("totp_algorithm")? {
-                Some(string_algorithm) => AccountAlgorithm::string_to_algorithm(string_algorithm),
-                None => None,
-            };
+    let formatted_ids = Rc::new(ids.iter().copied().map(Value::from).collect::<Vec<Value>>());
+    let mut rows = statement.query(named_params! {"@ids": formatted_ids, "@sync_account_id": sync_account_id})?;
+    let mut items = Vec::new();
 
-            Ok(Some(Account {
-                id: row.get("id")?,
-                name: row.get("name")?,
-                secret: row.get("secret")?,
-                totp_step: row.get("totp_step")?,
-                otp_digits: row.get("otp_digits")?,
-                colour: row.get("colour")?,
-                algorithm,
-                external_id: row.get("external_id")?,
-                external_last_updated: row.get("external_last_updated")?,
-                external_hash: row.get("external_hash")?,
-                deleted_at: None,
-            }))
-        }
-        _ => Ok(None),
+    while let Some(row) = rows.next()? {
+        items.push(row_to_external_account(row)?);
     }
+
+    Ok(items)
 }
 
-pub fn delete_account(account: &Account, db: &Connection) -> Result<bool, rusqlite::Error> {
-    let sync_account = get_main_sync_account(db).unwrap();
+pub fn get_accounts_by_ids(ids: Vec<i32>, db: &Connection) -> Result<Vec<Account>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, name, secret, totp_step, otp_digits, colour, totp_algorithm, external_id, external_last_updated, external_hash, sync_account_id FROM accounts WHERE id IN rarray(@ids) AND deleted_at IS NULL ORDER BY name ASC")?;
 
-    if sync_account.id != 0 && account.deleted_at.is_none() {
-        return soft_delete_account(account, db);
+    let formatted_ids = Rc::new(ids.iter().copied().map(Value::from).collect::<Vec<Value>>());
+    let mut rows = statement.query(named_params! {"@ids": formatted_ids})?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        items.push(row_to_external_account(row)?);
+    }
+
+    Ok(items)
+}
+
+fn row_to_external_account(row: &rusqlite::Row) -> Result<Account, rusqlite::Error> {
+    let algorithm = match row.get("totp_algorithm")? {
+        Some(string_algorithm) => AccountAlgorithm::string_to_algorithm(string_algorithm),
+        None => None,
+    };
+
+    Ok(Account {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        secret: row.get("secret")?,
+        totp_step: row.get("totp_step")?,
+        otp_digits: row.get("otp_digits")?,
+        colour: row.get("colour")?,
+        algorithm,
+        external_id: row.get("external_id")?,
+        external_last_updated: row.get("external_last_updated")?,
+        external_hash: row.get("external_hash")?,
+        deleted_at: None,
+        sync_account_id: row.get("sync_account_id")?,
+    })
+}
+
+pub fn delete_account(account: &Account, now: u64, db: &Connection) -> Result<bool, rusqlite::Error> {
+    soft_delete_account(account, now, db)
+}
+
+pub fn purge_account(account: &Account, db: &Connection) -> Result<bool, rusqlite::Error> {
+    if account.deleted_at.is_none() {
+        return Ok(false);
+    }
+
+    // Expire rather than remove so the next sync can still delete the remote record
+    if account.sync_account_id.is_some() && account.external_id.is_some() {
+        let mut statement = db.prepare("UPDATE accounts SET deleted_at = 0 WHERE id = ?")?;
+        let affected_rows = statement.execute([account.id])?;
+
+        return Ok(affected_rows == 1);
     }
 
     remove_account(account, db)
 }
 
-fn remove_account(account: &Account, db: &Connection) -> Result<bool, rusqlite::Error> {
-    let mut statement = db.prepare("DELETE FROM accounts WHERE id = ?")?;
+pub fn restore_account(account: &Account, now: u64, db: &Connection) -> Result<bool, rusqlite::Error> {
+    if account.deleted_at.is_none() {
+        return Ok(false);
+    }
+
+    let mut statement = db.prepare("UPDATE accounts SET deleted_at = NULL WHERE id = ?")?;
     let affected_rows = statement.execute([account.id])?;
 
+    // Records still held remotely are pushed as an update, the rest are recreated
+    if account.external_id.is_some() {
+        update_local_updated_at(db, account, now)?;
+    }
+
     Ok(affected_rows == 1)
 }
 
-fn soft_delete_account(account: &Account, db: &Connection) -> Result<bool, rusqlite::Error> {
-    let start = SystemTime::now();
-    let since_the_epoch = start
-        .duration_since(UNIX_EPOCH)
-        .expect("Error Generating Unix Time");
+pub fn get_trashed_accounts(deleted_after: u64, db: &Connection) -> Result<Vec<Account>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, name, totp_step, otp_digits, colour, external_id, external_last_updated, external_hash, deleted_at, sync_account_id FROM accounts WHERE deleted_at IS NOT NULL AND deleted_at >= ? ORDER BY deleted_at DESC")?;
+    let mut rows = statement.query([deleted_after])?;
+    let mut items = Vec::new();
 
+    while let Some(row) = rows.next()? {
+        let title: Account = Account {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            secret: "".to_string(),
+            totp_step: row.get("totp_step")?,
+            otp_digits: row.get("otp_digits")?,
+            colour: row.get("colour")?,
+            algorithm: None,
+            external_id: row.get("external_id")?,
+            external_last_updated: row.get("external_last_updated")?,
+            external_hash: row.get("external_hash")?,
+            deleted_at: row.get("deleted_at")?,
+            sync_account_id: row.get("sync_account_id")?,
+        };
+
+        items.push(title);
+    }
+
+    Ok(items)
+}
+
+pub fn get_trash_cutoff(now: u64, db: &Connection) -> Result<u64, rusqlite::Error> {
+    let settings = get_settings(db)?;
+
+    Ok(now.saturating_sub(settings.trash_retention_days as u64 * SECONDS_IN_DAY))
+}
+
+fn remove_account(account: &Account, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let tx = db.unchecked_transaction()?;
+
+    tx.execute("DELETE FROM account_revisions WHERE account_id = ?", [account.id])?;
+    tx.execute("DELETE FROM sync_outbox WHERE account_id = ?", [account.id])?;
+    tx.execute("DELETE FROM sync_failures WHERE account_id = ?", [account.id])?;
+    let affected_rows = tx.execute("DELETE FROM accounts WHERE id = ?", [account.id])?;
+
+    tx.commit()?;
+
+    Ok(affected_rows == 1)
+}
+
+fn soft_delete_account(account: &Account, now: u64, db: &Connection) -> Result<bool, rusqlite::Error> {
     let mut statement = db.prepare("UPDATE accounts SET deleted_at = ? WHERE id = ?")?;
-    let affected_rows = statement.execute([since_the_epoch.as_secs(), account.id as u64])?;
+    let affected_rows = statement.execute([now, account.id as u64])?;
 
     Ok(affected_rows == 1)
 }
 
-pub fn delete_accounts_without_external_ids(
+pub fn get_accounts_missing_from_manifest(
     ids: Vec<i32>,
+    sync_account_id: &i32,
     db: &Connection,
-) -> Result<usize, rusqlite::Error> {
+) -> Result<Vec<Account>, rusqlite::Error> {
     let mut statement = db.prepare(
-        "DELETE FROM accounts WHERE external_id IS NOT NULL AND external_id NOT IN rarray(?)",
+        "SELECT id, name, totp_step, otp_digits, colour, external_id, external_last_updated, external_hash, sync_account_id FROM accounts WHERE external_id IS NOT NULL AND external_id NOT IN rarray(@ids) AND sync_account_id = @sync_account_id AND deleted_at IS NULL ORDER BY name ASC",
     )?;
 
     let formatted_ids = Rc::new(ids.iter().copied().map(Value::from).collect::<Vec<Value>>());
-    let affected_rows = statement.execute([formatted_ids])?;
+    let mut rows = statement.query(named_params! {"@ids": formatted_ids, "@sync_account_id": sync_account_id})?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        let title: Account = Account {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            secret: "".to_string(),
+            totp_step: row.get("totp_step")?,
+            otp_digits: row.get("otp_digits")?,
+            colour: row.get("colour")?,
+            algorithm: None,
+            external_id: row.get("external_id")?,
+            external_last_updated: row.get("external_last_updated")?,
+            external_hash: row.get("external_hash")?,
+            deleted_at: None,
+            sync_account_id: row.get("sync_account_id")?,
+        };
+
+        items.push(title);
+    }
+
+    Ok(items)
+}
+
+pub fn count_synced_accounts(sync_account_id: &i32, db: &Connection) -> Result<usize, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT COUNT(id) FROM accounts WHERE external_id IS NOT NULL AND sync_account_id = ? AND deleted_at IS NULL")?;
+
+    statement.query_row([sync_account_id], |row| row.get(0))
+}
+
+pub fn trash_account(account: &Account, now: u64, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("UPDATE accounts SET deleted_at = @deleted_at, external_id = NULL, external_last_updated = NULL, external_hash = NULL WHERE id = @id")?;
+    let affected_rows = statement.execute(named_params! {"@deleted_at": now, "@id": account.id})?;
+
+    Ok(affected_rows == 1)
+}
+
+pub fn clear_remote_account(account: &Account, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("UPDATE accounts SET external_id = NULL, external_last_updated = NULL, external_hash = NULL WHERE id = ?")?;
+    let affected_rows = statement.execute([account.id])?;
+
+    Ok(affected_rows == 1)
+}
+
+pub fn purge_trashed_accounts(deleted_before: u64, db: &Connection) -> Result<usize, rusqlite::Error> {
+    let tx = db.unchecked_transaction()?;
+
+    let affected_rows = tx.execute("DELETE FROM accounts WHERE deleted_at IS NOT NULL AND deleted_at < ? AND external_id IS NULL", [deleted_before])?;
+    tx.execute("DELETE FROM account_revisions WHERE account_id NOT IN (SELECT id FROM accounts)", [])?;
+    tx.execute("DELETE FROM sync_outbox WHERE account_id NOT IN (SELECT id FROM accounts)", [])?;
+
+    tx.commit()?;
 
     Ok(affected_rows)
 }
@@ -439,58 +902,186 @@ pub fn create_sync_account(
     username: &str,
     password: &str,
     url: &str,
+    sync_type: &SyncType,
+    passphrase: Option<&str>,
+    auth_type: &SyncAuthType,
     db: &Connection,
 ) -> Result<SyncAccount, rusqlite::Error> {
     let mut statement = db.prepare(
-        "INSERT INTO sync_accounts (username, password, url) VALUES (@username, @password, @url)",
+        "INSERT INTO sync_accounts (username, password, url, sync_type, passphrase, auth_type) VALUES (@username, @password, @url, @sync_type, @passphrase, @auth_type)",
     )?;
 
     statement.execute(
-        named_params! { "@username": username, "@password": password, "@url": url },
+        named_params! { "@username": username, "@password": password, "@url": url, "@sync_type": sync_type.sync_type_to_num(), "@passphrase": passphrase, "@auth_type": auth_type.auth_type_to_num() },
     )?;
 
-    Ok(get_main_sync_account(db).unwrap())
+    get_sync_account_by_id(db.last_insert_rowid() as i32, db)
 }
 
 pub fn update_sync_account(
     sync_account: SyncAccount,
     db: &Connection,
 ) -> Result<bool, rusqlite::Error> {
-    let mut statement = db.prepare("UPDATE sync_accounts SET username = @username, password = @password, url = @url WHERE id = @id")?;
-    let affected_rows = statement.execute(named_params! {"@id": sync_account.id, "@username": sync_account.username, "@password": sync_account.password, "@url": sync_account.url})?;
+    // The cursor belongs to the previous server details, so start over with a full manifest
+    let mut statement = db.prepare("UPDATE sync_accounts SET username = @username, password = @password, url = @url, token = @token, sync_type = @sync_type, passphrase = @passphrase, auth_type = @auth_type, manifest_cursor = NULL WHERE id = @id")?;
+    let affected_rows = statement.execute(named_params! {"@id": sync_account.id, "@username": sync_account.username, "@password": sync_account.password, "@url": sync_account.url, "@token": sync_account.token, "@sync_type": sync_account.sync_type.sync_type_to_num(), "@passphrase": sync_account.passphrase, "@auth_type": sync_account.auth_type.auth_type_to_num()})?;
+
+    Ok(affected_rows == 1)
+}
+
+pub fn update_sync_account_token(
+    id: i32,
+    token: Option<String>,
+    db: &Connection,
+) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("UPDATE sync_accounts SET token = @token WHERE id = @id")?;
+    let affected_rows = statement.execute(named_params! {"@id": id, "@token": token})?;
+
+    Ok(affected_rows == 1)
+}
+
+pub fn get_manifest_cursor(id: &i32, db: &Connection) -> Result<Option<String>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT manifest_cursor FROM sync_accounts WHERE id = ?")?;
+    let mut rows = statement.query([id])?;
+
+    match rows.next()? {
+        Some(row) => row.get("manifest_cursor"),
+        None => Ok(None),
+    }
+}
+
+pub fn save_manifest_cursor(
+    id: &i32,
+    cursor: Option<String>,
+    db: &Connection,
+) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("UPDATE sync_accounts SET manifest_cursor = @cursor WHERE id = @id")?;
+    let affected_rows = statement.execute(named_params! {"@id": id, "@cursor": cursor})?;
 
     Ok(affected_rows == 1)
 }
 
 pub fn delete_sync_account(id: i32, db: &Connection) -> Result<bool, rusqlite::Error> {
-    let mut statement = db.prepare("DELETE FROM sync_accounts WHERE id = ?")?;
-    let affected_rows = statement.execute([id])?;
+    // Detaching accounts and removing the target succeed or fail together
+    let tx = db.unchecked_transaction()?;
+
+    tx.execute("DELETE FROM sync_outbox WHERE account_id IN (SELECT id FROM accounts WHERE sync_account_id = ?)", [id])?;
+    tx.execute("DELETE FROM sync_failures WHERE sync_account_id = ?", [id])?;
+    tx.execute("UPDATE accounts SET sync_account_id = NULL, external_id = NULL, external_last_updated = NULL, external_hash = NULL WHERE sync_account_id = ?", [id])?;
+    let affected_rows = tx.execute("DELETE FROM sync_accounts WHERE id = ?", [id])?;
+
+    tx.commit()?;
 
     Ok(affected_rows == 1)
 }
 
 pub fn get_main_sync_account(db: &Connection) -> Result<SyncAccount, rusqlite::Error> {
-    let mut statement =
-        db.prepare("SELECT id, username, password, url FROM sync_accounts LIMIT 1")?;
+    let mut statement = db.prepare(
+        "SELECT id, username, password, url, token, sync_type, passphrase, auth_type FROM sync_accounts ORDER BY id ASC LIMIT 1",
+    )?;
     let mut rows = statement.query([])?;
 
     match rows.next()? {
-        Some(row) => {
-            Ok(SyncAccount {
-                id: row.get("id")?,
-                username: row.get("username")?,
-                password: row.get("password")?,
-                url: row.get("url")?,
-                token: None,
-            })
-        }
-        _ => Ok(SyncAccount {
-            id: 0,
-            username: "".to_string(),
-            password: "".to_string(),
-            url: "".to_string(),
-            token: None,
-        }),
+        Some(row) => row_to_sync_account(row),
+        _ => Ok(empty_sync_account()),
+    }
+}
+
+pub fn get_sync_account_by_id(id: i32, db: &Connection) -> Result<SyncAccount, rusqlite::Error> {
+    let mut statement = db.prepare(
+        "SELECT id, username, password, url, token, sync_type, passphrase, auth_type FROM sync_accounts WHERE id = ?",
+    )?;
+    let mut rows = statement.query([id])?;
+
+    match rows.next()? {
+        Some(row) => row_to_sync_account(row),
+        _ => Ok(empty_sync_account()),
+    }
+}
+
+pub fn get_sync_accounts(db: &Connection) -> Result<Vec<SyncAccount>, rusqlite::Error> {
+    let mut statement = db.prepare(
+        "SELECT id, username, password, url, token, sync_type, passphrase, auth_type FROM sync_accounts ORDER BY id ASC",
+    )?;
+    let mut rows = statement.query([])?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        items.push(row_to_sync_account(row)?);
+    }
+
+    Ok(items)
+}
+
+pub fn set_account_sync_account(
+    account_id: i32,
+    sync_account_id: Option<i32>,
+    now: u64,
+    db: &Connection,
+) -> Result<bool, rusqlite::Error> {
+    let account = get_account_details_by_id(account_id as u32, db)?;
+
+    if account.id == 0 {
+        return Ok(false);
+    }
+
+    if account.sync_account_id == sync_account_id {
+        return Ok(true);
+    }
+
+    // The deleted copy and the move succeed or fail together
+    let tx = db.unchecked_transaction()?;
+
+    // Leave a deleted copy behind so the previous target removes its remote record on next sync
+    if account.sync_account_id.is_some() && account.external_id.is_some() {
+        let mut copy_statement = tx.prepare("INSERT INTO accounts (name, secret, totp_step, otp_digits, colour, totp_algorithm, external_id, external_last_updated, external_hash, deleted_at, sync_account_id) SELECT name, secret, totp_step, otp_digits, colour, totp_algorithm, external_id, external_last_updated, external_hash, @deleted_at, sync_account_id FROM accounts WHERE id = @id")?;
+        copy_statement.execute(named_params! {"@deleted_at": now, "@id": account.id})?;
+    }
+
+    let affected_rows = tx.execute(
+        "UPDATE accounts SET sync_account_id = @sync_account_id, external_id = NULL, external_last_updated = NULL, external_hash = NULL WHERE id = @id",
+        named_params! {"@sync_account_id": sync_account_id, "@id": account.id},
+    )?;
+
+    tx.commit()?;
+
+    Ok(affected_rows == 1)
+}
+
+pub fn assign_unassigned_accounts(
+    sync_account_id: i32,
+    db: &Connection,
+) -> Result<usize, rusqlite::Error> {
+    let mut statement =
+        db.prepare("UPDATE accounts SET sync_account_id = ? WHERE sync_account_id IS NULL")?;
+    let affected_rows = statement.execute([sync_account_id])?;
+
+    Ok(affected_rows)
+}
+
+fn row_to_sync_account(row: &rusqlite::Row) -> Result<SyncAccount, rusqlite::Error> {
+    Ok(SyncAccount {
+        id: row.get("id")?,
+        username: row.get("username")?,
+        password: row.get("password")?,
+        url: row.get("url")?,
+        token: row.get("token")?,
+        sync_type: SyncType::num_to_sync_type(row.get("sync_type")?),
+        passphrase: row.get("passphrase")?,
+        auth_type: SyncAuthType::num_to_auth_type(row.get("auth_type")?),
+    })
+}
+
+fn empty_sync_account() -> SyncAccount {
+    SyncAccount {
+        id: 0,
+        username: "".to_string(),
+        password: "".to_string(),
+        url: "".to_string(),
+        token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     }
 }
 
@@ -508,13 +1099,8 @@ pub fn set_remote_account(
 pub fn update_local_updated_at(
     db: &Connection,
     account: &Account,
+    timestamp: u64,
 ) -> Result<bool, rusqlite::Error> {
-    let start = SystemTime::now();
-    let since_the_epoch = start
-        .duration_since(UNIX_EPOCH)
-        .expect("Could not generate UNIX time");
-    let timestamp = since_the_epoch.as_secs();
-
     let mut statement =
         db.prepare("UPDATE accounts SET external_last_updated = @updated WHERE id = @id")?;
     let affected_rows =
@@ -523,9 +1109,12 @@ pub fn update_local_updated_at(
     Ok(affected_rows == 1)
 }
 
-pub fn get_soft_deleted_accounts(db: &Connection) -> Result<Vec<Account>, rusqlite::Error> {
-    let mut statement = db.prepare("SELECT id, name, totp_step, otp_digits, colour, external_id, external_last_updated, external_hash, deleted_at FROM accounts WHERE deleted_at IS NOT NULL")?;
-    let mut rows = statement.query([])?;
+pub fn get_soft_deleted_accounts(
+    sync_account_id: &i32,
+    db: &Connection,
+) -> Result<Vec<Account>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, name, totp_step, otp_digits, colour, external_id, external_last_updated, external_hash, deleted_at, sync_account_id FROM accounts WHERE deleted_at IS NOT NULL AND sync_account_id = ?")?;
+    let mut rows = statement.query([sync_account_id])?;
     let mut items = Vec::new();
 
     while let Some(row) = rows.next()? {
@@ -541,6 +1130,7 @@ pub fn get_soft_deleted_accounts(db: &Connection) -> Result<Vec<Account>, rusqli
             external_last_updated: row.get("external_last_updated")?,
             external_hash: row.get("external_hash")?,
             deleted_at: row.get("deleted_at")?,
+            sync_account_id: row.get("sync_account_id")?,
         };
 
         items.push(title);
@@ -553,34 +1143,54 @@ pub fn create_sync_log(
     db: &Connection,
     log: String,
     log_type: SyncLogType,
+    context: SyncLogContext,
+    timestamp: u64,
 ) -> Result<SyncLog, rusqlite::Error> {
-    let start = SystemTime::now();
-    let since_the_epoch = start
-        .duration_since(UNIX_EPOCH)
-        .expect("Could not generate UNIX time");
-    let timestamp = since_the_epoch.as_secs();
     let final_log_type = SyncLogType::sync_log_to_u16(log_type.clone());
+    let operation = context.operation.as_ref().map(|operation| operation.operation_to_num());
 
     let mut statement = db.prepare(
-        "INSERT INTO sync_logs (log, log_type, timestamp) VALUES (@log, @log_type, @timestamp)",
+        "INSERT INTO sync_logs (log, log_type, timestamp, session_id, sync_account_id, account_id, external_id, operation) VALUES (@log, @log_type, @timestamp, @session_id, @sync_account_id, @account_id, @external_id, @operation)",
     )?;
     statement.execute(
-        named_params! { "@log": log, "@log_type": final_log_type, "@timestamp": timestamp},
+        named_params! { "@log": log, "@log_type": final_log_type, "@timestamp": timestamp, "@session_id": context.session_id, "@sync_account_id": context.sync_account_id, "@account_id": context.account_id, "@external_id": context.external_id, "@operation": operation},
     )?;
 
     Ok(SyncLog {
-        id: 0,
+        id: db.last_insert_rowid() as i32,
         log,
         log_type,
         timestamp,
+        session_id: context.session_id,
+        sync_account_id: context.sync_account_id,
+        account_id: context.account_id,
+        external_id: context.external_id,
+        operation: context.operation,
     })
 }
 
-pub fn get_sync_logs(db: &Connection) -> Result<Vec<SyncLog>, rusqlite::Error> {
+pub fn get_sync_logs(db: &Connection, filter: &SyncLogFilter) -> Result<SyncLogPage, rusqlite::Error> {
+    let log_type = filter
+        .log_type
+        .clone()
+        .and_then(SyncLogType::sync_log_to_u16);
+    let page = filter.page.max(1);
+    let per_page = filter.per_page.max(1);
+
+    let mut count_statement = db.prepare(
+        "SELECT COUNT(id) FROM sync_logs WHERE (@log_type IS NULL OR log_type = @log_type) AND (@session_id IS NULL OR session_id = @session_id) AND (@account_id IS NULL OR account_id = @account_id)",
+    )?;
+    let total = count_statement.query_row(
+        named_params! {"@log_type": log_type, "@session_id": filter.session_id, "@account_id": filter.account_id},
+        |row| row.get(0),
+    )?;
+
     let mut statement = db.prepare(
-        "SELECT id, log, log_type, timestamp FROM sync_logs ORDER BY timestamp DESC LIMIT 10",
+        "SELECT id, log, log_type, timestamp, session_id, sync_account_id, account_id, external_id, operation FROM sync_logs WHERE (@log_type IS NULL OR log_type = @log_type) AND (@session_id IS NULL OR session_id = @session_id) AND (@account_id IS NULL OR account_id = @account_id) ORDER BY timestamp DESC, id DESC LIMIT @limit OFFSET @offset",
+    )?;
+    let mut rows = statement.query(
+        named_params! {"@log_type": log_type, "@session_id": filter.session_id, "@account_id": filter.account_id, "@limit": per_page, "@offset": (page - 1) * per_page},
     )?;
-    let mut rows = statement.query([])?;
     let mut items = Vec::new();
 
     while let Some(row) = rows.next()? {
@@ -588,23 +1198,256 @@ pub fn get_sync_logs(db: &Connection) -> Result<Vec<SyncLog>, rusqlite::Error> {
             Some(u16_log_type) => SyncLogType::u16_to_sync_log(u16_log_type).unwrap(),
             None => SyncLogType::ERROR,
         };
+        let operation = match row.get("operation")? {
+            Some(num_operation) => SyncOperation::num_to_operation(num_operation),
+            None => None,
+        };
 
         let log: SyncLog = SyncLog {
             id: row.get("id")?,
             log: row.get("log")?,
             log_type,
             timestamp: row.get("timestamp")?,
+            session_id: row.get("session_id")?,
+            sync_account_id: row.get("sync_account_id")?,
+            account_id: row.get("account_id")?,
+            external_id: row.get("external_id")?,
+            operation,
         };
 
         items.push(log);
     }
 
+    Ok(SyncLogPage {
+        logs: items,
+        total,
+        page,
+        per_page,
+    })
+}
+
+pub fn prune_sync_logs(older_than: u64, keep: usize, db: &Connection) -> Result<usize, rusqlite::Error> {
+    let mut statement = db.prepare(
+        "DELETE FROM sync_logs WHERE timestamp < @older_than OR id NOT IN (SELECT id FROM sync_logs ORDER BY timestamp DESC, id DESC LIMIT @keep)",
+    )?;
+    let affected_rows = statement.execute(named_params! {"@older_than": older_than, "@keep": keep})?;
+
+    Ok(affected_rows)
+}
+
+pub fn account_payload_hash(account: &Account) -> String {
+    let algorithm = match &account.algorithm {
+        Some(algorithm) => algorithm.algorithm_to_string(),
+        None => "".to_string(),
+    };
+    let payload = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        account.name, account.secret, account.totp_step, account.otp_digits, account.colour, algorithm
+    );
+
+    Sha256::digest(payload.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn queue_outbox_entry(
+    account_id: &i32,
+    operation: OutboxOperation,
+    now: u64,
+    db: &Connection,
+) -> Result<Option<OutboxEntry>, rusqlite::Error> {
+    let account = get_account_details_by_id(*account_id as u32, db)?;
+
+    if account.id == 0 || account.sync_account_id.is_none() {
+        return Ok(None);
+    }
+
+    let payload_hash = account_payload_hash(&account);
+    let existing = get_outbox_entry(account_id, db)?;
+
+    let existing = match existing {
+        Some(existing) => existing,
+        None => {
+            let mut statement = db.prepare("INSERT INTO sync_outbox (account_id, operation, payload_hash, idempotency_key, created_at) VALUES (@account_id, @operation, @payload_hash, lower(hex(randomblob(16))), @created_at)")?;
+            statement.execute(named_params! {"@account_id": account_id, "@operation": operation.operation_to_num(), "@payload_hash": payload_hash, "@created_at": now})?;
+
+            return get_outbox_entry(account_id, db);
+        }
+    };
+
+    // An unsent create only needs a fresh payload, and is dropped entirely if the account is deleted before it ever left
+    let keep_existing = match (&existing.operation, &operation) {
+        (OutboxOperation::CREATE, OutboxOperation::DELETE) if existing.attempts == 0 => {
+            db.prepare("DELETE FROM sync_outbox WHERE id = ?")?.execute([existing.id])?;
+            return Ok(None);
+        }
+        (OutboxOperation::CREATE, _) => true,
+        (existing_operation, new_operation) => existing_operation == new_operation,
+    };
+
+    match keep_existing {
+        true => {
+            let mut statement = db.prepare("UPDATE sync_outbox SET payload_hash = @payload_hash WHERE id = @id")?;
+            statement.execute(named_params! {"@payload_hash": payload_hash, "@id": existing.id})?;
+        }
+        false => {
+            let mut statement = db.prepare("UPDATE sync_outbox SET operation = @operation, payload_hash = @payload_hash, idempotency_key = lower(hex(randomblob(16))), attempts = 0, last_error = NULL WHERE id = @id")?;
+            statement.execute(named_params! {"@operation": operation.operation_to_num(), "@payload_hash": payload_hash, "@id": existing.id})?;
+        }
+    }
+
+    get_outbox_entry(account_id, db)
+}
+
+pub fn get_outbox_entry(account_id: &i32, db: &Connection) -> Result<Option<OutboxEntry>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, account_id, operation, payload_hash, idempotency_key, attempts, last_error, created_at FROM sync_outbox WHERE account_id = ?")?;
+    let mut rows = statement.query([account_id])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(row_to_outbox_entry(row)?)),
+        _ => Ok(None),
+    }
+}
+
+pub fn get_outbox_entries(sync_account_id: &i32, db: &Connection) -> Result<Vec<OutboxEntry>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT sync_outbox.id, sync_outbox.account_id, sync_outbox.operation, sync_outbox.payload_hash, sync_outbox.idempotency_key, sync_outbox.attempts, sync_outbox.last_error, sync_outbox.created_at FROM sync_outbox INNER JOIN accounts ON accounts.id = sync_outbox.account_id WHERE accounts.sync_account_id = ? ORDER BY sync_outbox.id ASC")?;
+    let mut rows = statement.query([sync_account_id])?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        items.push(row_to_outbox_entry(row)?);
+    }
+
+    Ok(items)
+}
+
+pub fn complete_outbox_entry(entry: &OutboxEntry, sent_payload_hash: &str, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("DELETE FROM sync_outbox WHERE id = @id AND operation = @operation AND payload_hash = @payload_hash")?;
+    let affected_rows = statement.execute(named_params! {"@id": entry.id, "@operation": entry.operation.operation_to_num(), "@payload_hash": sent_payload_hash})?;
+
+    if affected_rows == 1 {
+        return Ok(true);
+    }
+
+    // The account changed while the request was in flight, so push the newer payload on the next run
+    let mut requeue_statement = db.prepare("UPDATE sync_outbox SET operation = @update, idempotency_key = lower(hex(randomblob(16))), attempts = 0, last_error = NULL WHERE id = @id AND operation = @create")?;
+    requeue_statement.execute(named_params! {"@id": entry.id, "@update": OutboxOperation::UPDATE.operation_to_num(), "@create": OutboxOperation::CREATE.operation_to_num()})?;
+
+    Ok(false)
+}
+
+pub fn fail_outbox_entry(id: &i32, error: &str, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("UPDATE sync_outbox SET attempts = attempts + 1, last_error = @last_error WHERE id = @id")?;
+    let affected_rows = statement.execute(named_params! {"@id": id, "@last_error": error})?;
+
+    Ok(affected_rows == 1)
+}
+
+pub fn get_sync_failure(
+    sync_account_id: &i32,
+    account_id: Option<i32>,
+    external_id: Option<i32>,
+    db: &Connection,
+) -> Result<Option<SyncFailure>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, sync_account_id, account_id, external_id, operation, error, attempts, last_attempt_at FROM sync_failures WHERE sync_account_id = @sync_account_id AND account_id IS @account_id AND external_id IS @external_id")?;
+    let mut rows = statement.query(named_params! {"@sync_account_id": sync_account_id, "@account_id": account_id, "@external_id": external_id})?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(row_to_sync_failure(row)?)),
+        _ => Ok(None),
+    }
+}
+
+pub fn get_sync_failures(sync_account_id: &i32, db: &Connection) -> Result<Vec<SyncFailure>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, sync_account_id, account_id, external_id, operation, error, attempts, last_attempt_at FROM sync_failures WHERE sync_account_id = ? ORDER BY id ASC")?;
+    let mut rows = statement.query([sync_account_id])?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        items.push(row_to_sync_failure(row)?);
+    }
+
     Ok(items)
 }
 
+pub fn record_sync_failure(
+    sync_account_id: &i32,
+    account_id: Option<i32>,
+    external_id: Option<i32>,
+    operation: SyncOperation,
+    error: &str,
+    now: u64,
+    db: &Connection,
+) -> Result<SyncFailure, rusqlite::Error> {
+    match get_sync_failure(sync_account_id, account_id, external_id, db)? {
+        Some(existing) => {
+            let mut statement = db.prepare("UPDATE sync_failures SET operation = @operation, error = @error, attempts = attempts + 1, last_attempt_at = @last_attempt_at WHERE id = @id")?;
+            statement.execute(named_params! {"@operation": operation.operation_to_num(), "@error": error, "@last_attempt_at": now, "@id": existing.id})?;
+        }
+        None => {
+            let mut statement = db.prepare("INSERT INTO sync_failures (sync_account_id, account_id, external_id, operation, error, last_attempt_at) VALUES (@sync_account_id, @account_id, @external_id, @operation, @error, @last_attempt_at)")?;
+            statement.execute(named_params! {"@sync_account_id": sync_account_id, "@account_id": account_id, "@external_id": external_id, "@operation": operation.operation_to_num(), "@error": error, "@last_attempt_at": now})?;
+        }
+    }
+
+    Ok(get_sync_failure(sync_account_id, account_id, external_id, db)?.unwrap())
+}
+
+pub fn clear_sync_failure(
+    sync_account_id: &i32,
+    account_id: Option<i32>,
+    external_id: Option<i32>,
+    db: &Connection,
+) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("DELETE FROM sync_failures WHERE sync_account_id = @sync_account_id AND account_id IS @account_id AND external_id IS @external_id")?;
+    let affected_rows = statement.execute(named_params! {"@sync_account_id": sync_account_id, "@account_id": account_id, "@external_id": external_id})?;
+
+    Ok(affected_rows == 1)
+}
+
+pub fn clear_stale_sync_failures(
+    manifest_ids: Vec<i32>,
+    sync_account_id: &i32,
+    db: &Connection,
+) -> Result<usize, rusqlite::Error> {
+    let mut statement = db.prepare("DELETE FROM sync_failures WHERE sync_account_id = @sync_account_id AND account_id IS NULL AND external_id NOT IN rarray(@ids)")?;
+
+    let formatted_ids = Rc::new(manifest_ids.iter().copied().map(Value::from).collect::<Vec<Value>>());
+    let affected_rows = statement.execute(named_params! {"@ids": formatted_ids, "@sync_account_id": sync_account_id})?;
+
+    Ok(affected_rows)
+}
+
+fn row_to_sync_failure(row: &rusqlite::Row) -> Result<SyncFailure, rusqlite::Error> {
+    Ok(SyncFailure {
+        id: row.get("id")?,
+        sync_account_id: row.get("sync_account_id")?,
+        account_id: row.get("account_id")?,
+        external_id: row.get("external_id")?,
+        operation: SyncOperation::num_to_operation(row.get("operation")?).unwrap_or(SyncOperation::PULL),
+        error: row.get("error")?,
+        attempts: row.get("attempts")?,
+        last_attempt_at: row.get("last_attempt_at")?,
+    })
+}
+
+fn row_to_outbox_entry(row: &rusqlite::Row) -> Result<OutboxEntry, rusqlite::Error> {
+    Ok(OutboxEntry {
+        id: row.get("id")?,
+        account_id: row.get("account_id")?,
+        operation: OutboxOperation::num_to_operation(row.get("operation")?),
+        payload_hash: row.get("payload_hash")?,
+        idempotency_key: row.get("idempotency_key")?,
+        attempts: row.get("attempts")?,
+        last_error: row.get("last_error")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
 pub fn get_settings(db: &Connection) -> Result<Setting, rusqlite::Error> {
     let mut statement = db.prepare(
-        "SELECT id, theme FROM settings ORDER BY id DESC LIMIT 1",
+        "SELECT id, theme, trash_retention_days, sync_interval_minutes, local_api_enabled FROM settings ORDER BY id DESC LIMIT 1",
     )?;
 
     let mut rows = statement.query([])?;
@@ -613,11 +1456,17 @@ pub fn get_settings(db: &Connection) -> Result<Setting, rusqlite::Error> {
             Ok(Setting {
                 id: row.get("id")?,
                 theme: Theme::num_to_theme(row.get("theme")?),
+                trash_retention_days: row.get("trash_retention_days")?,
+                sync_interval_minutes: row.get("sync_interval_minutes")?,
+                local_api_enabled: row.get("local_api_enabled")?,
             })
         }
         _ => Ok(Setting {
             id: 0,
             theme: Theme::DEFAULT,
+            trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+            sync_interval_minutes: DEFAULT_SYNC_INTERVAL_MINUTES,
+            local_api_enabled: false,
         }),
     }
 }
@@ -632,6 +1481,109 @@ pub fn save_settings(db: &Connection, theme: Theme) -> Result<Setting, rusqlite:
     return create_settings(db, theme);
 }
 
+pub fn save_trash_retention(db: &Connection, trash_retention_days: u32) -> Result<Setting, rusqlite::Error> {
+    let settings = get_settings(db).unwrap();
+
+    if settings.id == 0 {
+        create_settings(db, settings.theme)?;
+    }
+
+    let mut statement = db.prepare(
+        "UPDATE settings SET trash_retention_days = @trash_retention_days WHERE id = @id"
+    )?;
+    statement.execute(
+        named_params! { "@id": get_settings(db)?.id, "@trash_retention_days": trash_retention_days}
+    )?;
+
+    Ok(get_settings(db).unwrap())
+}
+
+pub fn save_sync_interval(db: &Connection, sync_interval_minutes: u32) -> Result<Setting, rusqlite::Error> {
+    let settings = get_settings(db).unwrap();
+
+    if settings.id == 0 {
+        create_settings(db, settings.theme)?;
+    }
+
+    let mut statement = db.prepare(
+        "UPDATE settings SET sync_interval_minutes = @sync_interval_minutes WHERE id = @id"
+    )?;
+    statement.execute(
+        named_params! { "@id": get_settings(db)?.id, "@sync_interval_minutes": sync_interval_minutes}
+    )?;
+
+    Ok(get_settings(db).unwrap())
+}
+
+pub fn save_local_api_enabled(db: &Connection, local_api_enabled: bool) -> Result<Setting, rusqlite::Error> {
+    let settings = get_settings(db).unwrap();
+
+    if settings.id == 0 {
+        create_settings(db, settings.theme)?;
+    }
+
+    let mut statement = db.prepare(
+        "UPDATE settings SET local_api_enabled = @local_api_enabled WHERE id = @id"
+    )?;
+    statement.execute(
+        named_params! { "@id": get_settings(db)?.id, "@local_api_enabled": local_api_enabled}
+    )?;
+
+    Ok(get_settings(db).unwrap())
+}
+
+pub fn get_local_api_clients(db: &Connection) -> Result<Vec<LocalApiClient>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, name, created_at FROM local_api_clients ORDER BY name ASC")?;
+    let mut rows = statement.query([])?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        items.push(LocalApiClient {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            created_at: row.get("created_at")?,
+        });
+    }
+
+    Ok(items)
+}
+
+pub fn local_api_client_allowed(client_token: &str, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let token_hash = local_api_token_hash(client_token);
+    let mut statement = db.prepare("SELECT token_hash FROM local_api_clients")?;
+    let mut rows = statement.query([])?;
+    let mut allowed = false;
+
+    // Every stored hash is compared so the time taken doesn't depend on which client matched
+    while let Some(row) = rows.next()? {
+        let stored_hash: String = row.get("token_hash")?;
+        allowed |= encryption::constant_time_eq(stored_hash.as_bytes(), token_hash.as_bytes());
+    }
+
+    Ok(allowed)
+}
+
+pub fn allow_local_api_client(name: &str, client_token: &str, now: u64, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("INSERT INTO local_api_clients (name, token_hash, created_at) VALUES (@name, @token_hash, @created_at)")?;
+    let result = statement.execute(named_params! {"@name": name, "@token_hash": local_api_token_hash(client_token), "@created_at": now})?;
+
+    Ok(result == 1)
+}
+
+fn local_api_token_hash(client_token: &str) -> String {
+    Sha256::digest(client_token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn remove_local_api_client(id: i32, db: &Connection) -> Result<bool, rusqlite::Error> {
+    let mut statement = db.prepare("DELETE FROM local_api_clients WHERE id = ?")?;
+    let result = statement.execute([id])?;
+
+    Ok(result == 1)
+}
+
 fn create_settings(db: &Connection, theme: Theme) -> Result<Setting, rusqlite::Error> {
     let mut statement = db.prepare(
         "INSERT INTO settings (theme) VALUES (@theme)",
@@ -673,7 +1625,33 @@ fn update_database(db: &mut Connection, existing_version: u32, encryption_path:
         m2025_02_08_settings::migrate(db, existing_version)
             .expect("FAILED: Settings - ");
         m2025_02_18_account_colours::migrate(db, existing_version)
-            .expect("Failed: Account Colours - ")
+            .expect("Failed: Account Colours - ");
+        m2026_10_18_sync_account_token::migrate(db, existing_version)
+            .expect("FAILED: Sync Account Token - ");
+        m2026_10_18_sync_account_type::migrate(db, existing_version)
+            .expect("FAILED: Sync Account Type - ");
+        m2026_10_18_account_sync_target::migrate(db, existing_version)
+            .expect("FAILED: Account Sync Target - ");
+        m2026_10_18_trash_retention::migrate(db, existing_version)
+            .expect("FAILED: Trash Retention - ");
+        m2026_10_18_account_revisions::migrate(db, existing_version)
+            .expect("FAILED: Account Revisions - ");
+        m2026_10_18_structured_sync_logs::migrate(db, existing_version)
+            .expect("FAILED: Structured Sync Logs - ");
+        m2026_10_18_sync_interval::migrate(db, existing_version)
+            .expect("FAILED: Sync Interval - ");
+        m2026_10_18_sync_outbox::migrate(db, existing_version)
+            .expect("FAILED: Sync Outbox - ");
+        m2026_10_18_sync_failures::migrate(db, existing_version)
+            .expect("FAILED: Sync Failures - ");
+        m2026_10_18_manifest_cursor::migrate(db, existing_version)
+            .expect("FAILED: Manifest Cursor - ");
+        m2026_10_18_local_api::migrate(db, existing_version)
+            .expect("FAILED: Local API - ");
+        m2026_10_18_webdav_encryption::migrate(db, existing_version)
+            .expect("FAILED: WebDAV Encryption - ");
+        m2026_10_18_local_api_tokens::migrate(db, existing_version)
+            .expect("FAILED: Local API Tokens - ");
     }
 
     Ok(())
@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 use rusqlite::{Connection, named_params, Transaction};
-use crate::encryption::{encrypt, legacy_decrypt};
+use crate::encryption::{legacy_decrypt, load_key};
 
 const MIGRATION_NUMBER: u32 = 7;
 
@@ -23,6 +23,8 @@ pub fn migrate(db: &mut Connection, current_version: u32, encryption_path: PathB
 
     tx.pragma_update(None, "user_version", MIGRATION_NUMBER)?;
 
+    let key = load_key(&encryption_path).unwrap();
+
     for account in accounts.iter() {
         let decrypted_secret = legacy_decrypt(&account.encrypted);
 
@@ -30,7 +32,7 @@ pub fn migrate(db: &mut Connection, current_version: u32, encryption_path: PathB
             continue;
         }
 
-        let encrypted_secret = encrypt(&encryption_path, &decrypted_secret.unwrap()).unwrap();
+        let encrypted_secret = key.encrypt(&decrypted_secret.unwrap()).unwrap();
 
         tx.execute("UPDATE accounts SET secret = @secret WHERE id = @id", named_params!{"@id": account.id, "@secret": encrypted_secret})?;
     }
@@ -42,7 +44,7 @@ pub fn migrate(db: &mut Connection, current_version: u32, encryption_path: PathB
             continue;
         }
 
-        let encrypted_password = encrypt(&encryption_path, &decrypted_password.unwrap()).unwrap();
+        let encrypted_password = key.encrypt(&decrypted_password.unwrap()).unwrap();
 
         tx.execute("UPDATE sync_accounts SET password = @password WHERE id = @id", named_params!{"@id": sync_account.id, "@password": encrypted_password})?;
     }
@@ -0,0 +1,38 @@
+use rusqlite::Connection;
+
+const MIGRATION_NUMBER: u32 = 14;
+
+pub fn migrate(db: &mut Connection, current_version: u32) -> Result<(), rusqlite::Error> {
+    if current_version >= MIGRATION_NUMBER {
+        return Ok(());
+    }
+
+    db.pragma_update(None, "journal_mode", "WAL")?;
+
+    let tx = db.transaction()?;
+
+    tx.pragma_update(None, "user_version", MIGRATION_NUMBER)?;
+
+    tx.execute_batch(
+        "
+            CREATE TABLE account_revisions (
+                id INTEGER primary key,
+                account_id INTEGER NOT NULL,
+                name VARCHAR(255) NOT NULL,
+                secret VARCHAR(255) NOT NULL,
+                totp_step INTEGER NOT NULL,
+                otp_digits INTEGER NOT NULL,
+                colour VARCHAR(6) NOT NULL,
+                totp_algorithm VARCHAR(100),
+                origin INTEGER NOT NULL,
+                created_at INTEGER NOT NULL
+            );",
+    )?;
+    tx.execute_batch(
+        "CREATE INDEX account_revisions_account_id ON account_revisions (account_id);",
+    )?;
+
+    tx.commit()?;
+
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use rusqlite::Connection;
+
+const MIGRATION_NUMBER: u32 = 22;
+
+pub fn migrate(db: &mut Connection, current_version: u32) -> Result<(), rusqlite::Error> {
+    if current_version >= MIGRATION_NUMBER {
+        return Ok(());
+    }
+
+    db.pragma_update(None, "journal_mode", "WAL")?;
+
+    let tx = db.transaction()?;
+
+    tx.pragma_update(None, "user_version", MIGRATION_NUMBER)?;
+
+    // Clients allowed by name alone can't be bound to a token, so they pair again
+    tx.execute_batch("DROP TABLE local_api_clients;")?;
+
+    tx.execute_batch(
+        "
+            CREATE TABLE local_api_clients (
+                id INTEGER primary key,
+                name VARCHAR(255) NOT NULL,
+                token_hash VARCHAR(64) NOT NULL UNIQUE,
+                created_at INTEGER NOT NULL
+            );",
+    )?;
+
+    tx.commit()?;
+
+    Ok(())
+}
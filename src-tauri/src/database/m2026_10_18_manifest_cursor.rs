@@ -0,0 +1,41 @@
+use rusqlite::Connection;
+
+const MIGRATION_NUMBER: u32 = 19;
+
+pub fn migrate(db: &mut Connection, current_version: u32) -> Result<(), rusqlite::Error> {
+    if current_version >= MIGRATION_NUMBER {
+        return Ok(());
+    }
+
+    db.pragma_update(None, "journal_mode", "WAL")?;
+
+    let tx = db.transaction()?;
+
+    tx.pragma_update(None, "user_version", MIGRATION_NUMBER)?;
+
+    tx.execute_batch(
+        "
+            CREATE TABLE tmp_sync_accounts (
+                id integer primary key,
+                username VARCHAR(255) NOT NULL,
+                password VARCHAR(255) NOT NULL,
+                url VARCHAR(2083) NOT NULL,
+                token TEXT,
+                sync_type INTEGER NOT NULL DEFAULT 0,
+                manifest_cursor TEXT
+            );",
+    )?;
+    tx.execute_batch(
+        "
+        INSERT INTO tmp_sync_accounts (id, username, password, url, token, sync_type)
+            SELECT id, username, password, url, token, sync_type FROM sync_accounts;
+    ",
+    )?;
+
+    tx.execute_batch("DROP TABLE sync_accounts;")?;
+    tx.execute_batch("ALTER TABLE tmp_sync_accounts RENAME TO sync_accounts;")?;
+
+    tx.commit()?;
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use rusqlite::Connection;
+
+const MIGRATION_NUMBER: u32 = 15;
+
+pub fn migrate(db: &mut Connection, current_version: u32) -> Result<(), rusqlite::Error> {
+    if current_version >= MIGRATION_NUMBER {
+        return Ok(());
+    }
+
+    db.pragma_update(None, "journal_mode", "WAL")?;
+
+    let tx = db.transaction()?;
+
+    tx.pragma_update(None, "user_version", MIGRATION_NUMBER)?;
+
+    tx.execute_batch(
+        "
+            CREATE TABLE tmp_sync_logs (
+                id INTEGER primary key,
+                log VARCHAR(2083) NOT NULL,
+                log_type INTEGER NOT NULL,
+                timestamp INTEGER NOT NULL,
+                session_id VARCHAR(32),
+                sync_account_id INTEGER,
+                account_id INTEGER,
+                external_id INTEGER,
+                operation INTEGER
+            );",
+    )?;
+    tx.execute_batch(
+        "
+        INSERT INTO tmp_sync_logs (id, log, log_type, timestamp)
+            SELECT id, log, log_type, timestamp FROM sync_logs;
+    ",
+    )?;
+
+    tx.execute_batch("DROP TABLE sync_logs;")?;
+    tx.execute_batch("ALTER TABLE tmp_sync_logs RENAME TO sync_logs;")?;
+    tx.execute_batch("CREATE INDEX sync_logs_timestamp ON sync_logs (timestamp);")?;
+
+    tx.commit()?;
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use rusqlite::Connection;
+
+const MIGRATION_NUMBER: u32 = 18;
+
+pub fn migrate(db: &mut Connection, current_version: u32) -> Result<(), rusqlite::Error> {
+    if current_version >= MIGRATION_NUMBER {
+        return Ok(());
+    }
+
+    db.pragma_update(None, "journal_mode", "WAL")?;
+
+    let tx = db.transaction()?;
+
+    tx.pragma_update(None, "user_version", MIGRATION_NUMBER)?;
+
+    tx.execute_batch(
+        "
+            CREATE TABLE sync_failures (
+                id INTEGER primary key,
+                sync_account_id INTEGER NOT NULL,
+                account_id INTEGER,
+                external_id INTEGER,
+                operation INTEGER NOT NULL,
+                error TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 1,
+                last_attempt_at INTEGER NOT NULL
+            );",
+    )?;
+    tx.execute_batch(
+        "CREATE INDEX sync_failures_sync_account_id ON sync_failures (sync_account_id);",
+    )?;
+
+    tx.commit()?;
+
+    Ok(())
+}
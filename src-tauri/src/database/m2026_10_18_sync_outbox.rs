@@ -0,0 +1,33 @@
+use rusqlite::Connection;
+
+const MIGRATION_NUMBER: u32 = 17;
+
+pub fn migrate(db: &mut Connection, current_version: u32) -> Result<(), rusqlite::Error> {
+    if current_version >= MIGRATION_NUMBER {
+        return Ok(());
+    }
+
+    db.pragma_update(None, "journal_mode", "WAL")?;
+
+    let tx = db.transaction()?;
+
+    tx.pragma_update(None, "user_version", MIGRATION_NUMBER)?;
+
+    tx.execute_batch(
+        "
+            CREATE TABLE sync_outbox (
+                id INTEGER primary key,
+                account_id INTEGER NOT NULL UNIQUE,
+                operation INTEGER NOT NULL,
+                payload_hash VARCHAR(64) NOT NULL,
+                idempotency_key VARCHAR(32) NOT NULL UNIQUE,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                created_at INTEGER NOT NULL
+            );",
+    )?;
+
+    tx.commit()?;
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use rusqlite::Connection;
+
+const MIGRATION_NUMBER: u32 = 13;
+
+pub fn migrate(db: &mut Connection, current_version: u32) -> Result<(), rusqlite::Error> {
+    if current_version >= MIGRATION_NUMBER {
+        return Ok(());
+    }
+
+    db.pragma_update(None, "journal_mode", "WAL")?;
+
+    let tx = db.transaction()?;
+
+    tx.pragma_update(None, "user_version", MIGRATION_NUMBER)?;
+
+    tx.execute_batch(
+        "
+            CREATE TABLE tmp_settings (
+                id INTEGER primary key,
+                theme INTEGER NOT NULL,
+                trash_retention_days INTEGER NOT NULL DEFAULT 30
+            );",
+    )?;
+    tx.execute_batch(
+        "
+        INSERT INTO tmp_settings (id, theme)
+            SELECT id, theme FROM settings;
+    ",
+    )?;
+
+    tx.execute_batch("DROP TABLE settings;")?;
+    tx.execute_batch("ALTER TABLE tmp_settings RENAME TO settings;")?;
+
+    tx.commit()?;
+
+    Ok(())
+}
@@ -1,12 +1,12 @@
 use crate::database::AccountAlgorithm::{SHA1, SHA512};
-use crate::database::{create_new_account, create_sync_account, create_sync_log, delete_account, delete_sync_account, get_account_details_by_id, get_all_accounts, get_main_sync_account, get_soft_deleted_accounts, get_sync_logs, initialize_database, set_remote_account, update_existing_account, update_sync_account, AccountAlgorithm, SyncAccount, SyncLogType, get_settings, Theme, save_settings};
+use crate::database::{assign_unassigned_accounts, create_new_account, create_sync_account, count_synced_accounts, get_accounts_missing_from_manifest, purge_trashed_accounts, trash_account, get_accounts_without_external_id, get_sync_account_by_id, get_sync_accounts, set_account_sync_account, create_sync_log, delete_account, delete_sync_account, get_account_details_by_id, get_all_accounts, get_main_sync_account, get_soft_deleted_accounts, get_sync_logs, initialize_database, set_remote_account, update_existing_account, update_sync_account, update_sync_account_token, AccountAlgorithm, SyncAccount, SyncLogType, SyncAuthType, SyncType, get_settings, Theme, save_settings, save_trash_retention, save_sync_interval, get_trashed_accounts, restore_account, purge_account, clear_remote_account, get_account_revisions, get_account_revision, RevisionOrigin, prune_sync_logs, SyncLogContext, SyncLogFilter, SyncOperation, queue_outbox_entry, get_outbox_entries, complete_outbox_entry, fail_outbox_entry, account_payload_hash, OutboxOperation, record_sync_failure, get_sync_failure, get_sync_failures, clear_sync_failure, clear_stale_sync_failures, get_accounts_by_external_ids, get_manifest_cursor, save_manifest_cursor, save_local_api_enabled, get_local_api_clients, local_api_client_allowed, allow_local_api_client, remove_local_api_client};
 use crate::sync_api::Record;
 use libotp::HOTPAlgorithm;
 use rusqlite::Connection;
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 const SQLITE_TEST_NAME: &str = "Phoenix_test.sqlite";
+const NOW: u64 = 1_700_000_000;
 
 #[test]
 fn can_parse_sha1() {
@@ -82,7 +82,7 @@ fn create_new_account_full() {
     let step = 30;
     let algorithm = "SHA1";
 
-    let result = create_new_account(&name, &secret, &digits, &step, &colour, &algorithm, &db);
+    let result = create_new_account(&name, &secret, &digits, &step, &colour, &algorithm, None, &db);
 
     assert_eq!(true, result.is_ok());
 
@@ -108,7 +108,7 @@ fn create_new_account_required() {
     let step = 30;
     let algorithm = "";
 
-    let result = create_new_account(&name, &secret, &digits, &step, &colour, &algorithm, &db);
+    let result = create_new_account(&name, &secret, &digits, &step, &colour, &algorithm, None, &db);
 
     assert_eq!(true, result.is_ok());
 
@@ -141,7 +141,7 @@ fn update_existing_account_full() {
     let updated_algorithm = "SHA512";
 
     let original_account =
-        create_new_account(&name, &secret, &digits, &step, &colour, &algorithm, &db).unwrap();
+        create_new_account(&name, &secret, &digits, &step, &colour, &algorithm, None, &db).unwrap();
     let result = update_existing_account(
         &original_account.id,
         &updated_name,
@@ -150,6 +150,9 @@ fn update_existing_account_full() {
         updated_step,
         &updated_colour,
         &updated_algorithm,
+        RevisionOrigin::LOCAL,
+        None,
+        NOW,
         &db,
     );
 
@@ -181,7 +184,7 @@ fn update_existing_account_partial() {
     let updated_algorithm = "";
 
     let original_account =
-        create_new_account(&name, &secret, &digits, &step, &colour, &algorithm, &db).unwrap();
+        create_new_account(&name, &secret, &digits, &step, &colour, &algorithm, None, &db).unwrap();
     let result = update_existing_account(
         &original_account.id,
         &original_account.name,
@@ -190,6 +193,9 @@ fn update_existing_account_partial() {
         original_account.totp_step,
         &original_account.colour,
         &updated_algorithm,
+        RevisionOrigin::LOCAL,
+        None,
+        NOW,
         &db,
     );
 
@@ -207,14 +213,241 @@ fn update_existing_account_partial() {
     assert_eq!(true, updated_account.algorithm.is_none());
 }
 
+#[test]
+fn update_existing_account_stores_revision() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let original_account =
+        create_new_account("Revision Test", "HelloWorld", &6, &30, "ffffff", "SHA1", None, &db).unwrap();
+
+    let _ = update_existing_account(&original_account.id, "Revision Test Local", "HelloWorld", 6, 30, "ffffff", "SHA1", RevisionOrigin::LOCAL, None, NOW, &db);
+    let _ = update_existing_account(&original_account.id, "Revision Test Sync", "HelloWorld245", 8, 60, "fff123", "", RevisionOrigin::SYNC, None, NOW, &db);
+
+    let revisions = get_account_revisions(original_account.id, &db).unwrap();
+
+    assert_eq!(2, revisions.len());
+
+    assert_eq!("Revision Test Local", revisions[0].name);
+    assert_eq!(RevisionOrigin::SYNC, revisions[0].origin);
+
+    assert_eq!(original_account.id, revisions[1].account_id);
+    assert_eq!("Revision Test", revisions[1].name);
+    assert_eq!("HelloWorld", revisions[1].secret);
+    assert_eq!(6, revisions[1].otp_digits);
+    assert_eq!(30, revisions[1].totp_step);
+    assert_eq!(Some(SHA1), revisions[1].algorithm);
+    assert_eq!(RevisionOrigin::LOCAL, revisions[1].origin);
+    assert_eq!(true, revisions[1].created_at > 0);
+
+    let revision = get_account_revision(revisions[1].id, &db).unwrap();
+
+    assert_eq!(true, revision.is_some());
+    assert_eq!("Revision Test", revision.unwrap().name);
+    assert_eq!(true, get_account_revision(0, &db).unwrap().is_none());
+}
+
+#[test]
+fn update_existing_account_rolls_back_revision_on_failure() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let account = create_new_account("Revision Test", "HelloWorld", &6, &30, "ffffff", "SHA1", None, &db).unwrap();
+
+    db.execute_batch("CREATE TRIGGER block_account_update BEFORE UPDATE ON accounts BEGIN SELECT RAISE(ABORT, 'blocked'); END;").unwrap();
+    let result = update_existing_account(&account.id, "Revision Test Import", "HelloWorld", 6, 30, "ffffff", "SHA1", RevisionOrigin::IMPORT, None, NOW, &db);
+    db.execute_batch("DROP TRIGGER block_account_update;").unwrap();
+
+    assert_eq!(true, result.is_err());
+    assert_eq!(0, get_account_revisions(account.id, &db).unwrap().len());
+    assert_eq!("Revision Test", get_account_details_by_id(account.id as u32, &db).unwrap().name);
+}
+
+#[test]
+fn purge_account_removes_revisions() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let account = create_new_account("Revision Test", "HelloWorld", &6, &30, "ffffff", "SHA1", None, &db).unwrap();
+    let _ = update_existing_account(&account.id, "Revision Test Local", "HelloWorld", 6, 30, "ffffff", "SHA1", RevisionOrigin::LOCAL, None, NOW, &db);
+    let _ = delete_account(&account, NOW, &db);
+
+    assert_eq!(1, get_account_revisions(account.id, &db).unwrap().len());
+    assert_eq!(true, purge_account(&get_account_details_by_id(account.id as u32, &db).unwrap(), &db).unwrap());
+    assert_eq!(0, get_account_revisions(account.id, &db).unwrap().len());
+}
+
+#[test]
+fn queue_outbox_requires_sync_account() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let account = create_new_account("Outbox Test", "HelloWorld", &6, &30, "ffffff", "SHA1", None, &db).unwrap();
+
+    assert_eq!(true, queue_outbox_entry(&account.id, OutboxOperation::CREATE, NOW, &db).unwrap().is_none());
+}
+
+#[test]
+fn queue_outbox_coalesces_create_and_update() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let account = create_new_account("Outbox Test", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+
+    let created = queue_outbox_entry(&account.id, OutboxOperation::CREATE, NOW, &db).unwrap().unwrap();
+
+    assert_eq!(OutboxOperation::CREATE, created.operation);
+    assert_eq!(32, created.idempotency_key.len());
+
+    let updated_account = update_existing_account(&account.id, "Outbox Test Edited", "HelloWorld", 6, 30, "ffffff", "SHA1", RevisionOrigin::LOCAL, None, NOW, &db).unwrap();
+    let updated = queue_outbox_entry(&account.id, OutboxOperation::UPDATE, NOW, &db).unwrap().unwrap();
+
+    assert_eq!(created.id, updated.id);
+    assert_eq!(OutboxOperation::CREATE, updated.operation);
+    assert_eq!(created.idempotency_key, updated.idempotency_key);
+    assert_ne!(created.payload_hash, updated.payload_hash);
+    assert_eq!(account_payload_hash(&get_account_details_by_id(updated_account.id as u32, &db).unwrap()), updated.payload_hash);
+    assert_eq!(1, get_outbox_entries(&sync_account.id, &db).unwrap().len());
+}
+
+#[test]
+fn queue_outbox_drops_unsent_create_on_delete() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let unsent = create_new_account("Outbox Unsent", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+    let attempted = create_new_account("Outbox Attempted", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+
+    queue_outbox_entry(&unsent.id, OutboxOperation::CREATE, NOW, &db).unwrap();
+    let attempted_entry = queue_outbox_entry(&attempted.id, OutboxOperation::CREATE, NOW, &db).unwrap().unwrap();
+    fail_outbox_entry(&attempted_entry.id, "Error 0 Connection failed", &db).unwrap();
+
+    assert_eq!(true, queue_outbox_entry(&unsent.id, OutboxOperation::DELETE, NOW, &db).unwrap().is_none());
+
+    // The create may have reached the server, so it is replayed with its key before deleting
+    let kept = queue_outbox_entry(&attempted.id, OutboxOperation::DELETE, NOW, &db).unwrap().unwrap();
+
+    assert_eq!(OutboxOperation::CREATE, kept.operation);
+    assert_eq!(attempted_entry.idempotency_key, kept.idempotency_key);
+    assert_eq!(1, kept.attempts);
+    assert_eq!(Some("Error 0 Connection failed".to_string()), kept.last_error);
+
+    let entries = get_outbox_entries(&sync_account.id, &db).unwrap();
+
+    assert_eq!(1, entries.len());
+    assert_eq!(attempted.id, entries[0].account_id);
+}
+
+#[test]
+fn complete_outbox_entry_requeues_changed_payload() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let account = create_new_account("Outbox Test", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+    let entry = queue_outbox_entry(&account.id, OutboxOperation::CREATE, NOW, &db).unwrap().unwrap();
+    let sent_payload_hash = entry.payload_hash.clone();
+
+    let _ = update_existing_account(&account.id, "Outbox Test Edited", "HelloWorld", 6, 30, "ffffff", "SHA1", RevisionOrigin::LOCAL, None, NOW, &db);
+    queue_outbox_entry(&account.id, OutboxOperation::UPDATE, NOW, &db).unwrap();
+
+    assert_eq!(false, complete_outbox_entry(&entry, &sent_payload_hash, &db).unwrap());
+
+    let entries = get_outbox_entries(&sync_account.id, &db).unwrap();
+
+    assert_eq!(1, entries.len());
+    assert_eq!(OutboxOperation::UPDATE, entries[0].operation);
+    assert_ne!(entry.idempotency_key, entries[0].idempotency_key);
+
+    assert_eq!(true, complete_outbox_entry(&entries[0], &entries[0].payload_hash, &db).unwrap());
+    assert_eq!(0, get_outbox_entries(&sync_account.id, &db).unwrap().len());
+}
+
+#[test]
+fn outbox_entries_in_order_per_sync_account() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let other_sync_account = create_sync_account("Other", "password", "https://other.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let first = create_new_account("Outbox First", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+    let other = create_new_account("Outbox Other", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(other_sync_account.id), &db).unwrap();
+    let second = create_new_account("Outbox Second", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+
+    queue_outbox_entry(&first.id, OutboxOperation::CREATE, NOW, &db).unwrap();
+    queue_outbox_entry(&other.id, OutboxOperation::CREATE, NOW, &db).unwrap();
+    queue_outbox_entry(&second.id, OutboxOperation::CREATE, NOW, &db).unwrap();
+
+    let entries = get_outbox_entries(&sync_account.id, &db).unwrap();
+
+    assert_eq!(2, entries.len());
+    assert_eq!(first.id, entries[0].account_id);
+    assert_eq!(second.id, entries[1].account_id);
+
+    delete_sync_account(other_sync_account.id, &db).unwrap();
+
+    assert_eq!(0, get_outbox_entries(&other_sync_account.id, &db).unwrap().len());
+}
+
+#[test]
+fn record_and_clear_sync_failure() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let account = create_new_account("Failing Account", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+
+    record_sync_failure(&sync_account.id, Some(account.id), None, SyncOperation::CREATE, "Error 500", NOW, &db).unwrap();
+    let failure = record_sync_failure(&sync_account.id, Some(account.id), None, SyncOperation::UPDATE, "Error 400", NOW, &db).unwrap();
+
+    assert_eq!(2, failure.attempts);
+    assert_eq!(SyncOperation::UPDATE, failure.operation);
+    assert_eq!("Error 400", failure.error);
+    assert_eq!(true, get_sync_failure(&sync_account.id, None, Some(account.id), &db).unwrap().is_none());
+
+    record_sync_failure(&sync_account.id, None, Some(1234), SyncOperation::PULL, "Invalid record", NOW, &db).unwrap();
+
+    assert_eq!(2, get_sync_failures(&sync_account.id, &db).unwrap().len());
+    assert_eq!(true, clear_sync_failure(&sync_account.id, Some(account.id), None, &db).unwrap());
+    assert_eq!(false, clear_sync_failure(&sync_account.id, Some(account.id), None, &db).unwrap());
+    assert_eq!(1, get_sync_failures(&sync_account.id, &db).unwrap().len());
+}
+
+#[test]
+fn clear_stale_sync_failures_missing_from_manifest() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let account = create_new_account("Failing Account", "HelloWorld", &6, &30, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+
+    record_sync_failure(&sync_account.id, Some(account.id), None, SyncOperation::UPDATE, "Error 400", NOW, &db).unwrap();
+    record_sync_failure(&sync_account.id, None, Some(12), SyncOperation::PULL, "Invalid record", NOW, &db).unwrap();
+    record_sync_failure(&sync_account.id, None, Some(14), SyncOperation::PULL, "Invalid record", NOW, &db).unwrap();
+
+    assert_eq!(1, clear_stale_sync_failures(vec![12], &sync_account.id, &db).unwrap());
+
+    let failures = get_sync_failures(&sync_account.id, &db).unwrap();
+
+    assert_eq!(2, failures.len());
+    assert_eq!(Some(account.id), failures[0].account_id);
+    assert_eq!(Some(12), failures[1].external_id);
+
+    delete_sync_account(sync_account.id, &db).unwrap();
+
+    assert_eq!(0, get_sync_failures(&sync_account.id, &db).unwrap().len());
+}
+
 #[test]
 fn get_all_accounts_order() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let expected_second = create_new_account("AB Record", "1234", &8, &30, "fffff", "", &db).unwrap();
-    let expected_third = create_new_account("AC Record", "2134", &4, &15, "fffff","", &db).unwrap();
-    let expected_first = create_new_account("AA Record", "9284", &12, &60,"fffff", "", &db).unwrap();
+    let expected_second = create_new_account("AB Record", "1234", &8, &30, "fffff", "", None, &db).unwrap();
+    let expected_third = create_new_account("AC Record", "2134", &4, &15, "fffff","", None, &db).unwrap();
+    let expected_first = create_new_account("AA Record", "9284", &12, &60,"fffff", "", None, &db).unwrap();
 
     let result = get_all_accounts(&db, "");
 
@@ -242,7 +475,7 @@ fn get_account_details_by_id_default() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let expected = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", &db).unwrap();
+    let expected = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", None, &db).unwrap();
 
     let result = get_account_details_by_id(expected.id as u32, &db);
 
@@ -268,7 +501,7 @@ fn get_account_details_by_id_with_external_details() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let expected = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", &db).unwrap();
+    let expected = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", None, &db).unwrap();
     let _ = set_remote_account(
         &db,
         &expected,
@@ -318,9 +551,9 @@ fn delete_account_soft_delete() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let _ = create_sync_account("User", "passowrd", "https://test.com", &db);
+    let sync_account = create_sync_account("User", "passowrd", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
 
-    let expected = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", &db).unwrap();
+    let expected = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
     let _ = set_remote_account(
         &db,
         &expected,
@@ -333,12 +566,12 @@ fn delete_account_soft_delete() {
 
     let result = get_account_details_by_id(expected.id as u32, &db).unwrap();
 
-    let delete_result = delete_account(&result, &db);
+    let delete_result = delete_account(&result, NOW, &db);
 
     assert_eq!(true, delete_result.is_ok());
     assert_eq!(true, delete_result.unwrap());
 
-    let soft_delete_result = get_soft_deleted_accounts(&db);
+    let soft_delete_result = get_soft_deleted_accounts(&sync_account.id, &db);
 
     assert_eq!(true, soft_delete_result.is_ok());
 
@@ -349,22 +582,115 @@ fn delete_account_soft_delete() {
 }
 
 #[test]
-fn delete_account_hard() {
+fn delete_account_without_sync_account() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let expected = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", &db).unwrap();
+    let expected = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", None, &db).unwrap();
 
     let result = get_account_details_by_id(expected.id as u32, &db).unwrap();
 
-    let delete_result = delete_account(&result, &db);
+    let delete_result = delete_account(&result, NOW, &db);
 
     assert_eq!(true, delete_result.is_ok());
     assert_eq!(true, delete_result.unwrap());
 
     let result = get_account_details_by_id(expected.id as u32, &db).unwrap();
 
-    assert_eq!(0, result.id);
+    assert_eq!(expected.id, result.id);
+    assert_eq!(true, result.deleted_at.is_some());
+    assert_eq!(0, get_all_accounts(&db, "").unwrap().len());
+    assert_eq!(1, get_trashed_accounts(0, &db).unwrap().len());
+}
+
+#[test]
+fn restore_trashed_account() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let synced = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+    let unsynced = create_new_account("AB Record", "1234", &12, &60, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+    let _ = set_remote_account(
+        &db,
+        &synced,
+        &Record {
+            id: 15,
+            sync_hash: "15HA482".to_string(),
+            updated_at: 1847,
+        },
+    );
+
+    let _ = delete_account(&get_account_details_by_id(synced.id as u32, &db).unwrap(), NOW, &db);
+    let _ = delete_account(&get_account_details_by_id(unsynced.id as u32, &db).unwrap(), NOW, &db);
+
+    assert_eq!(false, restore_account(&synced, NOW, &db).unwrap());
+
+    let trashed_synced = get_account_details_by_id(synced.id as u32, &db).unwrap();
+    let trashed_unsynced = get_account_details_by_id(unsynced.id as u32, &db).unwrap();
+
+    assert_eq!(true, restore_account(&trashed_synced, NOW, &db).unwrap());
+    assert_eq!(true, restore_account(&trashed_unsynced, NOW, &db).unwrap());
+
+    let restored = get_account_details_by_id(synced.id as u32, &db).unwrap();
+
+    assert_eq!(true, restored.deleted_at.is_none());
+    assert_eq!(Some(15), restored.external_id);
+    assert_eq!(true, restored.external_last_updated.unwrap() > 1847);
+    assert_eq!(2, get_all_accounts(&db, "").unwrap().len());
+    assert_eq!(1, get_accounts_without_external_id(&sync_account.id, &db).unwrap().len());
+    assert_eq!(0, get_trashed_accounts(0, &db).unwrap().len());
+}
+
+#[test]
+fn purge_trashed_account() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let local = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", None, &db).unwrap();
+    let synced = create_new_account("AB Record", "1234", &12, &60, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+    let _ = set_remote_account(
+        &db,
+        &synced,
+        &Record {
+            id: 15,
+            sync_hash: "15HA482".to_string(),
+            updated_at: 1847,
+        },
+    );
+
+    assert_eq!(false, purge_account(&get_account_details_by_id(local.id as u32, &db).unwrap(), &db).unwrap());
+
+    let _ = delete_account(&get_account_details_by_id(local.id as u32, &db).unwrap(), NOW, &db);
+    let _ = delete_account(&get_account_details_by_id(synced.id as u32, &db).unwrap(), NOW, &db);
+
+    assert_eq!(true, purge_account(&get_account_details_by_id(local.id as u32, &db).unwrap(), &db).unwrap());
+    assert_eq!(true, purge_account(&get_account_details_by_id(synced.id as u32, &db).unwrap(), &db).unwrap());
+
+    assert_eq!(0, get_account_details_by_id(local.id as u32, &db).unwrap().id);
+    assert_eq!(0, get_trashed_accounts(1, &db).unwrap().len());
+
+    let pending_deletions = get_soft_deleted_accounts(&sync_account.id, &db).unwrap();
+
+    assert_eq!(1, pending_deletions.len());
+    assert_eq!(Some(15), pending_deletions[0].external_id);
+
+    let _ = clear_remote_account(&pending_deletions[0], &db);
+
+    assert_eq!(1, purge_trashed_accounts(1, &db).unwrap());
+}
+
+#[test]
+fn trashed_accounts_outside_retention() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let account = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", None, &db).unwrap();
+    let _ = delete_account(&account, NOW, &db);
+
+    assert_eq!(1, get_trashed_accounts(0, &db).unwrap().len());
+    assert_eq!(0, get_trashed_accounts(i64::MAX as u64, &db).unwrap().len());
 }
 
 #[test]
@@ -372,7 +698,7 @@ fn set_sync_accounts_success() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let result = create_sync_account("User", "passowrd", "https://test.com", &db);
+    let result = create_sync_account("User", "passowrd", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db);
 
     assert_eq!(true, result.is_ok());
 
@@ -389,7 +715,7 @@ fn update_sync_accounts() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let original = create_sync_account("User", "passowrd", "https://test.com", &db).unwrap();
+    let original = create_sync_account("User", "passowrd", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
 
     assert_eq!(true, original.id != 0);
 
@@ -399,6 +725,9 @@ fn update_sync_accounts() {
         password: "wjshf".to_string(),
         url: "http://updated.com".to_string(),
         token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
     let result = update_sync_account(update, &db).unwrap();
@@ -413,12 +742,96 @@ fn update_sync_accounts() {
     assert_eq!("http://updated.com", final_account.url);
 }
 
+#[test]
+fn sync_account_webdav_settings() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let original = create_sync_account("User", "password", "https://dav.test.com", &SyncType::WEBDAV, Some("passphrase"), &SyncAuthType::BEARER, &db).unwrap();
+
+    assert_eq!(Some("passphrase".to_string()), original.passphrase);
+    assert_eq!(SyncAuthType::BEARER, original.auth_type);
+
+    let update = SyncAccount {
+        passphrase: Some("changed".to_string()),
+        auth_type: SyncAuthType::BASIC,
+        ..original
+    };
+
+    assert_eq!(true, update_sync_account(update, &db).unwrap());
+
+    let final_account = get_main_sync_account(&db).unwrap();
+
+    assert_eq!(Some("changed".to_string()), final_account.passphrase);
+    assert_eq!(SyncAuthType::BASIC, final_account.auth_type);
+}
+
+#[test]
+fn update_sync_account_cached_token() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let original = create_sync_account("User", "passowrd", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+
+    assert_eq!(true, original.token.is_none());
+
+    let result = update_sync_account_token(original.id, Some("encrypted-token".to_string()), &db);
+
+    assert_eq!(true, result.unwrap());
+    assert_eq!(
+        Some("encrypted-token".to_string()),
+        get_main_sync_account(&db).unwrap().token
+    );
+
+    let update = SyncAccount {
+        id: original.id,
+        username: "updated".to_string(),
+        password: "wjshf".to_string(),
+        url: "http://updated.com".to_string(),
+        token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    };
+
+    update_sync_account(update, &db).unwrap();
+
+    assert_eq!(true, get_main_sync_account(&db).unwrap().token.is_none());
+}
+
+#[test]
+fn manifest_cursor_reset_on_update() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let original = create_sync_account("User", "passowrd", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+
+    assert_eq!(None, get_manifest_cursor(&original.id, &db).unwrap());
+    assert_eq!(true, save_manifest_cursor(&original.id, Some("cursor-1".to_string()), &db).unwrap());
+    assert_eq!(Some("cursor-1".to_string()), get_manifest_cursor(&original.id, &db).unwrap());
+
+    let update = SyncAccount {
+        id: original.id,
+        username: "updated".to_string(),
+        password: "wjshf".to_string(),
+        url: "http://updated.com".to_string(),
+        token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    };
+
+    update_sync_account(update, &db).unwrap();
+
+    assert_eq!(None, get_manifest_cursor(&original.id, &db).unwrap());
+}
+
 #[test]
 fn delete_sync_accounts() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let account = create_sync_account("User", "password", "https://test.com", &db).unwrap();
+    let account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
 
     let result = delete_sync_account(account.id, &db).unwrap();
 
@@ -430,23 +843,237 @@ fn delete_sync_accounts() {
 }
 
 #[test]
-fn error_sync_log() {
+fn get_multiple_sync_accounts() {
     let db = initialize_test_database().unwrap();
     reset_db(&db).expect("Cant reset");
 
-    let start = SystemTime::now();
-    let since_the_epoch = start
-        .duration_since(UNIX_EPOCH)
-        .expect("Could not generate UNIX time");
-    let timestamp_before = since_the_epoch.as_secs();
+    let work = create_sync_account("Work", "password", "https://work.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let personal = create_sync_account("Personal", "password", "https://dav.personal.com", &SyncType::WEBDAV, None, &SyncAuthType::BASIC, &db).unwrap();
+
+    assert_eq!("Work", work.username);
+    assert_eq!("Personal", personal.username);
+
+    let sync_accounts = get_sync_accounts(&db).unwrap();
+
+    assert_eq!(2, sync_accounts.len());
+    assert_eq!(work.id, sync_accounts[0].id);
+    assert_eq!(personal.id, sync_accounts[1].id);
+    assert_eq!(SyncType::WEBDAV, sync_accounts[1].sync_type);
+    assert_eq!(work.id, get_main_sync_account(&db).unwrap().id);
+    assert_eq!("https://dav.personal.com", get_sync_account_by_id(personal.id, &db).unwrap().url);
+    assert_eq!(0, get_sync_account_by_id(208, &db).unwrap().id);
+}
+
+#[test]
+fn sync_queries_scoped_to_sync_account() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let work = create_sync_account("Work", "password", "https://work.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let personal = create_sync_account("Personal", "password", "https://personal.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+
+    let work_account = create_new_account("Work Record", "1234", &6, &30, "ffffff", "", Some(work.id), &db).unwrap();
+    let personal_account = create_new_account("Personal Record", "4321", &6, &30, "ffffff", "", Some(personal.id), &db).unwrap();
+    let _ = create_new_account("Local Record", "5678", &6, &30, "ffffff", "", None, &db).unwrap();
+
+    let work_pending = get_accounts_without_external_id(&work.id, &db).unwrap();
+
+    assert_eq!(1, work_pending.len());
+    assert_eq!(work_account.id, work_pending[0].id);
+    assert_eq!(Some(work.id), work_pending[0].sync_account_id);
 
-    let result = create_sync_log(&db, "Error Test".to_string(), SyncLogType::ERROR);
+    let record = Record {
+        id: 15,
+        sync_hash: "15HA482".to_string(),
+        updated_at: 1847,
+    };
+    let _ = set_remote_account(&db, &work_account, &record);
+    let _ = set_remote_account(&db, &personal_account, &record);
+
+    assert_eq!(work_account.id, get_accounts_by_external_ids(vec![15], &work.id, &db).unwrap()[0].id);
+    assert_eq!(personal_account.id, get_accounts_by_external_ids(vec![15], &personal.id, &db).unwrap()[0].id);
 
-    let start = SystemTime::now();
-    let since_the_epoch = start
-        .duration_since(UNIX_EPOCH)
-        .expect("Could not generate UNIX time");
-    let timestamp_after = since_the_epoch.as_secs();
+    let missing = get_accounts_missing_from_manifest(vec![], &work.id, &db).unwrap();
+
+    assert_eq!(1, missing.len());
+    assert_eq!(work_account.id, missing[0].id);
+    assert_eq!(1, count_synced_accounts(&work.id, &db).unwrap());
+    assert_eq!(0, get_accounts_missing_from_manifest(vec![15], &personal.id, &db).unwrap().len());
+}
+
+#[test]
+fn trash_remote_deleted_account() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let account = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+    let _ = set_remote_account(
+        &db,
+        &account,
+        &Record {
+            id: 15,
+            sync_hash: "15HA482".to_string(),
+            updated_at: 1847,
+        },
+    );
+
+    assert_eq!(true, trash_account(&account, NOW, &db).unwrap());
+
+    let trashed = get_soft_deleted_accounts(&sync_account.id, &db).unwrap();
+
+    assert_eq!(1, trashed.len());
+    assert_eq!(true, trashed[0].deleted_at.is_some());
+    assert_eq!(true, trashed[0].external_id.is_none());
+    assert_eq!(0, get_all_accounts(&db, "").unwrap().len());
+    assert_eq!(0, count_synced_accounts(&sync_account.id, &db).unwrap());
+}
+
+#[test]
+fn purge_expired_trashed_accounts() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let account = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", Some(sync_account.id), &db).unwrap();
+    let _ = trash_account(&account, NOW, &db);
+
+    assert_eq!(0, purge_trashed_accounts(1, &db).unwrap());
+    assert_eq!(account.id, get_account_details_by_id(account.id as u32, &db).unwrap().id);
+
+    assert_eq!(1, purge_trashed_accounts(i64::MAX as u64, &db).unwrap());
+    assert_eq!(0, get_account_details_by_id(account.id as u32, &db).unwrap().id);
+}
+
+#[test]
+fn move_account_between_sync_accounts() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let work = create_sync_account("Work", "password", "https://work.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let personal = create_sync_account("Personal", "password", "https://personal.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+
+    let account = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", Some(work.id), &db).unwrap();
+    let _ = set_remote_account(
+        &db,
+        &account,
+        &Record {
+            id: 15,
+            sync_hash: "15HA482".to_string(),
+            updated_at: 1847,
+        },
+    );
+
+    assert_eq!(true, set_account_sync_account(account.id, Some(personal.id), NOW, &db).unwrap());
+
+    let moved = get_account_details_by_id(account.id as u32, &db).unwrap();
+
+    assert_eq!(Some(personal.id), moved.sync_account_id);
+    assert_eq!(true, moved.external_id.is_none());
+
+    let work_deletions = get_soft_deleted_accounts(&work.id, &db).unwrap();
+
+    assert_eq!(1, work_deletions.len());
+    assert_eq!(Some(15), work_deletions[0].external_id);
+    assert_eq!(1, get_accounts_without_external_id(&personal.id, &db).unwrap().len());
+    assert_eq!(1, get_all_accounts(&db, "").unwrap().len());
+}
+
+#[test]
+fn set_account_sync_account_rolls_back_on_failure() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let work = create_sync_account("Work", "password", "https://work.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let personal = create_sync_account("Personal", "password", "https://personal.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+
+    let account = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", Some(work.id), &db).unwrap();
+    let _ = set_remote_account(
+        &db,
+        &account,
+        &Record {
+            id: 15,
+            sync_hash: "15HA482".to_string(),
+            updated_at: 1847,
+        },
+    );
+
+    db.execute_batch("CREATE TRIGGER block_account_move BEFORE UPDATE OF sync_account_id ON accounts BEGIN SELECT RAISE(ABORT, 'blocked'); END;").unwrap();
+    let result = set_account_sync_account(account.id, Some(personal.id), NOW, &db);
+    db.execute_batch("DROP TRIGGER block_account_move;").unwrap();
+
+    assert_eq!(true, result.is_err());
+    assert_eq!(0, get_soft_deleted_accounts(&work.id, &db).unwrap().len());
+    assert_eq!(Some(work.id), get_account_details_by_id(account.id as u32, &db).unwrap().sync_account_id);
+}
+
+#[test]
+fn initialize_database_waits_for_busy_connections() {
+    let db = initialize_test_database().unwrap();
+    let busy_timeout: u32 = db.query_row("PRAGMA busy_timeout", [], |row| row.get(0)).unwrap();
+
+    assert_eq!(5000, busy_timeout);
+}
+
+#[test]
+fn delete_sync_account_detaches_accounts() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let work = create_sync_account("Work", "password", "https://work.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let account = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", Some(work.id), &db).unwrap();
+    let deleted = create_new_account("AB Record", "1234", &12, &60, "ffffff", "SHA1", Some(work.id), &db).unwrap();
+    let _ = delete_account(&get_account_details_by_id(deleted.id as u32, &db).unwrap(), NOW, &db);
+
+    assert_eq!(true, delete_sync_account(work.id, &db).unwrap());
+
+    let detached = get_account_details_by_id(account.id as u32, &db).unwrap();
+    let trashed = get_account_details_by_id(deleted.id as u32, &db).unwrap();
+
+    assert_eq!(true, detached.sync_account_id.is_none());
+    assert_eq!(true, trashed.sync_account_id.is_none());
+    assert_eq!(true, trashed.deleted_at.is_some());
+}
+
+#[test]
+fn delete_sync_account_rolls_back_on_failure() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let work = create_sync_account("Work", "password", "https://work.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+    let account = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", Some(work.id), &db).unwrap();
+
+    db.execute_batch("CREATE TRIGGER block_sync_account_delete BEFORE DELETE ON sync_accounts BEGIN SELECT RAISE(ABORT, 'blocked'); END;").unwrap();
+    let result = delete_sync_account(work.id, &db);
+    db.execute_batch("DROP TRIGGER block_sync_account_delete;").unwrap();
+
+    let attached = get_account_details_by_id(account.id as u32, &db).unwrap();
+
+    assert_eq!(true, result.is_err());
+    assert_eq!(Some(work.id), attached.sync_account_id);
+}
+
+#[test]
+fn assign_unassigned_accounts_to_first_sync_account() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let account = create_new_account("AA Record", "9284", &12, &60, "ffffff", "SHA1", None, &db).unwrap();
+    let sync_account = create_sync_account("User", "password", "https://test.com", &SyncType::PHOENIX, None, &SyncAuthType::BASIC, &db).unwrap();
+
+    assert_eq!(1, assign_unassigned_accounts(sync_account.id, &db).unwrap());
+    assert_eq!(
+        Some(sync_account.id),
+        get_account_details_by_id(account.id as u32, &db).unwrap().sync_account_id
+    );
+}
+
+#[test]
+fn error_sync_log() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let result = create_sync_log(&db, "Error Test".to_string(), SyncLogType::ERROR, SyncLogContext::default(), NOW);
 
     assert_eq!(true, result.is_ok());
 
@@ -454,25 +1081,106 @@ fn error_sync_log() {
 
     assert_eq!("Error Test".to_string(), sync_log.log);
     assert_eq!(SyncLogType::ERROR, sync_log.log_type);
-    assert_eq!(
-        true,
-        sync_log.timestamp >= timestamp_before && sync_log.timestamp <= timestamp_after
-    );
+    assert_eq!(NOW, sync_log.timestamp);
 
-    let get_result = get_sync_logs(&db);
+    let get_result = get_sync_logs(&db, &SyncLogFilter::default());
 
     assert_eq!(true, get_result.is_ok());
 
-    let sync_logs = get_result.unwrap();
+    let sync_logs = get_result.unwrap().logs;
 
     assert_eq!(1, sync_logs.len());
 
     assert_eq!("Error Test".to_string(), sync_logs[0].log);
     assert_eq!(SyncLogType::ERROR, sync_logs[0].log_type);
-    assert_eq!(
-        true,
-        sync_logs[0].timestamp >= timestamp_before && sync_logs[0].timestamp <= timestamp_after
-    );
+    assert_eq!(NOW, sync_logs[0].timestamp);
+}
+
+#[test]
+fn structured_sync_log() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let context = SyncLogContext {
+        session_id: Some("abc123".to_string()),
+        sync_account_id: Some(4),
+        account_id: Some(12),
+        external_id: Some(1234),
+        operation: Some(SyncOperation::UPDATE),
+    };
+
+    let sync_log = create_sync_log(&db, "Warn Test".to_string(), SyncLogType::WARN, context, NOW).unwrap();
+
+    assert_eq!(true, sync_log.id > 0);
+
+    let sync_logs = get_sync_logs(&db, &SyncLogFilter::default()).unwrap().logs;
+
+    assert_eq!(1, sync_logs.len());
+    assert_eq!(SyncLogType::WARN, sync_logs[0].log_type);
+    assert_eq!(Some("abc123".to_string()), sync_logs[0].session_id);
+    assert_eq!(Some(4), sync_logs[0].sync_account_id);
+    assert_eq!(Some(12), sync_logs[0].account_id);
+    assert_eq!(Some(1234), sync_logs[0].external_id);
+    assert_eq!(Some(SyncOperation::UPDATE), sync_logs[0].operation);
+}
+
+#[test]
+fn filter_and_page_sync_logs() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    for index in 0..15 {
+        let context = SyncLogContext {
+            session_id: Some(if index < 5 { "first".to_string() } else { "second".to_string() }),
+            account_id: Some(index % 3),
+            ..Default::default()
+        };
+        let log_type = if index % 5 == 0 { SyncLogType::ERROR } else { SyncLogType::INFO };
+
+        let _ = create_sync_log(&db, format!("Log {}", index), log_type, context, NOW);
+    }
+
+    let first_page = get_sync_logs(&db, &SyncLogFilter::default()).unwrap();
+
+    assert_eq!(15, first_page.total);
+    assert_eq!(10, first_page.logs.len());
+    assert_eq!("Log 14", first_page.logs[0].log);
+
+    let second_page = get_sync_logs(&db, &SyncLogFilter { page: 2, ..Default::default() }).unwrap();
+
+    assert_eq!(5, second_page.logs.len());
+    assert_eq!("Log 4", second_page.logs[0].log);
+
+    let errors = get_sync_logs(&db, &SyncLogFilter { log_type: Some(SyncLogType::ERROR), ..Default::default() }).unwrap();
+
+    assert_eq!(3, errors.total);
+
+    let session = get_sync_logs(&db, &SyncLogFilter { session_id: Some("first".to_string()), ..Default::default() }).unwrap();
+
+    assert_eq!(5, session.total);
+
+    let account = get_sync_logs(&db, &SyncLogFilter { account_id: Some(0), ..Default::default() }).unwrap();
+
+    assert_eq!(5, account.total);
+}
+
+#[test]
+fn prune_old_sync_logs() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    for index in 0..5 {
+        let _ = create_sync_log(&db, format!("Log {}", index), SyncLogType::INFO, SyncLogContext::default(), NOW);
+    }
+
+    assert_eq!(2, prune_sync_logs(0, 3, &db).unwrap());
+
+    let remaining = get_sync_logs(&db, &SyncLogFilter::default()).unwrap();
+
+    assert_eq!(3, remaining.total);
+    assert_eq!("Log 4", remaining.logs[0].log);
+
+    assert_eq!(3, prune_sync_logs(i64::MAX as u64, 10, &db).unwrap());
 }
 
 #[test]
@@ -487,6 +1195,8 @@ fn get_settings_no_previous() {
     let settings = result.unwrap();
     assert_eq!(0, settings.id);
     assert_eq!(Theme::DEFAULT, settings.theme);
+    assert_eq!(30, settings.trash_retention_days);
+    assert_eq!(15, settings.sync_interval_minutes);
 }
 
 #[test]
@@ -521,6 +1231,82 @@ fn update_existing_settings() {
     assert_eq!(Theme::LIGHT, settings.theme);
 }
 
+#[test]
+fn save_trash_retention_settings() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let settings = save_trash_retention(&db, 7).unwrap();
+
+    assert_eq!(true, settings.id != 0);
+    assert_eq!(Theme::DEFAULT, settings.theme);
+    assert_eq!(7, settings.trash_retention_days);
+
+    save_settings(&db, Theme::DARK).unwrap();
+
+    let result = get_settings(&db).unwrap();
+
+    assert_eq!(settings.id, result.id);
+    assert_eq!(7, result.trash_retention_days);
+}
+
+#[test]
+fn save_sync_interval_settings() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    let settings = save_sync_interval(&db, 60).unwrap();
+
+    assert_eq!(true, settings.id != 0);
+    assert_eq!(60, settings.sync_interval_minutes);
+    assert_eq!(30, settings.trash_retention_days);
+
+    save_trash_retention(&db, 7).unwrap();
+
+    let result = get_settings(&db).unwrap();
+
+    assert_eq!(settings.id, result.id);
+    assert_eq!(60, result.sync_interval_minutes);
+}
+
+#[test]
+fn save_local_api_enabled_settings() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    assert_eq!(false, get_settings(&db).unwrap().local_api_enabled);
+
+    let settings = save_local_api_enabled(&db, true).unwrap();
+
+    assert_eq!(true, settings.id != 0);
+    assert_eq!(true, settings.local_api_enabled);
+    assert_eq!(15, settings.sync_interval_minutes);
+    assert_eq!(true, get_settings(&db).unwrap().local_api_enabled);
+}
+
+#[test]
+fn allow_and_remove_local_api_clients() {
+    let db = initialize_test_database().unwrap();
+    reset_db(&db).expect("Cant reset");
+
+    assert_eq!(false, local_api_client_allowed("firefox-token", &db).unwrap());
+
+    assert_eq!(true, allow_local_api_client("Firefox", "firefox-token", NOW, &db).unwrap());
+    assert_eq!(true, allow_local_api_client("Alfred", "alfred-token", NOW, &db).unwrap());
+
+    let clients = get_local_api_clients(&db).unwrap();
+
+    assert_eq!(2, clients.len());
+    assert_eq!("Alfred", clients[0].name);
+    assert_eq!(true, local_api_client_allowed("firefox-token", &db).unwrap());
+    assert_eq!(false, local_api_client_allowed("Firefox", &db).unwrap());
+
+    assert_eq!(true, remove_local_api_client(clients[1].id, &db).unwrap());
+    assert_eq!(false, local_api_client_allowed("firefox-token", &db).unwrap());
+    assert_eq!(true, local_api_client_allowed("alfred-token", &db).unwrap());
+    assert_eq!(false, remove_local_api_client(clients[1].id, &db).unwrap());
+}
+
 fn initialize_test_database() -> Result<Connection, rusqlite::Error> {
     let base_path = PathBuf::from("./bin");
     let sqlite_path = base_path.join(SQLITE_TEST_NAME);
@@ -532,9 +1318,13 @@ fn initialize_test_database() -> Result<Connection, rusqlite::Error> {
 
 fn reset_db(db: &Connection) -> Result<(), rusqlite::Error> {
     db.prepare("DELETE FROM accounts")?.execute([])?;
+    db.prepare("DELETE FROM account_revisions")?.execute([])?;
+    db.prepare("DELETE FROM sync_outbox")?.execute([])?;
+    db.prepare("DELETE FROM sync_failures")?.execute([])?;
     db.prepare("DELETE FROM sync_accounts")?.execute([])?;
     db.prepare("DELETE FROM sync_logs")?.execute([])?;
     db.prepare("DELETE FROM settings")?.execute([])?;
+    db.prepare("DELETE FROM local_api_clients")?.execute([])?;
 
     Ok(())
 }
\ No newline at end of file
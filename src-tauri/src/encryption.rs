@@ -6,43 +6,112 @@ use base64::Engine;
 use base64::engine::general_purpose;
 use chacha20poly1305::{aead::{Aead, AeadCore, KeyInit, OsRng}, ChaCha20Poly1305, Key, Nonce};
 use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::rand_core::RngCore;
 use crate::database::{Account, SyncAccount};
-use tauri::{AppHandle, Manager};
+use sha2::Sha256;
+use zeroize::Zeroizing;
 
 
 const KEY: &str = dotenv!("ENCRYPTION_KEY");
 const KEY_FILE_NAME: &str = "validator";
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const SALT_LENGTH: usize = 16;
+const TOKEN_LENGTH: usize = 32;
+const PASSPHRASE_ROUNDS: u32 = 100_000;
+
+#[derive(Clone)]
+pub struct DataKey(Zeroizing<[u8; KEY_LENGTH]>);
+
+impl DataKey {
+    pub fn key(&self) -> &Key {
+        Key::from_slice(self.0.as_slice())
+    }
+
+    pub fn encrypt(&self, original: &str) -> Result<String, String> {
+        encrypt_with_key(self.key(), original)
+    }
 
-pub fn encrypt(key_location_path: &PathBuf, original: &str) -> Result<String, String> {
-    let key = get_key(key_location_path);
+    pub fn decrypt(&self, encrypted: &str) -> Result<String, String> {
+        decrypt_with_key(self.key(), encrypted)
+    }
+}
 
-    let cipher = ChaCha20Poly1305::new(&key);
+pub fn encrypt_with_key(key: &Key, original: &str) -> Result<String, String> {
+    let cipher = ChaCha20Poly1305::new(key);
     let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng); // 96-bits; unique per message
-    let mut enc_data = cipher.encrypt(&nonce, original.as_ref()).unwrap();
+    let mut enc_data = match cipher.encrypt(&nonce, original.as_ref()) {
+        Ok(data) => data,
+        Err(_) => return Err("Could not encrypt value".to_string()),
+    };
 
     enc_data.splice(0..0, nonce.to_vec());
 
     Ok(general_purpose::STANDARD.encode(enc_data))
 }
 
-pub fn decrypt(key_location_path: &PathBuf, encrypted: &str) -> Result<String, String> {
-    let key = get_key(&key_location_path);
+pub fn decrypt_with_key(key: &Key, encrypted: &str) -> Result<String, String> {
+    let cipher = ChaCha20Poly1305::new(key);
+    let mut encrypt_bytes = match general_purpose::STANDARD.decode(encrypted) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err("Encrypted value is not valid base64".to_string()),
+    };
+
+    if encrypt_bytes.len() < NONCE_LENGTH {
+        return Err("Encrypted value is too short".to_string());
+    }
+
+    let nonce = GenericArray::clone_from_slice(&encrypt_bytes[0..NONCE_LENGTH]) as Nonce;
+    encrypt_bytes.splice(0..NONCE_LENGTH, []);
+
+    let plaintext = match cipher.decrypt(&nonce, encrypt_bytes.as_ref()) {
+        Ok(plaintext) => plaintext,
+        Err(_) => return Err("Could not decrypt value".to_string()),
+    };
+
+    match String::from_utf8(plaintext) {
+        Ok(result) => Ok(result),
+        Err(_) => Err("Decrypted value is not valid UTF-8".to_string()),
+    }
+}
+
+pub fn derive_passphrase_key(passphrase: &str, salt: &str) -> Key {
+    let mut key = Key::default();
+
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt.as_bytes(), PASSPHRASE_ROUNDS, &mut key);
+
+    key
+}
+
+pub fn generate_salt() -> String {
+    let mut salt = [0u8; SALT_LENGTH];
+
+    OsRng.fill_bytes(&mut salt);
 
-    let cipher = ChaCha20Poly1305::new(&key);
-    let mut encrypt_bytes = general_purpose::STANDARD.decode(encrypted).unwrap();
-    let nonce = GenericArray::clone_from_slice(&encrypt_bytes[0..12]) as Nonce;
-    encrypt_bytes.splice(0..12, []);
+    general_purpose::STANDARD.encode(salt)
+}
+
+pub fn generate_token() -> String {
+    let mut token = [0u8; TOKEN_LENGTH];
 
-    let plaintext = cipher.decrypt(&nonce, encrypt_bytes.as_ref()).unwrap();
+    OsRng.fill_bytes(&mut token);
 
-    Ok(String::from_utf8(plaintext).unwrap())
+    general_purpose::URL_SAFE_NO_PAD.encode(token)
 }
 
-pub fn decrypt_account(key_location: &PathBuf, account: &Account) -> Account
+pub fn constant_time_eq(first: &[u8], second: &[u8]) -> bool {
+    if first.len() != second.len() {
+        return false;
+    }
+
+    first.iter().zip(second).fold(0u8, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+pub fn decrypt_account(key: &DataKey, account: &Account) -> Result<Account, String>
 {
-    let secret = decrypt(key_location, &account.secret).unwrap();
+    let secret = key.decrypt(&account.secret)?;
 
-    return Account {
+    Ok(Account {
         id: account.id.clone(),
         name: account.name.clone(),
         secret,
@@ -54,23 +123,31 @@ pub fn decrypt_account(key_location: &PathBuf, account: &Account) -> Account
         external_last_updated: account.external_last_updated.clone(),
         external_hash: account.external_hash.clone(),
         deleted_at: account.deleted_at.clone(),
-    }
+        sync_account_id: account.sync_account_id,
+    })
 }
 
-pub fn decrypt_sync_account(key_location: &PathBuf, account: SyncAccount) -> SyncAccount {
-    let password = decrypt(key_location, &account.password).unwrap();
-
-    return SyncAccount {
+pub fn decrypt_sync_account(key: &DataKey, account: SyncAccount) -> Result<SyncAccount, String> {
+    let password = key.decrypt(&account.password)?;
+    let token = match &account.token {
+        Some(token) => key.decrypt(token).ok(),
+        None => None,
+    };
+    let passphrase = match &account.passphrase {
+        Some(passphrase) => Some(key.decrypt(passphrase)?),
+        None => None,
+    };
+
+    Ok(SyncAccount {
         id: account.id.clone(),
         username: account.username.clone(),
         password,
         url: account.url.clone(),
-        token: account.token.clone(),
-    }
-}
-
-pub fn get_key_directory(handle: &AppHandle) -> PathBuf {
-    handle.path().app_data_dir().expect("The App data directory should exist")
+        token,
+        sync_type: account.sync_type.clone(),
+        passphrase,
+        auth_type: account.auth_type.clone(),
+    })
 }
 
 pub fn legacy_encrypt(original: &str) -> String {
@@ -91,62 +168,105 @@ pub fn legacy_decrypt(encrypted: &str) -> Result<String, ()> {
 }
 
 
-fn get_key(base_path: &PathBuf) -> Key {
-    fs::create_dir_all(base_path).expect("The app data directory should be created.");
+// A key file of the wrong length is reported rather than trusted
+pub fn load_key(base_path: &PathBuf) -> Result<DataKey, String> {
+    fs::create_dir_all(base_path).map_err(|e| format!("Could not create key directory: {}", e))?;
     let key_path = base_path.join(KEY_FILE_NAME);
-    let file_exists = fs::exists(&key_path).expect("Could not read key directory");
+    let file_exists = fs::exists(&key_path).map_err(|e| format!("Could not read key directory: {}", e))?;
 
-    match file_exists {
-        true => GenericArray::clone_from_slice(&fs::read(&key_path).unwrap()[0..]) as Key,
-        false => create_key(&key_path)
+    if !file_exists {
+        return create_key(&key_path);
     }
+
+    let contents = Zeroizing::new(fs::read(&key_path).map_err(|e| format!("Could not read key file: {}", e))?);
+
+    if contents.len() != KEY_LENGTH {
+        return Err(format!("Key file should be {} bytes but is {}", KEY_LENGTH, contents.len()));
+    }
+
+    let mut key = DataKey(Zeroizing::new([0u8; KEY_LENGTH]));
+    key.0.copy_from_slice(&contents);
+
+    Ok(key)
 }
 
-fn create_key(key_path: &PathBuf) -> Key {
-    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+fn create_key(key_path: &PathBuf) -> Result<DataKey, String> {
+    let mut key = DataKey(Zeroizing::new([0u8; KEY_LENGTH]));
 
-    fs::write(key_path, key).expect("Could not create Key file");
+    OsRng.fill_bytes(key.0.as_mut_slice());
+    fs::write(key_path, key.0.as_slice()).map_err(|e| format!("Could not create key file: {}", e))?;
 
-    key
+    Ok(key)
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
-    use crate::database::{Account, AccountAlgorithm, SyncAccount};
-    use crate::encryption::{decrypt, decrypt_account, decrypt_sync_account, encrypt, legacy_decrypt, legacy_encrypt};
+    use crate::database::{Account, AccountAlgorithm, SyncAccount, SyncAuthType, SyncType};
+    use std::fs;
+    use crate::encryption::{constant_time_eq, decrypt_account, decrypt_sync_account, decrypt_with_key, derive_passphrase_key, encrypt_with_key, generate_salt, legacy_decrypt, legacy_encrypt, load_key};
+
+    #[test]
+    fn can_compare_tokens() {
+        assert_eq!(true, constant_time_eq(b"local-token", b"local-token"));
+        assert_eq!(false, constant_time_eq(b"local-token", b"local-tokem"));
+        assert_eq!(false, constant_time_eq(b"local-token", b"local"));
+    }
 
     #[test]
     fn can_encrypt_and_decrypt_existing_key() {
-        let path = PathBuf::from("./bin");
+        let key = load_key(&PathBuf::from("./bin")).unwrap();
         let original = "hello world";
-        let encrypted = encrypt(&path, original).unwrap();
-        let decrypted = decrypt(&path, &encrypted).unwrap();
+        let encrypted = key.encrypt(original).unwrap();
+        let decrypted = key.decrypt(&encrypted).unwrap();
 
         assert_ne!(encrypted, "hello world");
         assert_eq!(decrypted, "hello world");
 
         let predefined_encrypted = "IJAJctNE9bichzwx5YtpKuU62ncethJ0p9HLymqueV1sdQEzfFb5";
-        let predefined_decrypted = decrypt(&path, &predefined_encrypted).unwrap();
+        let predefined_decrypted = key.decrypt(&predefined_encrypted).unwrap();
 
         assert_eq!(predefined_decrypted, "hello world");
     }
 
     #[test]
     fn can_encrypt_and_decrypt_missing_key() {
-        let path = PathBuf::from("./bin/blank");
+        let key = load_key(&PathBuf::from("./bin/blank")).unwrap();
         let original = "hello world";
-        let encrypted = encrypt(&path, original).unwrap();
-        let decrypted = decrypt(&path, &encrypted).unwrap();
+        let encrypted = key.encrypt(original).unwrap();
+        let decrypted = key.decrypt(&encrypted).unwrap();
 
         assert_ne!(encrypted, "hello world");
         assert_eq!(decrypted, "hello world");
     }
 
     #[test]
-    fn can_decrypt_account() {
+    fn cached_key_matches_key_file() {
         let path = PathBuf::from("./bin");
-        let secret = encrypt(&path, "hello world").unwrap();
+        let key = load_key(&path).unwrap();
+        let encrypted = key.encrypt("hello world").unwrap();
+
+        assert_eq!(load_key(&path).unwrap().decrypt(&encrypted).unwrap(), "hello world");
+        assert_eq!(key.decrypt("IJAJctNE9bichzwx5YtpKuU62ncethJ0p9HLymqueV1sdQEzfFb5").unwrap(), "hello world");
+    }
+
+    #[test]
+    fn short_key_file_is_rejected() {
+        let path = std::env::temp_dir().join(format!("phoenix_short_key_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("validator"), [1u8; 16]).unwrap();
+
+        let loaded = load_key(&path);
+        fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(Some("Key file should be 32 bytes but is 16".to_string()), loaded.err());
+    }
+
+    #[test]
+    fn can_decrypt_account() {
+        let key = load_key(&PathBuf::from("./bin")).unwrap();
+        let secret = key.encrypt("hello world").unwrap();
 
         let account = Account {
             id: 1,
@@ -160,9 +280,10 @@ mod tests {
             external_last_updated: Option::from(2003),
             external_hash: Option::from("HelloWorld".to_string()),
             deleted_at: Option::from(23),
+            sync_account_id: None,
         };
 
-        let decrypted_account = decrypt_account(&path, &account);
+        let decrypted_account = decrypt_account(&key, &account).unwrap();
 
         assert_eq!(decrypted_account.id, 1);
         assert_eq!(decrypted_account.name, "This is a test".to_string());
@@ -179,24 +300,46 @@ mod tests {
 
     #[test]
     fn can_decrypt_sync_account() {
-        let path = PathBuf::from("./bin");
-        let password = encrypt(&path, "hello world").unwrap();
-        
+        let key = load_key(&PathBuf::from("./bin")).unwrap();
+        let password = key.encrypt("hello world").unwrap();
+        let token = key.encrypt("token").unwrap();
+
         let sync_account = SyncAccount {
             id: 1,
             username: "username".to_string(),
             password,
             url: "https://test.com".to_string(),
-            token: Option::from("token".to_string()),
+            token: Option::from(token),
+            sync_type: SyncType::PHOENIX,
+            passphrase: Option::from(key.encrypt("passphrase").unwrap()),
+            auth_type: SyncAuthType::BASIC,
         };
 
-        let decrypted_account = decrypt_sync_account(&path, sync_account);
+        let decrypted_account = decrypt_sync_account(&key, sync_account).unwrap();
 
         assert_eq!(decrypted_account.id, 1);
         assert_eq!(decrypted_account.username, "username".to_string());
         assert_eq!(decrypted_account.password, "hello world".to_string());
         assert_eq!(decrypted_account.url, "https://test.com".to_string());
         assert_eq!(decrypted_account.token, Option::from("token".to_string()));
+        assert_eq!(decrypted_account.passphrase, Option::from("passphrase".to_string()));
+        assert_eq!(decrypted_account.sync_type, SyncType::PHOENIX);
+    }
+
+    #[test]
+    fn can_encrypt_and_decrypt_with_passphrase_key() {
+        let salt = generate_salt();
+        let key = derive_passphrase_key("correct horse", &salt);
+        let encrypted = encrypt_with_key(&key, "hello world").unwrap();
+
+        assert_ne!(encrypted, "hello world");
+        assert_eq!(decrypt_with_key(&key, &encrypted).unwrap(), "hello world");
+        assert_eq!(key, derive_passphrase_key("correct horse", &salt));
+
+        let wrong_key = derive_passphrase_key("battery staple", &salt);
+
+        assert_eq!(true, decrypt_with_key(&wrong_key, &encrypted).is_err());
+        assert_eq!(true, decrypt_with_key(&key, "not base64!").is_err());
     }
 
     #[test]
@@ -3,56 +3,43 @@
 
 mod database;
 mod encryption;
+mod local_api;
 mod otp_exporter;
 mod otp_parser;
 mod state;
 mod sync_api;
+mod sync_backend;
+mod sync_coordinator;
+mod sync_scheduler;
+mod sync_folder;
 mod sync_local;
+mod sync_webdav;
+#[cfg(test)]
+mod test_support;
+pub mod vault;
 
-use crate::database::{SyncAccount, Theme, Setting};
-use crate::otp_exporter::account_to_url;
+use crate::database::{Account, AccountRevision, LocalApiClient, SyncAccount, SyncAuthType, SyncLogFilter, SyncType, Theme, Setting};
 use crate::otp_parser::{is_valid_url, parse_url};
 use crate::state::ServiceAccess;
-use crate::sync_api::get_jwt_token;
-use libotp::{totp, totp_override};
+use crate::sync_api::{PhoenixBackend, SyncClient};
+use crate::sync_backend::SyncBackend;
+use crate::sync_folder::FolderBackend;
+use crate::sync_local::SyncReport;
+use crate::sync_webdav::WebDavBackend;
+use crate::vault::{AccountCode, OneTimePassword, Vault};
+use serde::Deserialize;
 use state::AppState;
 use tauri::{AppHandle, Manager, State};
 use tauri_plugin_updater::UpdaterExt;
 
 #[tauri::command]
-fn get_one_time_password_for_account(app_handle: AppHandle, account: u32) -> String {
-    let account = app_handle
-        .db(|db| database::get_account_details_by_id(account, db))
-        .unwrap();
-
-    if account.id == 0 {
-        return "Failed to generate OTP".to_string();
-    }
-
-    let decrypted_secret = encryption::decrypt(&encryption::get_key_directory(&app_handle), &account.secret).unwrap();
-
-    if account.algorithm.is_some() {
-        return match totp_override(
-            &decrypted_secret,
-            account.otp_digits as u32,
-            account.totp_step as u64,
-            0,
-            account.algorithm.unwrap().to_hotp_algorithm(),
-        ) {
-            Some(otp) => otp.to_string(),
-            None => "Failed to generate OTP".to_string(),
-        };
-    }
+fn get_one_time_password_for_account(app_handle: AppHandle, account: u32) -> Result<OneTimePassword, String> {
+    app_handle.vault(|vault| vault.get_one_time_password(account))
+}
 
-    match totp(
-        &decrypted_secret,
-        account.otp_digits as u32,
-        account.totp_step as u64,
-        0,
-    ) {
-        Some(otp) => otp.to_string(),
-        None => "Failed to generate OTP".to_string(),
-    }
+#[tauri::command]
+fn get_codes_for_accounts(app_handle: AppHandle, account_ids: Vec<i32>) -> Result<Vec<AccountCode>, String> {
+    app_handle.vault(|vault| vault.get_codes_for_accounts(account_ids))
 }
 
 #[tauri::command]
@@ -65,38 +52,23 @@ fn create_new_account(
     colour: &str,
     algorithm: &str,
 ) -> String {
-    let account_exists = app_handle
-        .db(|db| database::account_name_exists(name, db))
-        .unwrap();
-
-    if account_exists {
-        return format!("Account already exists: {}", name);
-    }
+    let result = app_handle.vault(|vault| vault.create_account(name, secret, digits, step, colour, algorithm));
 
-    if totp(secret, digits as u32, step as u64, 0).is_none() {
-        return "Invalid 2FA Secret".to_string();
+    match result {
+        Ok(account) => {
+            sync_scheduler::sync_after_edit(&app_handle);
+            format!("Created account called: {}", account.name)
+        }
+        Err(err) => err,
     }
-
-    let encryption_secret = encryption::encrypt(&encryption::get_key_directory(&app_handle), secret).unwrap();
-
-    app_handle
-        .db(|db| {
-            database::create_new_account(name, &encryption_secret, &digits, &step, colour, algorithm, db)
-        })
-        .unwrap();
-
-    format!("Created account called: {}", name)
 }
 
 #[tauri::command]
 fn get_editable_account(app_handle: AppHandle, account_id: u32) -> String {
-    let account = app_handle
-        .db(|db| database::get_account_details_by_id(account_id, db))
-        .unwrap();
-
-    if account.id == 0 {
-        return "{\"Error\": \"Invalid account id\"}".to_string();
-    }
+    let account = match app_handle.vault(|vault| vault.get_account(account_id)) {
+        Some(account) => account,
+        None => return "{\"Error\": \"Invalid account id\"}".to_string(),
+    };
 
     match serde_json::to_string(&account) {
         Ok(result) => result,
@@ -114,37 +86,34 @@ fn edit_account(
     colour: &str,
     algorithm: &str,
 ) -> String {
-    let account = app_handle
-        .db(|db| database::get_account_details_by_id(id.try_into().unwrap(), db))
-        .unwrap();
-
-    let account = app_handle
-        .db(|db| {
-            database::update_existing_account(
-                &id,
-                &name,
-                &account.secret,
-                digits,
-                step,
-                colour,
-                algorithm,
-                db,
-            )
-        })
-        .unwrap();
+    let result = app_handle.vault(|vault| vault.edit_account(id, name, digits, step, colour, algorithm));
+
+    match result {
+        Ok(_) => {
+            sync_scheduler::sync_after_edit(&app_handle);
+            "Updated account".to_string()
+        }
+        Err(err) => err,
+    }
+}
 
-    let _ = app_handle
-        .db(|db| database::update_local_updated_at(db, &account))
-        .unwrap();
+#[tauri::command]
+fn get_account_revisions(app_handle: AppHandle, account_id: i32) -> Result<Vec<AccountRevision>, ()> {
+    Ok(app_handle.vault(|vault| vault.get_account_revisions(account_id)))
+}
+
+#[tauri::command]
+fn restore_account_revision(app_handle: AppHandle, revision_id: i32) -> Result<bool, String> {
+    let updated = app_handle.vault(|vault| vault.restore_account_revision(revision_id))?;
+
+    sync_scheduler::sync_after_edit(&app_handle);
 
-    format!("Updated account")
+    Ok(updated)
 }
 
 #[tauri::command]
 fn get_all_accounts(app_handle: AppHandle, filter: &str) -> String {
-    let accounts = app_handle
-        .db(|db| database::get_all_accounts(db, filter))
-        .unwrap();
+    let accounts = app_handle.vault(|vault| vault.get_all_accounts(filter));
 
     match serde_json::to_string(&accounts) {
         Ok(result) => result,
@@ -154,19 +123,36 @@ fn get_all_accounts(app_handle: AppHandle, filter: &str) -> String {
 
 #[tauri::command]
 fn delete_account(app_handle: AppHandle, account_id: u32) -> String {
-    let account = app_handle
-        .db(|db| database::get_account_details_by_id(account_id, db))
-        .unwrap();
-    let result = app_handle
-        .db(|db| database::delete_account(&account, db))
-        .unwrap();
-
-    match result {
-        true => "Success".to_string(),
+    match app_handle.vault(|vault| vault.delete_account(account_id)) {
+        true => {
+            sync_scheduler::sync_after_edit(&app_handle);
+            "Success".to_string()
+        }
         false => "Failure".to_string(),
     }
 }
 
+#[tauri::command]
+fn get_trashed_accounts(app_handle: AppHandle) -> Result<Vec<Account>, ()> {
+    Ok(app_handle.vault(|vault| vault.get_trashed_accounts()))
+}
+
+#[tauri::command]
+fn restore_account(app_handle: AppHandle, account_id: u32) -> Result<bool, String> {
+    let restored = app_handle.vault(|vault| vault.restore_account(account_id))?;
+
+    if restored {
+        sync_scheduler::sync_after_edit(&app_handle);
+    }
+
+    Ok(restored)
+}
+
+#[tauri::command]
+fn purge_account(app_handle: AppHandle, account_id: u32) -> bool {
+    app_handle.vault(|vault| vault.purge_account(account_id))
+}
+
 #[tauri::command]
 fn parse_otp_url(otp_url: &str) -> String {
     if !is_valid_url(otp_url) {
@@ -182,102 +168,96 @@ fn parse_otp_url(otp_url: &str) -> String {
 }
 
 #[tauri::command]
-fn export_accounts_to_wa(app_handle: AppHandle) -> String {
-    let base_accounts = app_handle
-        .db(|db| database::get_all_accounts(db, ""))
-        .unwrap();
-    let mut otps: String = "".to_owned();
+fn export_accounts_to_wa(app_handle: AppHandle) -> Result<String, String> {
+    app_handle.vault(|vault| vault.export_accounts())
+}
 
-    for base_account in base_accounts {
-        let verbose_account = app_handle
-            .db(|db| database::get_account_details_by_id(base_account.id as u32, db))
-            .unwrap();
-        let url = account_to_url(
-            encryption::decrypt_account(&encryption::get_key_directory(&app_handle), &verbose_account)
-        );
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncAccountDetails {
+    host: String,
+    username: String,
+    password: String,
+    sync_type: i8,
+    passphrase: Option<String>,
+    auth_type: i8,
+}
 
-        otps.push_str(&url);
-        otps.push('\n');
+impl SyncAccountDetails {
+    fn into_sync_account(self, id: Option<i32>) -> SyncAccount {
+        SyncAccount {
+            id: id.unwrap_or(0),
+            username: self.username,
+            password: self.password,
+            url: self.host,
+            token: None,
+            sync_type: SyncType::num_to_sync_type(self.sync_type),
+            passphrase: self.passphrase.filter(|passphrase| !passphrase.is_empty()),
+            auth_type: SyncAuthType::num_to_auth_type(self.auth_type),
+        }
     }
-
-    otps
 }
 
 #[tauri::command]
-async fn validate_sync_account(
-    host: &str,
-    username: &str,
-    password: &str,
-) -> Result<String, String> {
-    let token_response = get_jwt_token(host, username, password).await;
-    match token_response {
+async fn validate_sync_account(details: SyncAccountDetails, app_handle: AppHandle) -> Result<String, String> {
+    let sync_account = details.into_sync_account(None);
+
+    let validation_response = match sync_account.sync_type {
+        SyncType::PHOENIX => PhoenixBackend::connect(app_handle.sync_client(), sync_account)
+            .await
+            .map(|backend| backend.sync_account().token.clone().unwrap_or_default()),
+        SyncType::FOLDER => FolderBackend::validate(&sync_account).map(|_| "".to_string()),
+        SyncType::WEBDAV => WebDavBackend::connect(app_handle.sync_client(), sync_account)
+            .await
+            .map(|_| "".to_string()),
+    };
+
+    match validation_response {
         Ok(token) => Ok(token),
         Err(e) => Err(e.formatted_message()),
     }
 }
 
 #[tauri::command]
-fn save_sync_account(
-    host: &str,
-    username: &str,
-    password: &str,
-    app_handle: AppHandle,
-) -> Result<SyncAccount, ()> {
-    let existing_account = app_handle.db(database::get_main_sync_account).unwrap();
-    let encrypted_password = encryption::encrypt(&encryption::get_key_directory(&app_handle), password).unwrap();
-
-    if existing_account.id == 0 {
-        let new_account = app_handle
-            .db(|db| database::create_sync_account(username, &encrypted_password, host, db))
-            .unwrap();
-        return Ok(new_account);
+fn save_sync_account(id: Option<i32>, details: SyncAccountDetails, app_handle: AppHandle) -> Result<SyncAccount, ()> {
+    let (sync_account, updated) = app_handle
+        .vault(|vault| vault.save_sync_account(details.into_sync_account(id)))
+        .map_err(|_| ())?;
+
+    if updated {
+        sync_accounts_with_remote(app_handle);
     }
 
-    let updated_sync_account = SyncAccount {
-        id: existing_account.id,
-        username: username.to_string(),
-        password: encrypted_password.to_string(),
-        url: host.to_string(),
-        token: None,
-    };
+    Ok(sync_account)
+}
 
+#[tauri::command]
+fn get_existing_sync_account(app_handle: AppHandle) -> Result<SyncAccount, String> {
     app_handle
-        .db(|db| database::update_sync_account(updated_sync_account, db))
-        .unwrap();
-
-    sync_accounts_with_remote(app_handle);
-
-    Ok(SyncAccount {
-        id: existing_account.id,
-        username: username.to_string(),
-        password: password.to_string(),
-        url: host.to_string(),
-        token: None,
-    })
+        .vault(|vault| vault.get_main_sync_account())
+        .ok_or_else(|| "Sync Account does not exist".to_string())
 }
 
 #[tauri::command]
-fn get_existing_sync_account(app_handle: AppHandle) -> Result<SyncAccount, String> {
-    let existing_account = app_handle.db(database::get_main_sync_account).unwrap();
+fn get_sync_accounts(app_handle: AppHandle) -> Result<Vec<SyncAccount>, ()> {
+    Ok(app_handle.vault(|vault| vault.get_sync_accounts()))
+}
 
-    if existing_account.id == 0 {
-        return Err("Sync Account does not exist".to_string());
-    }
+#[tauri::command]
+fn remove_sync_account(app_handle: AppHandle, id: i32) -> bool {
+    app_handle.vault(|vault| vault.remove_sync_account(id))
+}
 
-    Ok(SyncAccount {
-        id: existing_account.id,
-        username: existing_account.username,
-        password: "".to_string(),
-        url: existing_account.url,
-        token: None,
-    })
+#[tauri::command]
+fn set_account_sync_target(app_handle: AppHandle, account_id: i32, sync_account_id: Option<i32>) -> bool {
+    app_handle.vault(|vault| vault.set_account_sync_target(account_id, sync_account_id))
 }
 
 #[tauri::command]
-fn get_sync_logs(app_handle: AppHandle) -> String {
-    let accounts = app_handle.db(database::get_sync_logs).unwrap();
+fn get_sync_logs(app_handle: AppHandle, filter: Option<SyncLogFilter>) -> String {
+    let logs = app_handle.vault(|vault| vault.get_sync_logs(filter));
 
-    match serde_json::to_string(&accounts) {
+    match serde_json::to_string(&logs) {
         Ok(result) => result,
         _ => "{\"Error\": \"Can't get sync logs\"}".to_string(),
     }
@@ -285,31 +265,80 @@ fn get_sync_logs(app_handle: AppHandle) -> String {
 
 #[tauri::command]
 fn attempt_sync_with_remote(app_handle: AppHandle) -> bool {
-    sync_accounts_with_remote(app_handle);
+    sync_accounts_with_remote(app_handle)
+}
 
-    true
+#[tauri::command]
+fn cancel_sync(app_handle: AppHandle) -> bool {
+    app_handle.sync_coordinator().cancel()
 }
 
 #[tauri::command]
-fn get_settings(app_handle: AppHandle) -> Result<Setting, ()> {
-    let settings = app_handle.db(database::get_settings).unwrap();
+fn get_sync_status(app_handle: AppHandle) -> SyncReport {
+    app_handle.sync_report()
+}
+
+#[tauri::command]
+fn confirm_held_deletions(app_handle: AppHandle, sync_account_id: i32) -> Result<usize, String> {
+    sync_local::confirm_held_deletions(&app_handle, sync_account_id)
+}
 
-    return Ok(settings)
+#[tauri::command]
+fn get_settings(app_handle: AppHandle) -> Result<Setting, ()> {
+    Ok(app_handle.vault(|vault| vault.get_settings()))
 }
 
 #[tauri::command]
 fn save_settings(theme: i8, app_handle: AppHandle) -> Result<Setting, ()> {
-    let settings = app_handle.db(|db| database::save_settings(&db, Theme::num_to_theme(theme))).unwrap();
+    Ok(app_handle.vault(|vault| vault.save_theme(Theme::num_to_theme(theme))))
+}
+
+#[tauri::command]
+fn save_trash_retention(trash_retention_days: u32, app_handle: AppHandle) -> Result<Setting, ()> {
+    Ok(app_handle.vault(|vault| vault.save_trash_retention(trash_retention_days)))
+}
 
-    return Ok(settings)
+#[tauri::command]
+fn save_sync_interval(sync_interval_minutes: u32, app_handle: AppHandle) -> Result<Setting, ()> {
+    Ok(app_handle.vault(|vault| vault.save_sync_interval(sync_interval_minutes)))
 }
 
-fn sync_accounts_with_remote(app_handle: AppHandle) {
-    let sync_account = app_handle.db(database::get_main_sync_account).unwrap();
+#[tauri::command]
+fn save_local_api_enabled(local_api_enabled: bool, app_handle: AppHandle) -> Result<Setting, ()> {
+    let settings = app_handle.vault(|vault| vault.save_local_api_enabled(local_api_enabled));
 
-    if sync_account.id != 0 {
-        tauri::async_runtime::spawn(sync_local::sync_all_accounts(app_handle, sync_account));
+    match settings.local_api_enabled {
+        true => local_api::start(app_handle),
+        false => app_handle.local_api().stop(),
     }
+
+    Ok(settings)
+}
+
+#[tauri::command]
+fn get_local_api_error(app_handle: AppHandle) -> Option<String> {
+    app_handle.local_api().error()
+}
+
+#[tauri::command]
+fn get_local_api_clients(app_handle: AppHandle) -> Result<Vec<LocalApiClient>, ()> {
+    Ok(app_handle.vault(|vault| vault.get_local_api_clients()))
+}
+
+#[tauri::command]
+fn remove_local_api_client(app_handle: AppHandle, id: i32) -> bool {
+    app_handle.vault(|vault| vault.remove_local_api_client(id))
+}
+
+#[tauri::command]
+fn set_sync_online(online: bool, app_handle: AppHandle) {
+    let now = app_handle.vault(|vault| vault.now());
+
+    app_handle.sync_scheduler().set_online(online, now);
+}
+
+fn sync_accounts_with_remote(app_handle: AppHandle) -> bool {
+    sync_coordinator::request_sync(app_handle)
 }
 
 async fn check_for_updates(app_handle: AppHandle) -> tauri_plugin_updater::Result<()> {
@@ -343,40 +372,68 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .manage(AppState {
-            db: Default::default(),
+            vault: Default::default(),
+            sync_client: SyncClient::new(),
+            sync_report: Default::default(),
+            sync_coordinator: Default::default(),
+            sync_scheduler: Default::default(),
+            local_api: Default::default(),
         })
         .invoke_handler(tauri::generate_handler![
             create_new_account,
             get_all_accounts,
             delete_account,
+            get_trashed_accounts,
+            restore_account,
+            purge_account,
             get_one_time_password_for_account,
+            get_codes_for_accounts,
             parse_otp_url,
             export_accounts_to_wa,
             validate_sync_account,
             save_sync_account,
             get_existing_sync_account,
+            get_sync_accounts,
+            remove_sync_account,
+            set_account_sync_target,
             get_sync_logs,
             attempt_sync_with_remote,
+            get_sync_status,
+            confirm_held_deletions,
+            cancel_sync,
             get_editable_account,
             edit_account,
+            get_account_revisions,
+            restore_account_revision,
             get_settings,
             save_settings,
+            save_trash_retention,
+            save_sync_interval,
+            set_sync_online,
+            save_local_api_enabled,
+            get_local_api_error,
+            get_local_api_clients,
+            remove_local_api_client,
         ])
         .setup(|app| {
             let handle = app.handle();
             let app_data_dir = app.path().app_data_dir().expect("The App data directory should exist");
 
             let app_state: State<AppState> = handle.state();
-            let db = database::initialize_prod_database(app_data_dir.clone(), app_data_dir.clone())
-                .expect("Database initialize should succeed");
+            let vault = Vault::open(app_data_dir).expect("Database initialize should succeed");
 
-            *app_state.db.lock().unwrap() = Some(db);
+            *app_state.vault.lock().unwrap() = Some(vault);
 
             let update_handle = handle.clone();
             tauri::async_runtime::spawn(async move {
                 check_for_updates(update_handle).await.unwrap();
             });
             sync_accounts_with_remote(handle.clone());
+            sync_scheduler::start(handle.clone());
+
+            if handle.vault(|vault| vault.get_settings().local_api_enabled) {
+                local_api::start(handle.clone());
+            }
 
             Ok(())
         })
@@ -0,0 +1,396 @@
+use crate::encryption;
+use crate::state::ServiceAccess;
+use crate::vault::Vault;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::async_runtime::JoinHandle;
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::task::JoinSet;
+
+pub const CONNECTION_FILE_NAME: &str = "local-api.json";
+#[cfg(unix)]
+const SOCKET_DIRECTORY_NAME: &str = "local-api";
+#[cfg(unix)]
+const SOCKET_NAME: &str = "phoenix.sock";
+
+#[cfg(unix)]
+type Listener = std::os::unix::net::UnixListener;
+#[cfg(not(unix))]
+type Listener = std::net::TcpListener;
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum LocalApiAction {
+    Pair,
+    List,
+    Search { query: String },
+    GetCode { account: String },
+}
+
+impl LocalApiAction {
+    fn describe(&self) -> String {
+        match self {
+            LocalApiAction::Pair => "read codes without asking each time".to_string(),
+            LocalApiAction::List => "list your accounts".to_string(),
+            LocalApiAction::Search { query } => format!("search your accounts for \"{}\"", query),
+            LocalApiAction::GetCode { account } => format!("read the code for \"{}\"", account),
+        }
+    }
+}
+
+// One JSON object per line, e.g. {"client": "Firefox", "token": "...", "clientToken": "...", "action": "getCode", "account": "github"}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalApiRequest {
+    pub client: String,
+    pub token: String,
+    #[serde(default)]
+    pub client_token: Option<String>,
+    #[serde(flatten)]
+    pub action: LocalApiAction,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LocalApiResponse {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LocalApiResponse {
+    fn from_result(result: Result<Value, String>) -> LocalApiResponse {
+        match result {
+            Ok(data) => LocalApiResponse {
+                ok: true,
+                data: Some(data),
+                error: None,
+            },
+            Err(error) => LocalApiResponse {
+                ok: false,
+                data: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+pub trait Approver {
+    fn approve(&self, client: &str, action: &LocalApiAction) -> bool;
+}
+
+struct DialogApprover<'a> {
+    app_handle: &'a AppHandle,
+}
+
+impl Approver for DialogApprover<'_> {
+    fn approve(&self, client: &str, action: &LocalApiAction) -> bool {
+        self.app_handle
+            .dialog()
+            .message(format!("{} wants to {}.", client, action.describe()))
+            .title("Allow Local Access?")
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                "Allow".to_string(),
+                "Deny".to_string(),
+            ))
+            .blocking_show()
+    }
+}
+
+struct RunningApi {
+    task: JoinHandle<()>,
+    files: Vec<PathBuf>,
+}
+
+#[derive(Clone, Default)]
+pub struct LocalApi {
+    running: Arc<Mutex<Option<RunningApi>>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl LocalApi {
+    fn run(&self, task: JoinHandle<()>, files: Vec<PathBuf>) {
+        let previous = self
+            .running
+            .lock()
+            .unwrap()
+            .replace(RunningApi { task, files });
+
+        if let Some(previous) = previous {
+            previous.task.abort();
+        }
+    }
+
+    // Connections are owned by the accept loop, so aborting it closes them too
+    pub fn stop(&self) {
+        if let Some(running) = self.running.lock().unwrap().take() {
+            running.task.abort();
+
+            for file in running.files {
+                let _ = fs::remove_file(file);
+            }
+        }
+
+        self.set_error(None);
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+
+    fn set_error(&self, error: Option<String>) {
+        *self.error.lock().unwrap() = error;
+    }
+}
+
+pub fn parse_request(line: &str) -> Result<LocalApiRequest, String> {
+    serde_json::from_str(line).map_err(|e| format!("Invalid request: {}", e))
+}
+
+pub fn authorize(
+    request: &LocalApiRequest,
+    token: &str,
+    allowed: bool,
+    approver: &impl Approver,
+) -> Result<(), String> {
+    if !encryption::constant_time_eq(request.token.as_bytes(), token.as_bytes()) {
+        return Err("Invalid token".to_string());
+    }
+
+    // Pairing always asks, it is how a client gets allowed in the first place
+    let allowed = allowed && request.action != LocalApiAction::Pair;
+
+    if allowed || approver.approve(&request.client, &request.action) {
+        return Ok(());
+    }
+
+    Err("Request denied".to_string())
+}
+
+pub fn handle_action(
+    vault: &Vault,
+    client: &str,
+    action: &LocalApiAction,
+) -> Result<Value, String> {
+    match action {
+        LocalApiAction::Pair => Ok(json!({ "clientToken": vault.pair_local_api_client(client)? })),
+        LocalApiAction::List => Ok(accounts_to_json(vault, "")),
+        LocalApiAction::Search { query } => Ok(accounts_to_json(vault, query)),
+        LocalApiAction::GetCode { account } => {
+            let account = vault.find_account(account)?;
+            let otp = vault.get_one_time_password(account.id as u32)?;
+
+            Ok(json!({
+                "id": account.id,
+                "name": account.name,
+                "code": otp.code,
+                "secondsRemaining": otp.seconds_remaining,
+            }))
+        }
+    }
+}
+
+fn accounts_to_json(vault: &Vault, filter: &str) -> Value {
+    vault
+        .get_all_accounts(filter)
+        .into_iter()
+        .map(|account| json!({ "id": account.id, "name": account.name }))
+        .collect()
+}
+
+fn respond(app_handle: &AppHandle, token: &str, line: &str) -> LocalApiResponse {
+    let request = match parse_request(line) {
+        Ok(request) => request,
+        Err(e) => return LocalApiResponse::from_result(Err(e)),
+    };
+
+    let (enabled, allowed) = app_handle.vault(|vault| {
+        (
+            vault.get_settings().local_api_enabled,
+            request
+                .client_token
+                .as_deref()
+                .is_some_and(|client_token| vault.local_api_client_allowed(client_token)),
+        )
+    });
+
+    if !enabled {
+        return LocalApiResponse::from_result(Err("Local access is disabled".to_string()));
+    }
+
+    let approver = DialogApprover { app_handle };
+
+    if let Err(e) = authorize(&request, token, allowed, &approver) {
+        return LocalApiResponse::from_result(Err(e));
+    }
+
+    LocalApiResponse::from_result(
+        app_handle.vault(|vault| handle_action(vault, &request.client, &request.action)),
+    )
+}
+
+fn load_token(directory: &Path) -> io::Result<String> {
+    let connection_path = directory.join(CONNECTION_FILE_NAME);
+
+    if let Ok(contents) = fs::read_to_string(&connection_path) {
+        if let Ok(connection) = serde_json::from_str::<Value>(&contents) {
+            if let Some(token) = connection["token"].as_str() {
+                return Ok(token.to_string());
+            }
+        }
+    }
+
+    Ok(encryption::generate_token())
+}
+
+fn write_connection_file(directory: &Path, connection: Value) -> io::Result<()> {
+    let connection_path = directory.join(CONNECTION_FILE_NAME);
+    let mut options = fs::OpenOptions::new();
+
+    options.write(true).create_new(true);
+
+    // Created owner-only from the start, the token is never readable by other users
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.mode(0o600);
+    }
+
+    let _ = fs::remove_file(&connection_path);
+    let mut file = options.open(&connection_path)?;
+
+    file.write_all(connection.to_string().as_bytes())
+}
+
+async fn handle_connection<S>(app_handle: AppHandle, stream: S, token: String)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let request_handle = app_handle.clone();
+        let request_token = token.clone();
+        let response = tauri::async_runtime::spawn_blocking(move || {
+            respond(&request_handle, &request_token, &line)
+        })
+        .await;
+
+        let Ok(response) = response else {
+            break;
+        };
+
+        let mut payload = serde_json::to_string(&response).unwrap();
+        payload.push('\n');
+
+        if writer.write_all(payload.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+// The socket is created inside an owner-only directory, so it is never reachable by other users, not even briefly
+#[cfg(unix)]
+fn bind(directory: &Path, token: &str) -> io::Result<(Listener, Vec<PathBuf>)> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let socket_directory = directory.join(SOCKET_DIRECTORY_NAME);
+    let socket_path = socket_directory.join(SOCKET_NAME);
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&socket_directory)?;
+    fs::set_permissions(&socket_directory, fs::Permissions::from_mode(0o700))?;
+
+    let _ = fs::remove_file(&socket_path);
+    let listener = Listener::bind(&socket_path)?;
+    listener.set_nonblocking(true)?;
+
+    write_connection_file(directory, json!({ "socket": socket_path, "token": token }))?;
+
+    Ok((
+        listener,
+        vec![socket_path, directory.join(CONNECTION_FILE_NAME)],
+    ))
+}
+
+#[cfg(not(unix))]
+fn bind(directory: &Path, token: &str) -> io::Result<(Listener, Vec<PathBuf>)> {
+    let listener = Listener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+
+    write_connection_file(
+        directory,
+        json!({ "port": listener.local_addr()?.port(), "token": token }),
+    )?;
+
+    Ok((listener, vec![directory.join(CONNECTION_FILE_NAME)]))
+}
+
+#[cfg(unix)]
+async fn serve(app_handle: AppHandle, listener: Listener, token: String) -> io::Result<()> {
+    let listener = tokio::net::UnixListener::from_std(listener)?;
+    let mut connections = JoinSet::new();
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+
+        while connections.try_join_next().is_some() {}
+        connections.spawn(handle_connection(app_handle.clone(), stream, token.clone()));
+    }
+}
+
+#[cfg(not(unix))]
+async fn serve(app_handle: AppHandle, listener: Listener, token: String) -> io::Result<()> {
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let mut connections = JoinSet::new();
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+
+        while connections.try_join_next().is_some() {}
+        connections.spawn(handle_connection(app_handle.clone(), stream, token.clone()));
+    }
+}
+
+// Failures are kept on the LocalApi so the settings page can show them
+pub fn start(app_handle: AppHandle) {
+    let local_api = app_handle.local_api();
+    local_api.stop();
+
+    let directory = app_handle.key_directory();
+    let bound = load_token(&directory)
+        .and_then(|token| bind(&directory, &token).map(|bound| (bound, token)));
+
+    let ((listener, files), token) = match bound {
+        Ok(bound) => bound,
+        Err(e) => {
+            local_api.set_error(Some(format!("Local access could not start: {}", e)));
+            return;
+        }
+    };
+
+    let task_api = local_api.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve(app_handle, listener, token).await {
+            task_api.set_error(Some(format!("Local access stopped: {}", e)));
+        }
+    });
+
+    local_api.run(task, files);
+}
+
+#[cfg(test)]
+#[path = "./local_api_test.rs"]
+mod tests;
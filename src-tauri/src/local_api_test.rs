@@ -0,0 +1,208 @@
+use crate::local_api::{
+    authorize, bind, handle_action, parse_request, Approver, LocalApi, LocalApiAction,
+};
+use crate::test_support;
+use crate::vault::{SystemClock, Vault};
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+
+const TEST_SECRET: &str = "JBSWY3DPEHPK3PXP";
+const TEST_TOKEN: &str = "local-token";
+
+struct TestApprover {
+    approve: bool,
+    prompts: RefCell<Vec<String>>,
+}
+
+impl Approver for TestApprover {
+    fn approve(&self, client: &str, _action: &LocalApiAction) -> bool {
+        self.prompts.borrow_mut().push(client.to_string());
+        self.approve
+    }
+}
+
+fn test_approver(approve: bool) -> TestApprover {
+    TestApprover {
+        approve,
+        prompts: RefCell::new(vec![]),
+    }
+}
+
+#[test]
+fn test_parse_request() {
+    let request = parse_request(
+        r#"{"client": "Firefox", "token": "local-token", "action": "getCode", "account": "github"}"#,
+    )
+    .unwrap();
+
+    assert_eq!("Firefox", request.client);
+    assert_eq!(
+        LocalApiAction::GetCode {
+            account: "github".to_string()
+        },
+        request.action
+    );
+
+    let request =
+        parse_request(r#"{"client": "Alfred", "token": "local-token", "action": "list"}"#).unwrap();
+    assert_eq!(LocalApiAction::List, request.action);
+    assert_eq!(None, request.client_token);
+
+    let request = parse_request(
+        r#"{"client": "Alfred", "token": "local-token", "clientToken": "alfred", "action": "list"}"#,
+    )
+    .unwrap();
+    assert_eq!(Some("alfred".to_string()), request.client_token);
+
+    assert_eq!(
+        true,
+        parse_request(r#"{"client": "Alfred", "token": "local-token", "action": "delete"}"#)
+            .is_err()
+    );
+    assert_eq!(true, parse_request(r#"{"action": "list"}"#).is_err());
+}
+
+#[test]
+fn test_authorize_requires_token() {
+    let request =
+        parse_request(r#"{"client": "Firefox", "token": "wrong", "action": "list"}"#).unwrap();
+    let approver = test_approver(true);
+
+    assert_eq!(
+        Err("Invalid token".to_string()),
+        authorize(&request, TEST_TOKEN, true, &approver)
+    );
+    assert_eq!(0, approver.prompts.borrow().len());
+}
+
+#[test]
+fn test_authorize_allow_listed_client_skips_approval() {
+    let request =
+        parse_request(r#"{"client": "Firefox", "token": "local-token", "action": "list"}"#)
+            .unwrap();
+    let approver = test_approver(false);
+
+    assert_eq!(Ok(()), authorize(&request, TEST_TOKEN, true, &approver));
+    assert_eq!(0, approver.prompts.borrow().len());
+}
+
+#[test]
+fn test_authorize_pairing_always_asks() {
+    let request =
+        parse_request(r#"{"client": "Firefox", "token": "local-token", "action": "pair"}"#)
+            .unwrap();
+    let approver = test_approver(false);
+
+    assert_eq!(
+        Err("Request denied".to_string()),
+        authorize(&request, TEST_TOKEN, true, &approver)
+    );
+    assert_eq!(vec!["Firefox".to_string()], *approver.prompts.borrow());
+}
+
+#[test]
+fn test_authorize_asks_for_approval() {
+    let request =
+        parse_request(r#"{"client": "Firefox", "token": "local-token", "action": "list"}"#)
+            .unwrap();
+
+    let approver = test_approver(true);
+    assert_eq!(Ok(()), authorize(&request, TEST_TOKEN, false, &approver));
+    assert_eq!(vec!["Firefox".to_string()], *approver.prompts.borrow());
+
+    let approver = test_approver(false);
+    assert_eq!(
+        Err("Request denied".to_string()),
+        authorize(&request, TEST_TOKEN, false, &approver)
+    );
+}
+
+#[test]
+fn test_handle_action() {
+    let vault = test_vault("actions");
+    vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    vault
+        .create_account("GitLab", TEST_SECRET, 8, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    let accounts = handle_action(&vault, "Firefox", &LocalApiAction::List).unwrap();
+    assert_eq!(2, accounts.as_array().unwrap().len());
+    assert_eq!(None, accounts[0].get("secret"));
+
+    let accounts = handle_action(
+        &vault,
+        "Firefox",
+        &LocalApiAction::Search {
+            query: "Lab".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(1, accounts.as_array().unwrap().len());
+    assert_eq!("GitLab", accounts[0]["name"]);
+
+    let code = handle_action(
+        &vault,
+        "Firefox",
+        &LocalApiAction::GetCode {
+            account: "gitlab".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!("GitLab", code["name"]);
+    assert_eq!(true, code["code"].is_string());
+
+    assert_eq!(
+        Err("No account matches: Bitbucket".to_string()),
+        handle_action(
+            &vault,
+            "Firefox",
+            &LocalApiAction::GetCode {
+                account: "Bitbucket".to_string(),
+            },
+        )
+    );
+}
+
+#[test]
+fn test_pairing_issues_client_token() {
+    let vault = test_vault("pairing");
+
+    let paired = handle_action(&vault, "Firefox", &LocalApiAction::Pair).unwrap();
+    let client_token = paired["clientToken"].as_str().unwrap();
+
+    assert_eq!(true, vault.local_api_client_allowed(client_token));
+    assert_eq!(false, vault.local_api_client_allowed("Firefox"));
+    assert_eq!("Firefox", vault.get_local_api_clients()[0].name);
+}
+
+fn test_vault(name: &str) -> Vault {
+    test_support::test_vault(
+        PathBuf::from("./bin/local_api").join(name),
+        Box::new(SystemClock),
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn test_socket_is_private_and_removed_on_stop() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory = PathBuf::from("./bin/local_api/socket");
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    let (_listener, files) = bind(&directory, TEST_TOKEN).unwrap();
+    let socket_directory = fs::metadata(directory.join("local-api")).unwrap();
+
+    assert_eq!(0o700, socket_directory.permissions().mode() & 0o777);
+    assert_eq!(true, files.iter().all(|file| file.exists()));
+
+    let local_api = LocalApi::default();
+    local_api.run(tauri::async_runtime::spawn(async {}), files.clone());
+    local_api.stop();
+
+    assert_eq!(true, files.iter().all(|file| !file.exists()));
+}
@@ -53,6 +53,7 @@ mod tests {
             external_last_updated: None,
             external_hash: None,
             deleted_at: None,
+            sync_account_id: None,
         };
 
         let result = account_to_url(account);
@@ -78,6 +79,7 @@ mod tests {
             external_last_updated: None,
             external_hash: None,
             deleted_at: None,
+            sync_account_id: None,
         };
 
         let result = account_to_url(account);
@@ -102,6 +104,7 @@ mod tests {
             external_last_updated: None,
             external_hash: None,
             deleted_at: None,
+            sync_account_id: None,
         };
 
         let result = account_to_url(account);
@@ -127,6 +130,7 @@ mod tests {
             external_last_updated: None,
             external_hash: None,
             deleted_at: None,
+            sync_account_id: None,
         };
 
         let result = account_to_url(account);
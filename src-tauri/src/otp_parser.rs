@@ -27,6 +27,7 @@ pub fn parse_url(url: &str) -> Account {
         external_last_updated: None,
         external_hash: None,
         deleted_at: None,
+        sync_account_id: None,
     }
 }
 
@@ -1,40 +1,134 @@
+use crate::encryption::DataKey;
+use crate::local_api::LocalApi;
+use crate::sync_api::SyncClient;
+use crate::sync_coordinator::SyncCoordinator;
+use crate::sync_local::SyncReport;
+use crate::sync_scheduler::SyncScheduler;
+use crate::vault::Vault;
 use rusqlite::Connection;
-use tauri::{AppHandle, Manager, State};
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 pub struct AppState {
-    pub db: std::sync::Mutex<Option<Connection>>,
+    pub vault: std::sync::Mutex<Option<Vault>>,
+    pub sync_client: SyncClient,
+    pub sync_report: std::sync::Mutex<SyncReport>,
+    pub sync_coordinator: SyncCoordinator,
+    pub sync_scheduler: SyncScheduler,
+    pub local_api: LocalApi,
 }
 
 pub trait ServiceAccess {
+    fn vault<F, TResult>(&self, operation: F) -> TResult
+    where
+        F: FnOnce(&Vault) -> TResult;
+
+    fn vault_mut<F, TResult>(&self, operation: F) -> TResult
+    where
+        F: FnOnce(&mut Vault) -> TResult;
+
     fn db<F, TResult>(&self, operation: F) -> TResult
     where
-        F: FnOnce(&Connection) -> TResult;
+        F: FnOnce(&Connection) -> TResult,
+    {
+        self.vault(|vault| vault.db(operation))
+    }
 
     fn db_mut<F, TResult>(&self, operation: F) -> TResult
     where
-        F: FnOnce(&mut Connection) -> TResult;
+        F: FnOnce(&mut Connection) -> TResult,
+    {
+        self.vault_mut(|vault| vault.db_mut(operation))
+    }
+
+    fn key_directory(&self) -> PathBuf {
+        self.vault(|vault| vault.key_directory().to_path_buf())
+    }
+
+    fn data_key(&self) -> Result<DataKey, String> {
+        self.vault(|vault| vault.data_key().cloned())
+    }
+
+    fn now(&self) -> u64 {
+        self.vault(|vault| vault.now())
+    }
+
+    fn sync_client(&self) -> SyncClient;
+
+    fn sync_report(&self) -> SyncReport;
+
+    fn set_sync_report(&self, report: SyncReport);
+
+    fn sync_coordinator(&self) -> SyncCoordinator;
+
+    fn sync_scheduler(&self) -> SyncScheduler;
+
+    fn local_api(&self) -> LocalApi;
+
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S);
 }
 
 impl ServiceAccess for AppHandle {
-    fn db<F, TResult>(&self, operation: F) -> TResult
+    fn vault<F, TResult>(&self, operation: F) -> TResult
     where
-        F: FnOnce(&Connection) -> TResult,
+        F: FnOnce(&Vault) -> TResult,
     {
         let app_state: State<AppState> = self.state();
-        let db_connection_guard = app_state.db.lock().unwrap();
-        let db = db_connection_guard.as_ref().unwrap();
+        let vault_guard = app_state.vault.lock().unwrap();
+        let vault = vault_guard.as_ref().unwrap();
 
-        operation(db)
+        operation(vault)
     }
 
-    fn db_mut<F, TResult>(&self, operation: F) -> TResult
+    fn vault_mut<F, TResult>(&self, operation: F) -> TResult
     where
-        F: FnOnce(&mut Connection) -> TResult,
+        F: FnOnce(&mut Vault) -> TResult,
     {
         let app_state: State<AppState> = self.state();
-        let mut db_connection_guard = app_state.db.lock().unwrap();
-        let db = db_connection_guard.as_mut().unwrap();
+        let mut vault_guard = app_state.vault.lock().unwrap();
+        let vault = vault_guard.as_mut().unwrap();
+
+        operation(vault)
+    }
+
+    fn sync_client(&self) -> SyncClient {
+        let app_state: State<AppState> = self.state();
+
+        app_state.sync_client.clone()
+    }
+
+    fn sync_report(&self) -> SyncReport {
+        let app_state: State<AppState> = self.state();
+
+        app_state.sync_report.lock().unwrap().clone()
+    }
+
+    fn set_sync_report(&self, report: SyncReport) {
+        let app_state: State<AppState> = self.state();
+
+        *app_state.sync_report.lock().unwrap() = report;
+    }
+
+    fn sync_coordinator(&self) -> SyncCoordinator {
+        let app_state: State<AppState> = self.state();
+
+        app_state.sync_coordinator.clone()
+    }
+
+    fn sync_scheduler(&self) -> SyncScheduler {
+        let app_state: State<AppState> = self.state();
+
+        app_state.sync_scheduler.clone()
+    }
+
+    fn local_api(&self) -> LocalApi {
+        let app_state: State<AppState> = self.state();
+
+        app_state.local_api.clone()
+    }
 
-        operation(db)
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        let _ = self.emit(event, payload);
     }
 }
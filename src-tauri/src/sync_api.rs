@@ -1,18 +1,139 @@
 use crate::database::{Account, AccountAlgorithm, SyncAccount};
+use crate::sync_backend::{delete_each_record, fetch_each_record, upsert_each_record, SyncBackend};
+use base64::engine::general_purpose;
+use base64::Engine;
 use reqwest::header::AUTHORIZATION;
-use reqwest::{Error, Response};
+use reqwest::{Client, Error, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[cfg(test)]
 #[path = "./sync_api_test.rs"]
 mod tests;
 
+const CONNECT_TIMEOUT_SECONDS: u64 = 10;
+const READ_TIMEOUT_SECONDS: u64 = 30;
+const MAX_RETRIES: u32 = 3;
+const RETRY_BASE_DELAY_MILLISECONDS: u64 = 500;
+const RETRY_MAX_DELAY_MILLISECONDS: u64 = 8000;
+const TOKEN_EXPIRY_MARGIN_SECONDS: u64 = 60;
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const API_VERSION_HEADER: &str = "Api-Version";
+pub const MINIMUM_API_VERSION: i8 = 1;
+pub const MAXIMUM_API_VERSION: i8 = 2;
+const INCOMPATIBLE_VERSION_STATUS: &str = "426";
+const MANIFEST_CURSOR_EXPIRED_STATUS: &str = "410";
+const MAXIMUM_BATCH_SIZE: usize = 50;
+
+#[derive(Clone)]
+pub struct SyncClient {
+    client: Client,
+    verified_client: Client,
+    api_version: Option<i8>,
+    max_retries: u32,
+    retry_base_delay: Duration,
+}
+
+impl SyncClient {
+    pub fn new() -> SyncClient {
+        SyncClient::with_settings(
+            Duration::from_secs(CONNECT_TIMEOUT_SECONDS),
+            Duration::from_secs(READ_TIMEOUT_SECONDS),
+            MAX_RETRIES,
+            Duration::from_millis(RETRY_BASE_DELAY_MILLISECONDS),
+        )
+    }
+
+    pub fn with_settings(
+        connect_timeout: Duration,
+        read_timeout: Duration,
+        max_retries: u32,
+        retry_base_delay: Duration,
+    ) -> SyncClient {
+        SyncClient {
+            client: build_client(connect_timeout, read_timeout, true),
+            verified_client: build_client(connect_timeout, read_timeout, false),
+            api_version: None,
+            max_retries,
+            retry_base_delay,
+        }
+    }
+
+    // Third party hosts such as WebDAV servers are sent credentials, so their certificates are always checked
+    pub fn verified(&self) -> SyncClient {
+        SyncClient {
+            client: self.verified_client.clone(),
+            ..self.clone()
+        }
+    }
+
+    // Every request after negotiation tells the server which version to answer in
+    pub fn with_api_version(&self, api_version: i8) -> SyncClient {
+        SyncClient {
+            api_version: Some(api_version),
+            ..self.clone()
+        }
+    }
+
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .retry_base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt));
+
+        delay.min(Duration::from_millis(RETRY_MAX_DELAY_MILLISECONDS))
+    }
+
+    pub(crate) fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    pub(crate) async fn send(
+        &self,
+        request_builder: RequestBuilder,
+        idempotent: bool,
+    ) -> Result<Response, ResponseError> {
+        let request_builder = match self.api_version {
+            Some(api_version) => request_builder.header(API_VERSION_HEADER, api_version.to_string()),
+            None => request_builder,
+        };
+        let mut attempt = 0;
+
+        loop {
+            let request = match request_builder.try_clone() {
+                Some(request) => request,
+                None => return handle_response(request_builder.send().await),
+            };
+
+            let res = request.send().await;
+
+            if idempotent && attempt < self.max_retries && should_retry(&res) {
+                tokio::time::sleep(self.retry_delay(attempt)).await;
+                attempt += 1;
+                continue;
+            }
+
+            return handle_response(res);
+        }
+    }
+}
+
+pub fn user_agent() -> String {
+    format!("phoenix-auth/{}", env!("CARGO_PKG_VERSION"))
+}
+
 #[derive(Serialize, Deserialize)]
 struct TokenResponse {
     token: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct TokenClaims {
+    exp: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SyncManifest {
@@ -20,12 +141,91 @@ pub struct SyncManifest {
     pub updated_at: u64,
 }
 
+#[derive(Deserialize)]
+struct VersionedResponse {
+    version: i8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Capability {
+    Colour,
+    PaginatedManifest,
+    BatchRecords,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CapabilitiesData {
+    min_version: i8,
+    max_version: i8,
+    capabilities: Vec<Capability>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CapabilitiesResponse {
+    version: i8,
+    data: CapabilitiesData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerCapabilities {
+    pub api_version: i8,
+    pub capabilities: Vec<Capability>,
+}
+
+impl ServerCapabilities {
+    // Servers that predate discovery speak version 1 and have always stored colours
+    pub fn legacy() -> ServerCapabilities {
+        ServerCapabilities {
+            api_version: MINIMUM_API_VERSION,
+            capabilities: vec![Capability::Colour],
+        }
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct ManifestResponse {
     version: i8,
     data: Vec<SyncManifest>,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestPageResponse {
+    version: i8,
+    data: Vec<SyncManifest>,
+    #[serde(default)]
+    removed: Vec<i32>,
+    next_page: Option<String>,
+    cursor: Option<String>,
+}
+
+pub struct ManifestChanges {
+    pub records: Vec<SyncManifest>,
+    pub removed: Vec<i32>,
+    pub cursor: Option<String>,
+    // Every remote record is listed, anything missing was removed remotely
+    pub complete: bool,
+}
+
+impl ManifestChanges {
+    pub fn full(records: Vec<SyncManifest>) -> ManifestChanges {
+        ManifestChanges {
+            records,
+            removed: vec![],
+            cursor: None,
+            complete: true,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct RecordResponse {
     version: i8,
@@ -52,7 +252,9 @@ pub struct VerboseRecord {
     pub id: i32,
     pub name: String,
     pub secret: String,
-    pub colour: String,
+    // Missing when the server does not store colours
+    #[serde(default)]
+    pub colour: Option<String>,
     pub totp_step: i32,
     pub otp_digits: i32,
     pub algorithm: Option<AccountAlgorithm>,
@@ -60,6 +262,25 @@ pub struct VerboseRecord {
     pub updated_at: u64,
 }
 
+#[derive(Serialize, Deserialize)]
+struct BatchResponse<T> {
+    version: i8,
+    data: Vec<BatchItemResponse<T>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BatchItemResponse<T> {
+    status: u16,
+    record: Option<T>,
+    error: Option<String>,
+}
+
+pub struct RecordUpsert {
+    pub account: Account,
+    // Only needed for creates, updates are addressed by the external id
+    pub idempotency_key: Option<String>,
+}
+
 impl VerboseRecord {
     pub fn to_record(&self) -> Record {
         Record {
@@ -70,19 +291,115 @@ impl VerboseRecord {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseErrorKind {
+    Authentication,
+    Server,
+    Connection,
+    Request,
+    InvalidResponse,
+    Storage,
+    IncompatibleVersion,
+}
+
 #[derive(Debug, Clone)]
 pub struct ResponseError {
     status: String,
     message: String,
+    kind: ResponseErrorKind,
 }
 
 impl ResponseError {
+    pub fn new(status: &str, message: &str, kind: ResponseErrorKind) -> ResponseError {
+        ResponseError {
+            status: status.to_string(),
+            message: message.to_string(),
+            kind,
+        }
+    }
+
     pub fn formatted_message(&self) -> String {
         format!("Error {} {}", self.status, self.message)
     }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn kind(&self) -> ResponseErrorKind {
+        self.kind.clone()
+    }
+
+    pub fn is_authentication_error(&self) -> bool {
+        self.kind == ResponseErrorKind::Authentication
+    }
+}
+
+pub fn check_api_version(version: i8) -> Result<(), ResponseError> {
+    if version < MINIMUM_API_VERSION {
+        return Err(server_too_old(version));
+    }
+
+    if version > MAXIMUM_API_VERSION {
+        return Err(server_too_new(version));
+    }
+
+    Ok(())
+}
+
+pub fn negotiate_api_version(min_version: i8, max_version: i8) -> Result<i8, ResponseError> {
+    if max_version < MINIMUM_API_VERSION {
+        return Err(server_too_old(max_version));
+    }
+
+    if min_version > MAXIMUM_API_VERSION {
+        return Err(server_too_new(min_version));
+    }
+
+    Ok(max_version.min(MAXIMUM_API_VERSION))
+}
+
+fn server_too_old(version: i8) -> ResponseError {
+    ResponseError {
+        status: INCOMPATIBLE_VERSION_STATUS.to_string(),
+        message: format!(
+            "Server API version {} is too old, please update the sync server",
+            version
+        ),
+        kind: ResponseErrorKind::IncompatibleVersion,
+    }
+}
+
+fn server_too_new(version: i8) -> ResponseError {
+    ResponseError {
+        status: INCOMPATIBLE_VERSION_STATUS.to_string(),
+        message: format!(
+            "Server API version {} is too new, please update Phoenix",
+            version
+        ),
+        kind: ResponseErrorKind::IncompatibleVersion,
+    }
+}
+
+pub fn get_token_expiry(token: &str) -> Option<u64> {
+    let payload = token.split('.').nth(1)?;
+    let decoded = general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let claims = serde_json::from_slice::<TokenClaims>(&decoded).ok()?;
+
+    claims.exp
+}
+
+pub fn is_token_expired(token: &str, now: u64) -> bool {
+    match get_token_expiry(token) {
+        Some(expiry) => expiry <= now + TOKEN_EXPIRY_MARGIN_SECONDS,
+        None => false,
+    }
 }
 
 pub async fn get_jwt_token(
+    client: &SyncClient,
     base_url: &str,
     username: &str,
     password: &str,
@@ -93,13 +410,13 @@ pub async fn get_jwt_token(
         "password": password,
     });
 
-    let response = match make_post(url, body, None).await {
+    let response = match make_post(client, url, body, None).await {
         Ok(res) => res,
         Err(e) => return Err(e),
     };
 
     let token_response =
-        match serde_json::from_str::<TokenResponse>(&response.text().await.unwrap()) {
+        match serde_json::from_str::<TokenResponse>(&read_body(response).await?) {
             Ok(tr) => tr,
             Err(_) => return Err(handle_invalid_response_body()),
         };
@@ -107,30 +424,123 @@ pub async fn get_jwt_token(
     Ok(token_response.token)
 }
 
-pub async fn get_manifest(account: &SyncAccount) -> Result<Vec<SyncManifest>, ResponseError> {
+pub async fn get_manifest(
+    client: &SyncClient,
+    account: &mut SyncAccount,
+) -> Result<Vec<SyncManifest>, ResponseError> {
     let url = format!("{}/api/records/manifest", account.url);
-    let token = account.token.clone();
 
-    let response = match make_get(url, token).await {
+    let response = match with_reauthentication(client, account, |token| {
+        make_get(client, url.clone(), token)
+    })
+    .await
+    {
         Ok(res) => res,
         Err(e) => return Err(e),
     };
 
-    let manifest_response =
-        match serde_json::from_str::<ManifestResponse>(&response.text().await.unwrap()) {
-            Ok(m) => m,
-            Err(_) => return Err(handle_invalid_response_body()),
-        };
+    let manifest_response = parse_response::<ManifestResponse>(&read_body(response).await?)?;
 
     Ok(manifest_response.data)
 }
 
-pub async fn authenticate_account(account: SyncAccount) -> Result<SyncAccount, ResponseError> {
-    if account.token.is_some() {
-        return Ok(account);
+pub async fn get_manifest_changes(
+    client: &SyncClient,
+    since: Option<&str>,
+    account: &mut SyncAccount,
+) -> Result<ManifestChanges, ResponseError> {
+    let mut changes = ManifestChanges {
+        records: vec![],
+        removed: vec![],
+        cursor: None,
+        complete: since.is_none(),
+    };
+    let mut page: Option<String> = None;
+
+    loop {
+        let url = manifest_page_url(&account.url, since, page.as_deref());
+
+        let response = match with_reauthentication(client, account, |token| {
+            make_get(client, url.clone(), token)
+        })
+        .await
+        {
+            Ok(res) => res,
+            Err(e) => return Err(e),
+        };
+
+        let page_response =
+            parse_response::<ManifestPageResponse>(&read_body(response).await?)?;
+
+        changes.records.extend(page_response.data);
+        changes.removed.extend(page_response.removed);
+
+        match page_response.next_page {
+            Some(next_page) => page = Some(next_page),
+            None => {
+                changes.cursor = page_response.cursor;
+                return Ok(changes);
+            }
+        }
     }
+}
+
+fn manifest_page_url(base_url: &str, since: Option<&str>, page: Option<&str>) -> String {
+    let manifest_url = format!("{}/api/records/manifest", base_url);
+
+    let mut url = match Url::parse(&manifest_url) {
+        Ok(url) => url,
+        Err(_) => return manifest_url,
+    };
 
-    let token = get_jwt_token(&account.url, &account.username, &account.password).await;
+    if let Some(since) = since {
+        url.query_pairs_mut().append_pair("since", since);
+    }
+
+    if let Some(page) = page {
+        url.query_pairs_mut().append_pair("page", page);
+    }
+
+    url.to_string()
+}
+
+pub async fn get_capabilities(
+    client: &SyncClient,
+    account: &mut SyncAccount,
+) -> Result<ServerCapabilities, ResponseError> {
+    let url = format!("{}/api/capabilities", account.url);
+
+    let response = match with_reauthentication(client, account, |token| {
+        make_get(client, url.clone(), token)
+    })
+    .await
+    {
+        Ok(res) => res,
+        Err(e) if e.status().starts_with("404") => return Ok(ServerCapabilities::legacy()),
+        Err(e) => return Err(e),
+    };
+
+    let capabilities_response =
+        parse_response::<CapabilitiesResponse>(&read_body(response).await?)?;
+    let data = capabilities_response.data;
+
+    Ok(ServerCapabilities {
+        api_version: negotiate_api_version(data.min_version, data.max_version)?,
+        capabilities: data.capabilities,
+    })
+}
+
+pub async fn authenticate_account(
+    client: &SyncClient,
+    account: SyncAccount,
+) -> Result<SyncAccount, ResponseError> {
+    if let Some(token) = &account.token {
+        if !is_token_expired(token, current_timestamp()) {
+            return Ok(account);
+        }
+    }
+
+    let token = get_jwt_token(client, &account.url, &account.username, &account.password).await;
 
     if token.is_ok() {
         return Ok(SyncAccount {
@@ -139,6 +549,9 @@ pub async fn authenticate_account(account: SyncAccount) -> Result<SyncAccount, R
             password: account.password,
             url: account.url,
             token: Option::from(token.unwrap()),
+            sync_type: account.sync_type,
+            passphrase: account.passphrase.clone(),
+            auth_type: account.auth_type.clone(),
         });
     }
 
@@ -146,41 +559,50 @@ pub async fn authenticate_account(account: SyncAccount) -> Result<SyncAccount, R
 }
 
 pub async fn get_record(
+    client: &SyncClient,
     account: &Account,
-    sync_account: &SyncAccount,
+    idempotency_key: &str,
+    capabilities: &ServerCapabilities,
+    sync_account: &mut SyncAccount,
 ) -> Result<Record, ResponseError> {
     let url = format!("{}/api/records", sync_account.url);
-    let token = sync_account.token.clone();
-    let otp_digits = account.otp_digits;
-    let totp_step = account.totp_step;
-    let totp_algorithm = account.algorithm.clone();
-
-    let body = json!({
-        "name": account.name,
-        "secret": account.secret,
-        "colour": account.colour,
-        "otpDigits": otp_digits,
-        "totpStep": totp_step,
-        "totpAlgorithm": totp_algorithm,
-    });
+    let body = record_body(account, capabilities);
 
-    let response = match make_post(url, body, token).await {
+    let response = match with_reauthentication(client, sync_account, |token| {
+        make_idempotent_post(client, url.clone(), body.clone(), idempotency_key, token)
+    })
+    .await
+    {
         Ok(res) => res,
         Err(e) => return Err(e),
     };
 
-    let record_response =
-        match serde_json::from_str::<RecordResponse>(&response.text().await.unwrap()) {
-            Ok(r) => r,
-            Err(_) => return Err(handle_invalid_response_body()),
-        };
+    let record_response = parse_response::<RecordResponse>(&read_body(response).await?)?;
 
     Ok(record_response.data)
 }
 
+fn record_body(account: &Account, capabilities: &ServerCapabilities) -> Value {
+    let mut body = json!({
+        "name": account.name,
+        "secret": account.secret,
+        "otpDigits": account.otp_digits,
+        "totpStep": account.totp_step,
+        "totpAlgorithm": account.algorithm.clone(),
+    });
+
+    if capabilities.supports(Capability::Colour) {
+        body["colour"] = json!(account.colour);
+    }
+
+    body
+}
+
 pub async fn update_record(
+    client: &SyncClient,
     account: &Account,
-    sync_account: &SyncAccount,
+    capabilities: &ServerCapabilities,
+    sync_account: &mut SyncAccount,
 ) -> Result<Record, ResponseError> {
     let external_id = match account.external_id {
         Some(id) => id,
@@ -188,161 +610,463 @@ pub async fn update_record(
             return Err(ResponseError {
                 status: "400".to_string(),
                 message: "Missing External Id".to_string(),
+                kind: ResponseErrorKind::Request,
             })
         }
     };
 
     let url = format!("{}/api/records/{}", sync_account.url, external_id);
-    let token = sync_account.token.clone();
-    let otp_digits = account.otp_digits;
-    let totp_step = account.totp_step;
-    let totp_algorithm = account.algorithm.clone();
-
-    let body = json!({
-        "name": account.name,
-        "secret": account.secret,
-        "colour": account.colour,
-        "otpDigits": otp_digits,
-        "totpStep": totp_step,
-        "totpAlgorithm": totp_algorithm,
-    });
+    let body = record_body(account, capabilities);
 
-    let response = match make_put(url, body, token).await {
+    let response = match with_reauthentication(client, sync_account, |token| {
+        make_put(client, url.clone(), body.clone(), token)
+    })
+    .await
+    {
         Ok(res) => res,
         Err(e) => return Err(e),
     };
 
-    let record_response =
-        match serde_json::from_str::<RecordResponse>(&response.text().await.unwrap()) {
-            Ok(r) => r,
-            Err(_) => return Err(handle_invalid_response_body()),
-        };
+    let record_response = parse_response::<RecordResponse>(&read_body(response).await?)?;
 
     Ok(record_response.data)
 }
 
 pub async fn get_single_record(
+    client: &SyncClient,
     id: &i32,
-    sync_account: &SyncAccount,
+    sync_account: &mut SyncAccount,
 ) -> Result<VerboseRecord, ResponseError> {
     let url = format!("{}/api/records/{}", sync_account.url, id);
-    let token = sync_account.token.clone();
 
-    let response = match make_get(url, token).await {
+    let response = match with_reauthentication(client, sync_account, |token| {
+        make_get(client, url.clone(), token)
+    })
+    .await
+    {
         Ok(res) => res,
         Err(e) => return Err(e),
     };
 
-    let record_response =
-        match serde_json::from_str::<SingleRecordResponse>(&response.text().await.unwrap()) {
-            Ok(r) => r,
-            Err(_) => return Err(handle_invalid_response_body()),
-        };
+    let record_response = parse_response::<SingleRecordResponse>(&read_body(response).await?)?;
 
     Ok(record_response.data)
 }
 
-pub async fn remove_record(id: &i32, sync_account: &SyncAccount) -> Result<bool, ResponseError> {
+pub async fn remove_record(
+    client: &SyncClient,
+    id: &i32,
+    sync_account: &mut SyncAccount,
+) -> Result<bool, ResponseError> {
     let url = format!("{}/api/records/{}", sync_account.url, id);
-    let token = sync_account.token.clone();
 
-    match make_delete(url, token).await {
+    match with_reauthentication(client, sync_account, |token| {
+        make_delete(client, url.clone(), token)
+    })
+    .await
+    {
         Ok(_) => Ok(true),
         Err(e) => Err(e),
     }
 }
 
-async fn make_get(url: String, token: Option<String>) -> Result<Response, ResponseError> {
-    let builder = reqwest::Client::builder();
-    let mut request_builder = builder
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap()
-        .get(url);
+pub async fn get_records_batch(
+    client: &SyncClient,
+    ids: &[i32],
+    sync_account: &mut SyncAccount,
+) -> Vec<Result<VerboseRecord, ResponseError>> {
+    let url = format!("{}/api/records/batch/fetch", sync_account.url);
+    let body = json!({ "ids": ids });
+
+    send_batch(client, url, body, ids.len(), sync_account)
+        .await
+        .into_iter()
+        .map(require_record)
+        .collect()
+}
+
+pub async fn upsert_records_batch(
+    client: &SyncClient,
+    upserts: &[RecordUpsert],
+    capabilities: &ServerCapabilities,
+    sync_account: &mut SyncAccount,
+) -> Vec<Result<Record, ResponseError>> {
+    let url = format!("{}/api/records/batch/upsert", sync_account.url);
+    let records: Vec<Value> = upserts
+        .iter()
+        .map(|upsert| {
+            let mut body = record_body(&upsert.account, capabilities);
+            body["id"] = json!(upsert.account.external_id);
+            body["idempotencyKey"] = json!(upsert.idempotency_key);
+            body
+        })
+        .collect();
+    let body = json!({ "records": records });
+
+    send_batch(client, url, body, upserts.len(), sync_account)
+        .await
+        .into_iter()
+        .map(require_record)
+        .collect()
+}
+
+pub async fn remove_records_batch(
+    client: &SyncClient,
+    ids: &[i32],
+    sync_account: &mut SyncAccount,
+) -> Vec<Result<bool, ResponseError>> {
+    let url = format!("{}/api/records/batch/delete", sync_account.url);
+    let body = json!({ "ids": ids });
+
+    send_batch::<Value>(client, url, body, ids.len(), sync_account)
+        .await
+        .into_iter()
+        .map(|result| result.map(|_| true))
+        .collect()
+}
+
+// One result per requested item, in request order
+async fn send_batch<T: DeserializeOwned>(
+    client: &SyncClient,
+    url: String,
+    body: Value,
+    expected_items: usize,
+    sync_account: &mut SyncAccount,
+) -> Vec<Result<Option<T>, ResponseError>> {
+    let response = match with_reauthentication(client, sync_account, |token| {
+        make_batch_post(client, url.clone(), body.clone(), token)
+    })
+    .await
+    {
+        Ok(res) => res,
+        Err(e) => return failed_batch(e, expected_items),
+    };
+
+    let batch_response = match read_body(response).await.and_then(|body| parse_response::<BatchResponse<T>>(&body)) {
+        Ok(b) => b,
+        Err(e) => return failed_batch(e, expected_items),
+    };
 
-    if token.is_some() {
-        request_builder =
-            request_builder.header(AUTHORIZATION, "Bearer ".to_owned() + &token.unwrap());
+    let mut results: Vec<Result<Option<T>, ResponseError>> = batch_response
+        .data
+        .into_iter()
+        .take(expected_items)
+        .map(batch_item_result)
+        .collect();
+
+    while results.len() < expected_items {
+        results.push(Err(handle_invalid_response_body()));
     }
 
-    let res = request_builder.send().await;
+    results
+}
+
+fn failed_batch<T>(
+    error: ResponseError,
+    expected_items: usize,
+) -> Vec<Result<Option<T>, ResponseError>> {
+    (0..expected_items).map(|_| Err(error.clone())).collect()
+}
+
+fn batch_item_result<T>(item: BatchItemResponse<T>) -> Result<Option<T>, ResponseError> {
+    let status = match StatusCode::from_u16(item.status) {
+        Ok(status) => status,
+        Err(_) => return Err(handle_invalid_response_body()),
+    };
+
+    if !status.is_success() {
+        return Err(ResponseError {
+            status: status.to_string(),
+            message: item
+                .error
+                .unwrap_or_else(|| "Error from server".to_string()),
+            kind: ResponseErrorKind::Server,
+        });
+    }
+
+    Ok(item.record)
+}
 
-    match handle_response(res) {
-        Ok(res) => Ok(res),
+fn require_record<T>(result: Result<Option<T>, ResponseError>) -> Result<T, ResponseError> {
+    match result {
+        Ok(Some(record)) => Ok(record),
+        Ok(None) => Err(handle_invalid_response_body()),
         Err(e) => Err(e),
     }
 }
 
+pub struct PhoenixBackend {
+    client: SyncClient,
+    account: SyncAccount,
+    capabilities: ServerCapabilities,
+}
+
+impl PhoenixBackend {
+    pub async fn connect(
+        client: SyncClient,
+        account: SyncAccount,
+    ) -> Result<PhoenixBackend, ResponseError> {
+        let mut account = authenticate_account(&client, account).await?;
+        let capabilities = get_capabilities(&client, &mut account).await?;
+
+        Ok(PhoenixBackend {
+            client: client.with_api_version(capabilities.api_version),
+            account,
+            capabilities,
+        })
+    }
+
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        &self.capabilities
+    }
+
+    // A colour from a server that does not advertise storing them is not trusted
+    fn supported_record(&self, mut record: VerboseRecord) -> VerboseRecord {
+        if !self.capabilities.supports(Capability::Colour) {
+            record.colour = None;
+        }
+
+        record
+    }
+}
+
+impl SyncBackend for PhoenixBackend {
+    fn sync_account(&self) -> &SyncAccount {
+        &self.account
+    }
+
+    async fn list_manifest(&mut self) -> Result<Vec<SyncManifest>, ResponseError> {
+        get_manifest(&self.client, &mut self.account).await
+    }
+
+    async fn list_manifest_changes(
+        &mut self,
+        since: Option<&str>,
+    ) -> Result<ManifestChanges, ResponseError> {
+        if !self.capabilities.supports(Capability::PaginatedManifest) {
+            return self.list_manifest().await.map(ManifestChanges::full);
+        }
+
+        match get_manifest_changes(&self.client, since, &mut self.account).await {
+            // The server has forgotten the cursor, so start again from a full listing
+            Err(e) if since.is_some() && e.status().starts_with(MANIFEST_CURSOR_EXPIRED_STATUS) => {
+                get_manifest_changes(&self.client, None, &mut self.account).await
+            }
+            result => result,
+        }
+    }
+
+    async fn fetch_record(&mut self, id: &i32) -> Result<VerboseRecord, ResponseError> {
+        get_single_record(&self.client, id, &mut self.account)
+            .await
+            .map(|record| self.supported_record(record))
+    }
+
+    async fn create_record(
+        &mut self,
+        account: &Account,
+        idempotency_key: &str,
+    ) -> Result<Record, ResponseError> {
+        get_record(
+            &self.client,
+            account,
+            idempotency_key,
+            &self.capabilities,
+            &mut self.account,
+        )
+        .await
+    }
+
+    async fn update_record(&mut self, account: &Account) -> Result<Record, ResponseError> {
+        update_record(&self.client, account, &self.capabilities, &mut self.account).await
+    }
+
+    async fn delete_record(&mut self, id: &i32) -> Result<bool, ResponseError> {
+        remove_record(&self.client, id, &mut self.account).await
+    }
+
+    async fn fetch_records(&mut self, ids: &[i32]) -> Vec<Result<VerboseRecord, ResponseError>> {
+        if !self.capabilities.supports(Capability::BatchRecords) {
+            return fetch_each_record(self, ids).await;
+        }
+
+        let mut results = Vec::with_capacity(ids.len());
+
+        for chunk in ids.chunks(MAXIMUM_BATCH_SIZE) {
+            results.extend(get_records_batch(&self.client, chunk, &mut self.account).await);
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.map(|record| self.supported_record(record)))
+            .collect()
+    }
+
+    async fn upsert_records(
+        &mut self,
+        upserts: &[RecordUpsert],
+    ) -> Vec<Result<Record, ResponseError>> {
+        if !self.capabilities.supports(Capability::BatchRecords) {
+            return upsert_each_record(self, upserts).await;
+        }
+
+        let mut results = Vec::with_capacity(upserts.len());
+
+        for chunk in upserts.chunks(MAXIMUM_BATCH_SIZE) {
+            results.extend(
+                upsert_records_batch(&self.client, chunk, &self.capabilities, &mut self.account)
+                    .await,
+            );
+        }
+
+        results
+    }
+
+    async fn delete_records(&mut self, ids: &[i32]) -> Vec<Result<bool, ResponseError>> {
+        if !self.capabilities.supports(Capability::BatchRecords) {
+            return delete_each_record(self, ids).await;
+        }
+
+        let mut results = Vec::with_capacity(ids.len());
+
+        for chunk in ids.chunks(MAXIMUM_BATCH_SIZE) {
+            results.extend(remove_records_batch(&self.client, chunk, &mut self.account).await);
+        }
+
+        results
+    }
+}
+
+async fn with_reauthentication<F, Fut>(
+    client: &SyncClient,
+    sync_account: &mut SyncAccount,
+    request: F,
+) -> Result<Response, ResponseError>
+where
+    F: Fn(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Response, ResponseError>>,
+{
+    let error = match request(sync_account.token.clone()).await {
+        Err(e) if e.is_authentication_error() => e,
+        result => return result,
+    };
+
+    let token = match get_jwt_token(
+        client,
+        &sync_account.url,
+        &sync_account.username,
+        &sync_account.password,
+    )
+    .await
+    {
+        Ok(token) => token,
+        Err(_) => return Err(error),
+    };
+
+    sync_account.token = Some(token);
+
+    request(sync_account.token.clone()).await
+}
+
+async fn make_get(
+    client: &SyncClient,
+    url: String,
+    token: Option<String>,
+) -> Result<Response, ResponseError> {
+    let request_builder = with_token(client.client.get(url), token);
+
+    client.send(request_builder, true).await
+}
+
 async fn make_post(
+    client: &SyncClient,
     url: String,
     body: Value,
     token: Option<String>,
 ) -> Result<Response, ResponseError> {
-    let builder = reqwest::Client::builder();
-    let mut request_builder = builder
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap()
-        .post(url)
-        .json(&body);
+    let request_builder = with_token(client.client.post(url).json(&body), token);
 
-    if token.is_some() {
-        request_builder =
-            request_builder.header(AUTHORIZATION, "Bearer ".to_owned() + &token.unwrap());
-    }
+    client.send(request_builder, false).await
+}
 
-    let res = request_builder.send().await;
+async fn make_idempotent_post(
+    client: &SyncClient,
+    url: String,
+    body: Value,
+    idempotency_key: &str,
+    token: Option<String>,
+) -> Result<Response, ResponseError> {
+    let request_builder = with_token(
+        client
+            .client
+            .post(url)
+            .header(IDEMPOTENCY_KEY_HEADER, idempotency_key)
+            .json(&body),
+        token,
+    );
+
+    // The server deduplicates on the key, so a create is safe to retry
+    client.send(request_builder, true).await
+}
 
-    match handle_response(res) {
-        Ok(res) => Ok(res),
-        Err(e) => Err(e),
-    }
+async fn make_batch_post(
+    client: &SyncClient,
+    url: String,
+    body: Value,
+    token: Option<String>,
+) -> Result<Response, ResponseError> {
+    let request_builder = with_token(client.client.post(url).json(&body), token);
+
+    // Batches only fetch, delete, or carry a key per create, so replaying one is safe
+    client.send(request_builder, true).await
 }
 
 async fn make_put(
+    client: &SyncClient,
     url: String,
     body: Value,
     token: Option<String>,
 ) -> Result<Response, ResponseError> {
-    let builder = reqwest::Client::builder();
-    let mut request_builder = builder
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap()
-        .put(url)
-        .json(&body);
+    let request_builder = with_token(client.client.put(url).json(&body), token);
 
-    if token.is_some() {
-        request_builder =
-            request_builder.header(AUTHORIZATION, "Bearer ".to_owned() + &token.unwrap());
-    }
+    client.send(request_builder, true).await
+}
 
-    let res = request_builder.send().await;
+async fn make_delete(
+    client: &SyncClient,
+    url: String,
+    token: Option<String>,
+) -> Result<Response, ResponseError> {
+    let request_builder = with_token(client.client.delete(url), token);
 
-    match handle_response(res) {
-        Ok(res) => Ok(res),
-        Err(e) => Err(e),
-    }
+    client.send(request_builder, true).await
 }
 
-async fn make_delete(url: String, token: Option<String>) -> Result<Response, ResponseError> {
-    let builder = reqwest::Client::builder();
-    let mut request_builder = builder
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap()
-        .delete(url);
-
-    if token.is_some() {
-        request_builder =
-            request_builder.header(AUTHORIZATION, "Bearer ".to_owned() + &token.unwrap());
+fn with_token(request_builder: RequestBuilder, token: Option<String>) -> RequestBuilder {
+    match token {
+        Some(token) => request_builder.header(AUTHORIZATION, "Bearer ".to_owned() + &token),
+        None => request_builder,
     }
+}
 
-    let res = request_builder.send().await;
+fn build_client(
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    accept_invalid_certs: bool,
+) -> Client {
+    Client::builder()
+        .danger_accept_invalid_certs(accept_invalid_certs)
+        .connect_timeout(connect_timeout)
+        .read_timeout(read_timeout)
+        .user_agent(user_agent())
+        .build()
+        .expect("The sync HTTP client should build")
+}
 
-    match handle_response(res) {
-        Ok(res) => Ok(res),
-        Err(e) => Err(e),
+fn should_retry(response: &Result<Response, Error>) -> bool {
+    match response {
+        Ok(res) => res.status().is_server_error(),
+        Err(e) => e.is_connect() || e.is_timeout(),
     }
 }
 
@@ -352,10 +1076,19 @@ fn handle_response(response: Result<Response, Error>) -> Result<Response, Respon
         Err(e) => return Err(handle_reqwest_error(e)),
     };
 
+    if valid_response.status() == StatusCode::UNAUTHORIZED {
+        return Err(ResponseError {
+            status: valid_response.status().to_string(),
+            message: "Authentication failed".to_string(),
+            kind: ResponseErrorKind::Authentication,
+        });
+    }
+
     if !valid_response.status().is_success() {
         let error = ResponseError {
             status: valid_response.status().to_string(),
             message: "Error from server".to_string(),
+            kind: ResponseErrorKind::Server,
         };
 
         return Err(error);
@@ -370,6 +1103,7 @@ fn handle_reqwest_error(e: Error) -> ResponseError {
         _ => "0".to_string(),
     };
     let mut message: String = "Unknown Error".to_string();
+    let mut kind = ResponseErrorKind::Request;
 
     if e.is_redirect() {
         if let Some(final_stop) = e.url() {
@@ -383,22 +1117,67 @@ fn handle_reqwest_error(e: Error) -> ResponseError {
 
     if e.is_connect() {
         message = "Connection could not be made.".to_string();
+        kind = ResponseErrorKind::Connection;
     }
 
     if e.is_request() {
         message = "Issue making the request to server".to_string();
     }
 
+    if e.is_timeout() {
+        message = "Request to server timed out.".to_string();
+        kind = ResponseErrorKind::Connection;
+    }
+
     if e.is_builder() {
         message = "FATAL ERROR: Builder Issue in client".to_string();
     }
 
-    ResponseError { status, message }
+    ResponseError {
+        status,
+        message,
+        kind,
+    }
+}
+
+// The read timeout also covers the body, so a stalled or cut off body is a connection failure
+async fn read_body(response: Response) -> Result<String, ResponseError> {
+    response.text().await.map_err(|e| {
+        let timed_out = e.is_timeout();
+        let mut error = handle_reqwest_error(e);
+
+        if !timed_out {
+            error.message = "Response from server was cut off.".to_string();
+        }
+        error.kind = ResponseErrorKind::Connection;
+
+        error
+    })
+}
+
+// Reports an unsupported API version ahead of a generic parse failure
+fn parse_response<T: DeserializeOwned>(body: &str) -> Result<T, ResponseError> {
+    if let Ok(versioned) = serde_json::from_str::<VersionedResponse>(body) {
+        check_api_version(versioned.version)?;
+    }
+
+    match serde_json::from_str::<T>(body) {
+        Ok(response) => Ok(response),
+        Err(_) => Err(handle_invalid_response_body()),
+    }
 }
 
-fn handle_invalid_response_body() -> ResponseError {
+pub(crate) fn handle_invalid_response_body() -> ResponseError {
     ResponseError {
         status: "418".to_string(),
         message: "Could not parse Server response".to_string(),
+        kind: ResponseErrorKind::InvalidResponse,
     }
 }
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Could not generate UNIX time")
+        .as_secs()
+}
@@ -1,10 +1,20 @@
-use crate::database::{Account, AccountAlgorithm, SyncAccount};
+use crate::database::{Account, AccountAlgorithm, SyncAccount, SyncAuthType, SyncType};
 use crate::sync_api::{
-    authenticate_account, get_jwt_token, get_manifest, get_record, make_delete, make_get,
-    make_post, make_put, remove_record, update_record,
+    authenticate_account, get_capabilities, get_jwt_token, get_manifest, get_manifest_changes,
+    get_record, get_records_batch, get_token_expiry, is_token_expired, make_delete, make_get,
+    make_post, make_put, negotiate_api_version, remove_record, remove_records_batch, update_record,
+    upsert_records_batch, user_agent, Capability, PhoenixBackend, RecordUpsert, ResponseErrorKind,
+    ServerCapabilities, SyncClient,
 };
+use crate::sync_backend::SyncBackend;
+use base64::engine::general_purpose;
+use base64::Engine;
 use httpmock::prelude::*;
 use serde_json::{json, Value};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const IDEMPOTENCY_KEY: &str = "0123456789abcdef0123456789abcdef";
 
 #[tokio::test]
 async fn test_get_request_no_auth() {
@@ -30,7 +40,7 @@ async fn test_get_request_no_auth() {
         })
         .await;
 
-    let response = make_get(server.url("/endpoint").to_string(), None).await;
+    let response = make_get(&test_client(), server.url("/endpoint").to_string(), None).await;
 
     assert_eq!(true, response.is_ok());
 
@@ -59,6 +69,7 @@ async fn test_get_request_with_auth() {
         .await;
 
     let response = make_get(
+        &test_client(),
         server.url("/endpoint").to_string(),
         Some("123456789".to_string()),
     )
@@ -102,6 +113,7 @@ async fn test_post_request_no_auth() {
         .await;
 
     let response = make_post(
+        &test_client(),
         server.url("/endpoint").to_string(),
         json!({ "name": "test" }),
         None,
@@ -136,6 +148,7 @@ async fn test_post_request_with_auth() {
         .await;
 
     let response = make_post(
+        &test_client(),
         server.url("/endpoint").to_string(),
         json!({ "name": "test" }),
         Some("123456789".to_string()),
@@ -181,6 +194,7 @@ async fn test_put_request_no_auth() {
         .await;
 
     let response = make_put(
+        &test_client(),
         server.url("/endpoint/1").to_string(),
         json!({ "id": 1, "name": "updated" }),
         None,
@@ -215,6 +229,7 @@ async fn test_put_request_with_auth() {
         .await;
 
     let response = make_put(
+        &test_client(),
         server.url("/endpoint/1").to_string(),
         json!({ "id": 1, "name": "updated" }),
         Some("123456789".to_string()),
@@ -256,7 +271,7 @@ async fn test_delete_request_no_auth() {
         })
         .await;
 
-    let response = make_delete(server.url("/endpoint/1").to_string(), None).await;
+    let response = make_delete(&test_client(), server.url("/endpoint/1").to_string(), None).await;
 
     assert_eq!(true, response.is_ok());
 
@@ -285,6 +300,7 @@ async fn test_delete_request_with_auth() {
         .await;
 
     let response = make_delete(
+        &test_client(),
         server.url("/endpoint/1").to_string(),
         Some("123456789".to_string()),
     )
@@ -316,7 +332,13 @@ async fn test_successfully_validate_account() {
         })
         .await;
 
-    let response = get_jwt_token(&server.url(""), &"test@test.com", &"Passw!rd1234").await;
+    let response = get_jwt_token(
+        &test_client(),
+        &server.url(""),
+        &"test@test.com",
+        &"Passw!rd1234",
+    )
+    .await;
 
     assert_eq!(true, response.is_ok());
 
@@ -340,14 +362,20 @@ async fn test_invalid_validate_account() {
         })
         .await;
 
-    let response = get_jwt_token(&server.url(""), &"test@test.com", &"Passw!rd1234").await;
+    let response = get_jwt_token(
+        &test_client(),
+        &server.url(""),
+        &"test@test.com",
+        &"Passw!rd1234",
+    )
+    .await;
 
     assert_eq!(true, response.is_err());
 
     let body = response.err();
 
     assert_eq!(
-        "Error 401 Unauthorized Error from server",
+        "Error 401 Unauthorized Authentication failed",
         body.unwrap().formatted_message()
     );
 }
@@ -367,7 +395,13 @@ async fn test_successful_validate_account_invalid_response() {
         })
         .await;
 
-    let response = get_jwt_token(&server.url(""), &"test@test.com", &"Passw!rd1234").await;
+    let response = get_jwt_token(
+        &test_client(),
+        &server.url(""),
+        &"test@test.com",
+        &"Passw!rd1234",
+    )
+    .await;
 
     assert_eq!(true, response.is_err());
 
@@ -406,14 +440,17 @@ async fn test_successfully_get_manifest() {
         })
         .await;
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Password".to_string(),
         url: server.url("").to_string(),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
-    let response = get_manifest(&sync_account).await;
+    let response = get_manifest(&test_client(), &mut sync_account).await;
 
     assert_eq!(true, response.is_ok());
 
@@ -441,21 +478,24 @@ async fn test_invalid_get_manifest() {
         })
         .await;
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Password".to_string(),
         url: server.url("").to_string(),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
-    let response = get_manifest(&sync_account).await;
+    let response = get_manifest(&test_client(), &mut sync_account).await;
 
     assert_eq!(true, response.is_err());
 
     let body = response.err();
 
     assert_eq!(
-        "Error 401 Unauthorized Error from server",
+        "Error 401 Unauthorized Authentication failed",
         body.unwrap().formatted_message()
     );
 }
@@ -487,14 +527,17 @@ async fn test_successful_get_manifest_invalid_response() {
         })
         .await;
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Password".to_string(),
         url: server.url("").to_string(),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
-    let response = get_manifest(&sync_account).await;
+    let response = get_manifest(&test_client(), &mut sync_account).await;
 
     assert_eq!(true, response.is_err());
 
@@ -527,9 +570,12 @@ async fn test_successfully_authenticate_account() {
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = authenticate_account(account).await;
+    let response = authenticate_account(&test_client(), account).await;
 
     assert_eq!(true, response.is_ok());
 
@@ -563,16 +609,19 @@ async fn test_invalid_authenticate_account() {
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = authenticate_account(account).await;
+    let response = authenticate_account(&test_client(), account).await;
 
     assert_eq!(true, response.is_err());
 
     let body = response.err();
 
     assert_eq!(
-        "Error 401 Unauthorized Error from server",
+        "Error 401 Unauthorized Authentication failed",
         body.unwrap().formatted_message()
     );
 }
@@ -598,9 +647,12 @@ async fn test_successful_authenticate_account_invalid_response() {
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = authenticate_account(account).await;
+    let response = authenticate_account(&test_client(), account).await;
     assert_eq!(true, response.is_err());
 
     let body = response.err();
@@ -628,7 +680,8 @@ async fn test_successful_get_record_full() {
                     "otpDigits": 6,
                     "totpAlgorithm": "SHA256",
                 }))
-                .header("Authorization", "Bearer 123456789");
+                .header("Authorization", "Bearer 123456789")
+                .header("Idempotency-Key", IDEMPOTENCY_KEY);
             then.status(200)
                 .header("content-type", "application/json")
                 .json_body(json!({
@@ -654,17 +707,28 @@ async fn test_successful_get_record_full() {
         external_last_updated: None,
         external_hash: None,
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = get_record(&account, &sync_account).await;
+    let response = get_record(
+        &test_client(),
+        &account,
+        IDEMPOTENCY_KEY,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
 
     assert_eq!(true, response.is_ok());
 
@@ -718,17 +782,28 @@ async fn test_successful_get_record_required() {
         external_last_updated: None,
         external_hash: None,
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = get_record(&account, &sync_account).await;
+    let response = get_record(
+        &test_client(),
+        &account,
+        IDEMPOTENCY_KEY,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
 
     assert_eq!(true, response.is_ok());
 
@@ -772,24 +847,35 @@ async fn test_invalid_get_record() {
         external_last_updated: None,
         external_hash: None,
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = get_record(&account, &sync_account).await;
+    let response = get_record(
+        &test_client(),
+        &account,
+        IDEMPOTENCY_KEY,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
 
     assert_eq!(true, response.is_err());
 
     let body = response.err();
 
     assert_eq!(
-        "Error 401 Unauthorized Error from server",
+        "Error 401 Unauthorized Authentication failed",
         body.unwrap().formatted_message()
     );
 }
@@ -837,17 +923,28 @@ async fn test_successful_get_record_invalid_response() {
         external_last_updated: None,
         external_hash: None,
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = get_record(&account, &sync_account).await;
+    let response = get_record(
+        &test_client(),
+        &account,
+        IDEMPOTENCY_KEY,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
     assert_eq!(true, response.is_err());
 
     let body = response.err();
@@ -901,17 +998,27 @@ async fn test_successful_update_record_full() {
         external_last_updated: Some(1234689),
         external_hash: Some("helpodsa1".to_string()),
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = update_record(&account, &sync_account).await;
+    let response = update_record(
+        &test_client(),
+        &account,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
 
     assert_eq!(true, response.is_ok());
 
@@ -965,17 +1072,27 @@ async fn test_successful_update_record_required() {
         external_last_updated: Some(1234),
         external_hash: Some("Helosfaf".to_string()),
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = update_record(&account, &sync_account).await;
+    let response = update_record(
+        &test_client(),
+        &account,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
 
     assert_eq!(true, response.is_ok());
 
@@ -1019,24 +1136,34 @@ async fn test_invalid_update_record() {
         external_last_updated: Some(1243),
         external_hash: Some("Hello".to_string()),
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = update_record(&account, &sync_account).await;
+    let response = update_record(
+        &test_client(),
+        &account,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
 
     assert_eq!(true, response.is_err());
 
     let body = response.err();
 
     assert_eq!(
-        "Error 401 Unauthorized Error from server",
+        "Error 401 Unauthorized Authentication failed",
         body.unwrap().formatted_message()
     );
 }
@@ -1057,17 +1184,27 @@ async fn test_invalid_update_record_missing_external() {
         external_last_updated: None,
         external_hash: None,
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: "".to_string(),
         token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = update_record(&account, &sync_account).await;
+    let response = update_record(
+        &test_client(),
+        &account,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
 
     assert_eq!(true, response.is_err());
 
@@ -1122,17 +1259,27 @@ async fn test_successful_update_record_invalid_response() {
         external_last_updated: Some(1235),
         external_hash: Some("Heelo".to_string()),
         deleted_at: None,
+        sync_account_id: None,
     };
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = update_record(&account, &sync_account).await;
+    let response = update_record(
+        &test_client(),
+        &account,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
     assert_eq!(true, response.is_err());
 
     let body = response.err();
@@ -1156,15 +1303,18 @@ async fn test_successful_delete_record() {
         })
         .await;
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = remove_record(&8, &sync_account).await;
+    let response = remove_record(&test_client(), &8, &mut sync_account).await;
 
     assert_eq!(true, response.is_ok());
     assert_eq!(true, response.unwrap());
@@ -1183,15 +1333,18 @@ async fn test_delete_record_missing_record() {
         })
         .await;
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
     };
 
-    let response = remove_record(&8, &sync_account).await;
+    let response = remove_record(&test_client(), &8, &mut sync_account).await;
 
     assert_eq!(true, response.is_err());
     assert_eq!(
@@ -1212,19 +1365,1146 @@ async fn test_delete_record_invalid() {
         })
         .await;
 
-    let sync_account = SyncAccount {
+    let mut sync_account = SyncAccount {
         id: 1,
         username: "test@test.com".to_string(),
         password: "Passw!rd1234".to_string(),
         url: server.url(""),
         token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    };
+
+    let response = remove_record(&test_client(), &8, &mut sync_account).await;
+
+    assert_eq!(true, response.is_err());
+    assert_eq!(
+        "Error 401 Unauthorized Authentication failed",
+        response.err().unwrap().formatted_message()
+    );
+}
+
+#[tokio::test]
+async fn test_requests_send_user_agent() {
+    let server = MockServer::start_async().await;
+
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/endpoint")
+                .header("user-agent", user_agent());
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({ "name": "test" }));
+        })
+        .await;
+
+    let response = make_get(&test_client(), server.url("/endpoint").to_string(), None).await;
+
+    assert_eq!(true, response.is_ok());
+    assert_eq!(1, mock.hits_async().await);
+    assert_eq!(
+        format!("phoenix-auth/{}", env!("CARGO_PKG_VERSION")),
+        user_agent()
+    );
+}
+
+#[tokio::test]
+async fn test_get_request_retries_until_exhausted() {
+    let server = MockServer::start_async().await;
+
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/endpoint");
+            then.status(503);
+        })
+        .await;
+
+    let response = make_get(&test_client(), server.url("/endpoint").to_string(), None).await;
+
+    assert_eq!(true, response.is_err());
+    assert_eq!(
+        "Error 503 Service Unavailable Error from server",
+        response.err().unwrap().formatted_message()
+    );
+    assert_eq!(3, mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_get_request_recovers_from_flaky_server() {
+    let server = MockServer::start_async().await;
+
+    let failing_mock = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/endpoint");
+            then.status(502);
+        })
+        .await;
+
+    let client = test_client();
+    let request = make_get(&client, server.url("/endpoint").to_string(), None);
+    let recover_server = async {
+        while failing_mock.hits_async().await == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        failing_mock.delete_async().await;
+
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path("/endpoint");
+                then.status(200)
+                    .header("content-type", "application/json")
+                    .json_body(json!({ "name": "test" }));
+            })
+            .await
     };
 
-    let response = remove_record(&8, &sync_account).await;
+    let (response, success_mock) = tokio::join!(request, recover_server);
+
+    assert_eq!(true, response.is_ok());
+    assert_eq!(1, success_mock.hits_async().await);
+
+    let user: Value = serde_json::from_str(&response.unwrap().text().await.unwrap())
+        .expect("cannot deserialize JSON");
+
+    assert_eq!(user.as_object().unwrap().get("name").unwrap(), "test");
+}
+
+#[tokio::test]
+async fn test_put_request_retries_server_error() {
+    let server = MockServer::start_async().await;
+
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(PUT).path("/endpoint/1");
+            then.status(500);
+        })
+        .await;
+
+    let response = make_put(
+        &test_client(),
+        server.url("/endpoint/1").to_string(),
+        json!({ "id": 1, "name": "updated" }),
+        None,
+    )
+    .await;
+
+    assert_eq!(true, response.is_err());
+    assert_eq!(3, mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_delete_request_retries_server_error() {
+    let server = MockServer::start_async().await;
+
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(DELETE).path("/endpoint/1");
+            then.status(500);
+        })
+        .await;
+
+    let response = make_delete(&test_client(), server.url("/endpoint/1").to_string(), None).await;
+
+    assert_eq!(true, response.is_err());
+    assert_eq!(3, mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_post_request_is_not_retried() {
+    let server = MockServer::start_async().await;
+
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/endpoint");
+            then.status(503);
+        })
+        .await;
+
+    let response = make_post(
+        &test_client(),
+        server.url("/endpoint").to_string(),
+        json!({ "name": "test" }),
+        None,
+    )
+    .await;
+
+    assert_eq!(true, response.is_err());
+    assert_eq!(1, mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_client_error_is_not_retried() {
+    let server = MockServer::start_async().await;
+
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/endpoint");
+            then.status(404);
+        })
+        .await;
+
+    let response = make_get(&test_client(), server.url("/endpoint").to_string(), None).await;
+
+    assert_eq!(true, response.is_err());
+    assert_eq!(1, mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_get_request_read_timeout() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/endpoint");
+            then.status(200).delay(Duration::from_secs(2));
+        })
+        .await;
+
+    let client = SyncClient::with_settings(
+        Duration::from_secs(1),
+        Duration::from_millis(200),
+        0,
+        Duration::from_millis(10),
+    );
+
+    let response = make_get(&client, server.url("/endpoint").to_string(), None).await;
 
     assert_eq!(true, response.is_err());
     assert_eq!(
-        "Error 401 Unauthorized Error from server",
+        "Error 0 Request to server timed out.",
         response.err().unwrap().formatted_message()
     );
 }
+
+#[tokio::test]
+async fn test_body_read_timeout_is_an_error() {
+    // httpmock delays whole responses, so a raw listener sends the headers and then stalls the body
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request).await;
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 64\r\n\r\n{\"version\"")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    });
+
+    let client = SyncClient::with_settings(
+        Duration::from_secs(1),
+        Duration::from_millis(200),
+        0,
+        Duration::from_millis(10),
+    );
+    let mut sync_account = phoenix_sync_account(url);
+    let capabilities = get_capabilities(&client, &mut sync_account).await;
+
+    let error = capabilities.err().unwrap();
+    assert_eq!(ResponseErrorKind::Connection, error.kind());
+    assert_eq!(
+        "Error 0 Request to server timed out.",
+        error.formatted_message()
+    );
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_get_request_retries_connection_error() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/endpoint", listener.local_addr().unwrap());
+    drop(listener);
+
+    let started = Instant::now();
+    let response = make_get(&test_client(), url, None).await;
+
+    assert_eq!(true, response.is_err());
+    assert_eq!(true, started.elapsed() >= Duration::from_millis(150));
+}
+
+#[test]
+fn test_retry_delay_backs_off_exponentially() {
+    let client = SyncClient::with_settings(
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        10,
+        Duration::from_millis(500),
+    );
+
+    assert_eq!(Duration::from_millis(500), client.retry_delay(0));
+    assert_eq!(Duration::from_millis(1000), client.retry_delay(1));
+    assert_eq!(Duration::from_millis(2000), client.retry_delay(2));
+    assert_eq!(Duration::from_millis(8000), client.retry_delay(5));
+    assert_eq!(Duration::from_millis(8000), client.retry_delay(40));
+}
+
+#[test]
+fn test_token_expiry_is_decoded() {
+    let token = jwt_with_expiry(1722803353);
+
+    assert_eq!(Some(1722803353), get_token_expiry(&token));
+    assert_eq!(true, is_token_expired(&token, 1722803353));
+    assert_eq!(true, is_token_expired(&token, 1722803300));
+    assert_eq!(false, is_token_expired(&token, 1722800000));
+}
+
+#[test]
+fn test_token_expiry_invalid_token() {
+    assert_eq!(None, get_token_expiry("token1234"));
+    assert_eq!(None, get_token_expiry("header.not-base64!.signature"));
+    assert_eq!(false, is_token_expired("token1234", 1722803353));
+}
+
+#[tokio::test]
+async fn test_authenticate_account_reuses_valid_token() {
+    let server = MockServer::start_async().await;
+    let token = jwt_with_expiry(now() + 3600);
+
+    let login_mock = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/api/login_check");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({ "token": "token1234" }));
+        })
+        .await;
+
+    let account = SyncAccount {
+        id: 1,
+        username: "test@test.com".to_string(),
+        password: "Passw!rd1234".to_string(),
+        url: server.url(""),
+        token: Some(token.clone()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    };
+
+    let response = authenticate_account(&test_client(), account).await;
+
+    assert_eq!(true, response.is_ok());
+    assert_eq!(Some(token), response.unwrap().token);
+    assert_eq!(0, login_mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_authenticate_account_refreshes_expired_token() {
+    let server = MockServer::start_async().await;
+    let new_token = jwt_with_expiry(now() + 3600);
+
+    let login_mock = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/api/login_check")
+                .json_body(json!({"username": "test@test.com", "password": "Passw!rd1234"}));
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({ "token": new_token }));
+        })
+        .await;
+
+    let account = SyncAccount {
+        id: 1,
+        username: "test@test.com".to_string(),
+        password: "Passw!rd1234".to_string(),
+        url: server.url(""),
+        token: Some(jwt_with_expiry(now() - 10)),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    };
+
+    let response = authenticate_account(&test_client(), account).await;
+
+    assert_eq!(true, response.is_ok());
+    assert_eq!(Some(new_token), response.unwrap().token);
+    assert_eq!(1, login_mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_get_manifest_logs_in_again_on_unauthorized() {
+    let server = MockServer::start_async().await;
+
+    let expired_mock = server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/api/records/manifest")
+                .header("Authorization", "Bearer expired");
+            then.status(401)
+                .header("content-type", "application/json")
+                .json_body(json!({ "code": 401, "message": "Expired JWT Token" }));
+        })
+        .await;
+
+    let login_mock = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/api/login_check")
+                .json_body(json!({"username": "test@test.com", "password": "Passw!rd1234"}));
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({ "token": "refreshed" }));
+        })
+        .await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/api/records/manifest")
+                .header("Authorization", "Bearer refreshed");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 1,
+                    "data": [
+                        {
+                            "id": 6,
+                            "updatedAt": 1722803353
+                        }
+                    ]
+                }));
+        })
+        .await;
+
+    let mut sync_account = SyncAccount {
+        id: 1,
+        username: "test@test.com".to_string(),
+        password: "Passw!rd1234".to_string(),
+        url: server.url(""),
+        token: Some("expired".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    };
+
+    let response = get_manifest(&test_client(), &mut sync_account).await;
+
+    assert_eq!(true, response.is_ok());
+    assert_eq!(6, response.unwrap()[0].id);
+    assert_eq!(Some("refreshed".to_string()), sync_account.token);
+    assert_eq!(1, expired_mock.hits_async().await);
+    assert_eq!(1, login_mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_get_manifest_only_logs_in_again_once() {
+    let server = MockServer::start_async().await;
+
+    let manifest_mock = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/records/manifest");
+            then.status(401)
+                .header("content-type", "application/json")
+                .json_body(json!({ "code": 401, "message": "Invalid JWT Token" }));
+        })
+        .await;
+
+    let login_mock = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/api/login_check");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({ "token": "refreshed" }));
+        })
+        .await;
+
+    let mut sync_account = SyncAccount {
+        id: 1,
+        username: "test@test.com".to_string(),
+        password: "Passw!rd1234".to_string(),
+        url: server.url(""),
+        token: Some("expired".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    };
+
+    let response = get_manifest(&test_client(), &mut sync_account).await;
+
+    assert_eq!(true, response.is_err());
+
+    let error = response.err().unwrap();
+
+    assert_eq!(true, error.is_authentication_error());
+    assert_eq!(ResponseErrorKind::Authentication, error.kind());
+    assert_eq!(2, manifest_mock.hits_async().await);
+    assert_eq!(1, login_mock.hits_async().await);
+}
+
+#[tokio::test]
+async fn test_server_error_is_not_authentication_error() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/endpoint");
+            then.status(403);
+        })
+        .await;
+
+    let response = make_get(&test_client(), server.url("/endpoint").to_string(), None).await;
+    let error = response.err().unwrap();
+
+    assert_eq!(false, error.is_authentication_error());
+    assert_eq!(ResponseErrorKind::Server, error.kind());
+    assert_eq!(
+        "Error 403 Forbidden Error from server",
+        error.formatted_message()
+    );
+}
+
+#[tokio::test]
+async fn test_get_capabilities_negotiates_version() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/api/capabilities")
+                .header("Authorization", "Bearer 123456789");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": {
+                        "minVersion": 1,
+                        "maxVersion": 4,
+                        "capabilities": ["colour", "paginatedManifest", "somethingNew"]
+                    }
+                }));
+        })
+        .await;
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let capabilities = get_capabilities(&test_client(), &mut sync_account)
+        .await
+        .unwrap();
+
+    assert_eq!(2, capabilities.api_version);
+    assert_eq!(true, capabilities.supports(Capability::Colour));
+    assert_eq!(true, capabilities.supports(Capability::PaginatedManifest));
+    assert_eq!(false, capabilities.supports(Capability::BatchRecords));
+}
+
+#[tokio::test]
+async fn test_get_capabilities_legacy_server() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/capabilities");
+            then.status(404);
+        })
+        .await;
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let capabilities = get_capabilities(&test_client(), &mut sync_account).await;
+
+    assert_eq!(ServerCapabilities::legacy(), capabilities.unwrap());
+}
+
+#[tokio::test]
+async fn test_get_capabilities_server_too_new() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/capabilities");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": {
+                        "minVersion": 3,
+                        "maxVersion": 4,
+                        "capabilities": []
+                    }
+                }));
+        })
+        .await;
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let error = get_capabilities(&test_client(), &mut sync_account)
+        .await
+        .err()
+        .unwrap();
+
+    assert_eq!(ResponseErrorKind::IncompatibleVersion, error.kind());
+    assert_eq!(
+        "Error 426 Server API version 3 is too new, please update Phoenix",
+        error.formatted_message()
+    );
+}
+
+#[tokio::test]
+async fn test_get_manifest_unsupported_version() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/records/manifest");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 3,
+                    "data": {
+                        "records": []
+                    }
+                }));
+        })
+        .await;
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let error = get_manifest(&test_client(), &mut sync_account)
+        .await
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        "Error 426 Server API version 3 is too new, please update Phoenix",
+        error.formatted_message()
+    );
+}
+
+#[test]
+fn test_negotiate_api_version() {
+    assert_eq!(1, negotiate_api_version(1, 1).unwrap());
+    assert_eq!(2, negotiate_api_version(1, 2).unwrap());
+    assert_eq!(2, negotiate_api_version(2, 5).unwrap());
+    assert_eq!(
+        "Error 426 Server API version 0 is too old, please update the sync server",
+        negotiate_api_version(0, 0)
+            .err()
+            .unwrap()
+            .formatted_message()
+    );
+}
+
+#[tokio::test]
+async fn test_get_manifest_changes_follows_pages() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/api/records/manifest")
+                .query_param("since", "cursor-1")
+                .query_param("page", "page-2");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": [{ "id": 7, "updatedAt": 1722803934 }],
+                    "removed": [],
+                    "nextPage": null,
+                    "cursor": "cursor-2"
+                }));
+        })
+        .await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/api/records/manifest")
+                .query_param("since", "cursor-1");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": [{ "id": 6, "updatedAt": 1722803353 }],
+                    "removed": [3],
+                    "nextPage": "page-2",
+                    "cursor": null
+                }));
+        })
+        .await;
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let changes = get_manifest_changes(&test_client(), Some("cursor-1"), &mut sync_account)
+        .await
+        .unwrap();
+
+    assert_eq!(false, changes.complete);
+    assert_eq!(2, changes.records.len());
+    assert_eq!(6, changes.records[0].id);
+    assert_eq!(7, changes.records[1].id);
+    assert_eq!(vec![3], changes.removed);
+    assert_eq!(Some("cursor-2".to_string()), changes.cursor);
+}
+
+#[tokio::test]
+async fn test_expired_manifest_cursor_falls_back_to_full_listing() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/capabilities");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": {
+                        "minVersion": 1,
+                        "maxVersion": 2,
+                        "capabilities": ["colour", "paginatedManifest"]
+                    }
+                }));
+        })
+        .await;
+
+    let expired_mock = server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/api/records/manifest")
+                .query_param("since", "expired");
+            then.status(410);
+        })
+        .await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/records/manifest");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": [{ "id": 6, "updatedAt": 1722803353 }],
+                    "nextPage": null,
+                    "cursor": "cursor-1"
+                }));
+        })
+        .await;
+
+    let mut backend = PhoenixBackend::connect(test_client(), phoenix_sync_account(server.url("")))
+        .await
+        .unwrap();
+    let changes = backend
+        .list_manifest_changes(Some("expired"))
+        .await
+        .unwrap();
+
+    assert_eq!(1, expired_mock.hits_async().await);
+    assert_eq!(true, changes.complete);
+    assert_eq!(6, changes.records[0].id);
+    assert_eq!(Some("cursor-1".to_string()), changes.cursor);
+}
+
+#[tokio::test]
+async fn test_legacy_server_lists_full_manifest() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/capabilities");
+            then.status(404);
+        })
+        .await;
+
+    let manifest_mock = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/records/manifest");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 1,
+                    "data": [{ "id": 6, "updatedAt": 1722803353 }]
+                }));
+        })
+        .await;
+
+    let mut backend = PhoenixBackend::connect(test_client(), phoenix_sync_account(server.url("")))
+        .await
+        .unwrap();
+    let changes = backend
+        .list_manifest_changes(Some("cursor-1"))
+        .await
+        .unwrap();
+
+    assert_eq!(1, manifest_mock.hits_async().await);
+    assert_eq!(true, changes.complete);
+    assert_eq!(None, changes.cursor);
+}
+
+#[tokio::test]
+async fn test_update_record_leaves_out_unsupported_colour() {
+    let server = MockServer::start_async().await;
+
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(PUT).path("/api/records/4").json_body(json!({
+                "name": "No Colour",
+                "secret": "Test123",
+                "totpStep": 30,
+                "otpDigits": 6,
+                "totpAlgorithm": null,
+            }));
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": { "id": 4, "syncHash": "HASHED1234", "updatedAt": 1722803353 }
+                }));
+        })
+        .await;
+
+    let account = Account {
+        id: 1,
+        name: "No Colour".to_string(),
+        secret: "Test123".to_string(),
+        colour: "ffc456".to_string(),
+        totp_step: 30,
+        otp_digits: 6,
+        algorithm: None,
+        external_id: Some(4),
+        external_last_updated: None,
+        external_hash: None,
+        deleted_at: None,
+        sync_account_id: None,
+    };
+
+    let capabilities = ServerCapabilities {
+        api_version: 2,
+        capabilities: vec![],
+    };
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let response = update_record(&test_client(), &account, &capabilities, &mut sync_account).await;
+
+    mock.assert_async().await;
+    assert_eq!(true, response.is_ok());
+}
+
+#[tokio::test]
+async fn test_backend_sends_negotiated_version() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/capabilities");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": {
+                        "minVersion": 1,
+                        "maxVersion": 2,
+                        "capabilities": ["paginatedManifest"]
+                    }
+                }));
+        })
+        .await;
+
+    let record_mock = server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/api/records/6")
+                .header("Api-Version", "2");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": {
+                        "id": 6,
+                        "name": "Versioned",
+                        "secret": "Test123",
+                        "colour": "ff0000",
+                        "totpStep": 30,
+                        "otpDigits": 6,
+                        "algorithm": null,
+                        "syncHash": "HASHED1234",
+                        "updatedAt": 1722803353
+                    }
+                }));
+        })
+        .await;
+
+    let mut backend = PhoenixBackend::connect(test_client(), phoenix_sync_account(server.url("")))
+        .await
+        .unwrap();
+    let record = backend.fetch_record(&6).await.unwrap();
+
+    record_mock.assert_async().await;
+    assert_eq!(2, backend.capabilities().api_version);
+    assert_eq!(None, record.colour);
+}
+
+#[tokio::test]
+async fn test_get_records_batch_per_item_results() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/api/records/batch/fetch")
+                .json_body(json!({ "ids": [6, 7] }))
+                .header("Authorization", "Bearer 123456789");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": [
+                        {
+                            "status": 200,
+                            "record": {
+                                "id": 6,
+                                "name": "Batch Item",
+                                "secret": "Test123",
+                                "colour": "fff234",
+                                "totpStep": 30,
+                                "otpDigits": 6,
+                                "algorithm": null,
+                                "syncHash": "HASHED1234",
+                                "updatedAt": 1722803353
+                            },
+                            "error": null
+                        },
+                        {
+                            "status": 404,
+                            "record": null,
+                            "error": "Record not found"
+                        }
+                    ]
+                }));
+        })
+        .await;
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let results = get_records_batch(&test_client(), &[6, 7], &mut sync_account).await;
+
+    assert_eq!(2, results.len());
+    assert_eq!("Batch Item", results[0].as_ref().unwrap().name);
+    assert_eq!(
+        "Error 404 Not Found Record not found",
+        results[1].as_ref().err().unwrap().formatted_message()
+    );
+}
+
+#[tokio::test]
+async fn test_upsert_records_batch_sends_keys() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/api/records/batch/upsert")
+                .json_body(json!({
+                    "records": [
+                        {
+                            "id": null,
+                            "idempotencyKey": IDEMPOTENCY_KEY,
+                            "name": "New Item",
+                            "secret": "Test123",
+                            "colour": "fff234",
+                            "totpStep": 30,
+                            "otpDigits": 6,
+                            "totpAlgorithm": null,
+                        },
+                        {
+                            "id": 12,
+                            "idempotencyKey": null,
+                            "name": "Existing Item",
+                            "secret": "Test123",
+                            "colour": "fff234",
+                            "totpStep": 30,
+                            "otpDigits": 6,
+                            "totpAlgorithm": null,
+                        }
+                    ]
+                }));
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": [
+                        {
+                            "status": 201,
+                            "record": { "id": 13, "syncHash": "NEW1234", "updatedAt": 1722803353 },
+                            "error": null
+                        },
+                        {
+                            "status": 200,
+                            "record": { "id": 12, "syncHash": "UPDATED1234", "updatedAt": 1722803353 },
+                            "error": null
+                        }
+                    ]
+                }));
+        })
+        .await;
+
+    let upserts = vec![
+        RecordUpsert {
+            account: batch_account("New Item", None),
+            idempotency_key: Some(IDEMPOTENCY_KEY.to_string()),
+        },
+        RecordUpsert {
+            account: batch_account("Existing Item", Some(12)),
+            idempotency_key: None,
+        },
+    ];
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let results = upsert_records_batch(
+        &test_client(),
+        &upserts,
+        &ServerCapabilities::legacy(),
+        &mut sync_account,
+    )
+    .await;
+
+    assert_eq!(13, results[0].as_ref().unwrap().id);
+    assert_eq!("UPDATED1234", results[1].as_ref().unwrap().sync_hash);
+}
+
+#[tokio::test]
+async fn test_failed_batch_fails_every_item() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(POST).path("/api/records/batch/delete");
+            then.status(400);
+        })
+        .await;
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let results = remove_records_batch(&test_client(), &[6, 7, 8], &mut sync_account).await;
+
+    assert_eq!(3, results.len());
+    assert_eq!(true, results.iter().all(|result| result.is_err()));
+}
+
+#[tokio::test]
+async fn test_short_batch_response_fails_missing_items() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(POST).path("/api/records/batch/delete");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 2,
+                    "data": [{ "status": 204, "record": null, "error": null }]
+                }));
+        })
+        .await;
+
+    let mut sync_account = phoenix_sync_account(server.url(""));
+    let results = remove_records_batch(&test_client(), &[6, 7], &mut sync_account).await;
+
+    assert_eq!(true, results[0].as_ref().unwrap());
+    assert_eq!(
+        "Error 418 Could not parse Server response",
+        results[1].as_ref().err().unwrap().formatted_message()
+    );
+}
+
+#[tokio::test]
+async fn test_backend_without_batch_support_fetches_each_record() {
+    let server = MockServer::start_async().await;
+
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/capabilities");
+            then.status(404);
+        })
+        .await;
+
+    let batch_mock = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/api/records/batch/fetch");
+            then.status(200);
+        })
+        .await;
+
+    let record_mock = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/records/6");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "version": 1,
+                    "data": {
+                        "id": 6,
+                        "name": "Single Item",
+                        "secret": "Test123",
+                        "colour": "fff234",
+                        "totpStep": 30,
+                        "otpDigits": 6,
+                        "algorithm": null,
+                        "syncHash": "HASHED1234",
+                        "updatedAt": 1722803353
+                    }
+                }));
+        })
+        .await;
+
+    let mut backend = PhoenixBackend::connect(test_client(), phoenix_sync_account(server.url("")))
+        .await
+        .unwrap();
+    let results = backend.fetch_records(&[6]).await;
+
+    assert_eq!("Single Item", results[0].as_ref().unwrap().name);
+    assert_eq!(1, record_mock.hits_async().await);
+    assert_eq!(0, batch_mock.hits_async().await);
+}
+
+fn batch_account(name: &str, external_id: Option<i32>) -> Account {
+    Account {
+        id: 1,
+        name: name.to_string(),
+        secret: "Test123".to_string(),
+        colour: "fff234".to_string(),
+        totp_step: 30,
+        otp_digits: 6,
+        algorithm: None,
+        external_id,
+        external_last_updated: None,
+        external_hash: None,
+        deleted_at: None,
+        sync_account_id: None,
+    }
+}
+
+fn phoenix_sync_account(url: String) -> SyncAccount {
+    SyncAccount {
+        id: 1,
+        username: "test@test.com".to_string(),
+        password: "Password".to_string(),
+        url,
+        token: Some("123456789".to_string()),
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    }
+}
+
+fn jwt_with_expiry(expiry: u64) -> String {
+    let header = general_purpose::URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+    let claims = general_purpose::URL_SAFE_NO_PAD
+        .encode(json!({ "username": "test@test.com", "exp": expiry }).to_string());
+
+    format!("{}.{}.signature", header, claims)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Could not generate UNIX time")
+        .as_secs()
+}
+
+fn test_client() -> SyncClient {
+    SyncClient::with_settings(
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        2,
+        Duration::from_millis(50),
+    )
+}
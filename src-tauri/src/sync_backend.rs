@@ -0,0 +1,375 @@
+use crate::database::{Account, AccountAlgorithm, SyncAccount, SyncType};
+use crate::encryption;
+use crate::sync_api::{
+    ManifestChanges, PhoenixBackend, Record, RecordUpsert, ResponseError, ResponseErrorKind,
+    SyncClient, SyncManifest, VerboseRecord,
+};
+use crate::sync_folder::FolderBackend;
+use crate::sync_webdav::WebDavBackend;
+use chacha20poly1305::Key;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const PASSPHRASE_MANIFEST_VERSION: i8 = 1;
+const PASSPHRASE_CHECK: &str = "phoenix-auth";
+const RECORD_ID_CANDIDATES: usize = 8;
+
+// Stored next to the records by targets that hold them encrypted, the server never sees the passphrase
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PassphraseManifest {
+    version: i8,
+    salt: String,
+    check: String,
+}
+
+impl PassphraseManifest {
+    pub fn new(passphrase: &str) -> PassphraseManifest {
+        let salt = encryption::generate_salt();
+        let key = encryption::derive_passphrase_key(passphrase, &salt);
+
+        PassphraseManifest {
+            version: PASSPHRASE_MANIFEST_VERSION,
+            salt,
+            check: encryption::encrypt_with_key(&key, PASSPHRASE_CHECK).unwrap(),
+        }
+    }
+
+    pub fn unlock(&self, passphrase: &str) -> Option<Key> {
+        let key = encryption::derive_passphrase_key(passphrase, &self.salt);
+
+        match encryption::decrypt_with_key(&key, &self.check) {
+            Ok(check) if check == PASSPHRASE_CHECK => Some(key),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncryptedRecord {
+    name: String,
+    secret: String,
+    colour: String,
+    totp_step: i32,
+    otp_digits: i32,
+    algorithm: Option<AccountAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    idempotency_key: Option<String>,
+}
+
+pub fn encrypt_record(
+    key: &Key,
+    account: &Account,
+    idempotency_key: Option<&str>,
+) -> Result<String, String> {
+    let encrypted_record = EncryptedRecord {
+        name: account.name.clone(),
+        secret: account.secret.clone(),
+        colour: account.colour.clone(),
+        totp_step: account.totp_step,
+        otp_digits: account.otp_digits,
+        algorithm: account.algorithm.clone(),
+        idempotency_key: idempotency_key.map(str::to_string),
+    };
+
+    match serde_json::to_string(&encrypted_record) {
+        Ok(contents) => encryption::encrypt_with_key(key, &contents),
+        Err(_) => Err("Could not serialise record".to_string()),
+    }
+}
+
+pub fn decrypt_record(key: &Key, encrypted: &str, record: Record) -> Result<VerboseRecord, String> {
+    let contents = encryption::decrypt_with_key(key, encrypted)?;
+    let encrypted_record = match serde_json::from_str::<EncryptedRecord>(&contents) {
+        Ok(encrypted_record) => encrypted_record,
+        Err(_) => return Err("Could not parse record".to_string()),
+    };
+
+    Ok(VerboseRecord {
+        id: record.id,
+        name: encrypted_record.name,
+        secret: encrypted_record.secret,
+        colour: Some(encrypted_record.colour),
+        totp_step: encrypted_record.totp_step,
+        otp_digits: encrypted_record.otp_digits,
+        algorithm: encrypted_record.algorithm,
+        sync_hash: record.sync_hash,
+        updated_at: record.updated_at,
+    })
+}
+
+// Record ids are a truncated hash, so an existing record is only a replay when it carries the same key
+pub fn is_replayed_record(key: &Key, encrypted: &str, idempotency_key: &str) -> bool {
+    encryption::decrypt_with_key(key, encrypted)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<EncryptedRecord>(&contents).ok())
+        .and_then(|encrypted_record| encrypted_record.idempotency_key)
+        .is_some_and(|existing_key| existing_key == idempotency_key)
+}
+
+pub trait SyncBackend {
+    fn sync_account(&self) -> &SyncAccount;
+
+    async fn list_manifest(&mut self) -> Result<Vec<SyncManifest>, ResponseError>;
+
+    // Targets that can't do deltas list everything
+    async fn list_manifest_changes(
+        &mut self,
+        _since: Option<&str>,
+    ) -> Result<ManifestChanges, ResponseError> {
+        self.list_manifest().await.map(ManifestChanges::full)
+    }
+
+    async fn fetch_record(&mut self, id: &i32) -> Result<VerboseRecord, ResponseError>;
+
+    async fn create_record(
+        &mut self,
+        account: &Account,
+        idempotency_key: &str,
+    ) -> Result<Record, ResponseError>;
+
+    async fn update_record(&mut self, account: &Account) -> Result<Record, ResponseError>;
+
+    async fn delete_record(&mut self, id: &i32) -> Result<bool, ResponseError>;
+
+    // Batch calls return one result per item in request order
+    async fn fetch_records(&mut self, ids: &[i32]) -> Vec<Result<VerboseRecord, ResponseError>> {
+        fetch_each_record(self, ids).await
+    }
+
+    async fn upsert_records(
+        &mut self,
+        upserts: &[RecordUpsert],
+    ) -> Vec<Result<Record, ResponseError>> {
+        upsert_each_record(self, upserts).await
+    }
+
+    async fn delete_records(&mut self, ids: &[i32]) -> Vec<Result<bool, ResponseError>> {
+        delete_each_record(self, ids).await
+    }
+}
+
+pub async fn fetch_each_record<B: SyncBackend + ?Sized>(
+    backend: &mut B,
+    ids: &[i32],
+) -> Vec<Result<VerboseRecord, ResponseError>> {
+    let mut results = Vec::with_capacity(ids.len());
+
+    for id in ids {
+        results.push(backend.fetch_record(id).await);
+    }
+
+    results
+}
+
+pub async fn upsert_each_record<B: SyncBackend + ?Sized>(
+    backend: &mut B,
+    upserts: &[RecordUpsert],
+) -> Vec<Result<Record, ResponseError>> {
+    let mut results = Vec::with_capacity(upserts.len());
+
+    for upsert in upserts {
+        let result = match &upsert.idempotency_key {
+            Some(idempotency_key) if upsert.account.external_id.is_none() => {
+                backend
+                    .create_record(&upsert.account, idempotency_key)
+                    .await
+            }
+            _ => backend.update_record(&upsert.account).await,
+        };
+
+        results.push(result);
+    }
+
+    results
+}
+
+pub async fn delete_each_record<B: SyncBackend + ?Sized>(
+    backend: &mut B,
+    ids: &[i32],
+) -> Vec<Result<bool, ResponseError>> {
+    let mut results = Vec::with_capacity(ids.len());
+
+    for id in ids {
+        results.push(backend.delete_record(id).await);
+    }
+
+    results
+}
+
+pub enum SyncTarget {
+    Phoenix(PhoenixBackend),
+    Folder(FolderBackend),
+    WebDav(WebDavBackend),
+}
+
+impl SyncTarget {
+    pub async fn connect(
+        client: SyncClient,
+        account: SyncAccount,
+    ) -> Result<SyncTarget, ResponseError> {
+        match account.sync_type {
+            SyncType::PHOENIX => match PhoenixBackend::connect(client, account).await {
+                Ok(backend) => Ok(SyncTarget::Phoenix(backend)),
+                Err(e) => Err(e),
+            },
+            SyncType::FOLDER => match FolderBackend::open(account) {
+                Ok(backend) => Ok(SyncTarget::Folder(backend)),
+                Err(e) => Err(e),
+            },
+            SyncType::WEBDAV => match WebDavBackend::connect(client, account).await {
+                Ok(backend) => Ok(SyncTarget::WebDav(backend)),
+                Err(e) => Err(e),
+            },
+        }
+    }
+}
+
+impl SyncBackend for SyncTarget {
+    fn sync_account(&self) -> &SyncAccount {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.sync_account(),
+            SyncTarget::Folder(backend) => backend.sync_account(),
+            SyncTarget::WebDav(backend) => backend.sync_account(),
+        }
+    }
+
+    async fn list_manifest(&mut self) -> Result<Vec<SyncManifest>, ResponseError> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.list_manifest().await,
+            SyncTarget::Folder(backend) => backend.list_manifest().await,
+            SyncTarget::WebDav(backend) => backend.list_manifest().await,
+        }
+    }
+
+    async fn list_manifest_changes(
+        &mut self,
+        since: Option<&str>,
+    ) -> Result<ManifestChanges, ResponseError> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.list_manifest_changes(since).await,
+            SyncTarget::Folder(backend) => backend.list_manifest_changes(since).await,
+            SyncTarget::WebDav(backend) => backend.list_manifest_changes(since).await,
+        }
+    }
+
+    async fn fetch_record(&mut self, id: &i32) -> Result<VerboseRecord, ResponseError> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.fetch_record(id).await,
+            SyncTarget::Folder(backend) => backend.fetch_record(id).await,
+            SyncTarget::WebDav(backend) => backend.fetch_record(id).await,
+        }
+    }
+
+    async fn create_record(
+        &mut self,
+        account: &Account,
+        idempotency_key: &str,
+    ) -> Result<Record, ResponseError> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.create_record(account, idempotency_key).await,
+            SyncTarget::Folder(backend) => backend.create_record(account, idempotency_key).await,
+            SyncTarget::WebDav(backend) => backend.create_record(account, idempotency_key).await,
+        }
+    }
+
+    async fn update_record(&mut self, account: &Account) -> Result<Record, ResponseError> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.update_record(account).await,
+            SyncTarget::Folder(backend) => backend.update_record(account).await,
+            SyncTarget::WebDav(backend) => backend.update_record(account).await,
+        }
+    }
+
+    async fn delete_record(&mut self, id: &i32) -> Result<bool, ResponseError> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.delete_record(id).await,
+            SyncTarget::Folder(backend) => backend.delete_record(id).await,
+            SyncTarget::WebDav(backend) => backend.delete_record(id).await,
+        }
+    }
+
+    async fn fetch_records(&mut self, ids: &[i32]) -> Vec<Result<VerboseRecord, ResponseError>> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.fetch_records(ids).await,
+            SyncTarget::Folder(backend) => backend.fetch_records(ids).await,
+            SyncTarget::WebDav(backend) => backend.fetch_records(ids).await,
+        }
+    }
+
+    async fn upsert_records(
+        &mut self,
+        upserts: &[RecordUpsert],
+    ) -> Vec<Result<Record, ResponseError>> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.upsert_records(upserts).await,
+            SyncTarget::Folder(backend) => backend.upsert_records(upserts).await,
+            SyncTarget::WebDav(backend) => backend.upsert_records(upserts).await,
+        }
+    }
+
+    async fn delete_records(&mut self, ids: &[i32]) -> Vec<Result<bool, ResponseError>> {
+        match self {
+            SyncTarget::Phoenix(backend) => backend.delete_records(ids).await,
+            SyncTarget::Folder(backend) => backend.delete_records(ids).await,
+            SyncTarget::WebDav(backend) => backend.delete_records(ids).await,
+        }
+    }
+}
+
+pub fn record_id_for_key(idempotency_key: &str) -> i32 {
+    let digest = Sha256::digest(idempotency_key.as_bytes());
+    let id = (u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) >> 1) as i32;
+
+    match id {
+        0 => 1,
+        id => id,
+    }
+}
+
+// Ids a create tries in order, a replay walks the same sequence and stops at the record it wrote
+pub fn record_ids_for_key(idempotency_key: &str) -> impl Iterator<Item = i32> + '_ {
+    (0..RECORD_ID_CANDIDATES).map(move |attempt| match attempt {
+        0 => record_id_for_key(idempotency_key),
+        attempt => record_id_for_key(&format!("{}:{}", idempotency_key, attempt)),
+    })
+}
+
+pub fn record_id_exhausted_error() -> ResponseError {
+    ResponseError::new(
+        "409",
+        "Could not allocate a record id",
+        ResponseErrorKind::Storage,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync_backend::{record_id_for_key, record_ids_for_key};
+
+    #[test]
+    fn test_record_id_for_key() {
+        let id = record_id_for_key("0123456789abcdef0123456789abcdef");
+
+        assert_eq!(true, id > 0);
+        assert_eq!(id, record_id_for_key("0123456789abcdef0123456789abcdef"));
+        assert_ne!(id, record_id_for_key("fedcba9876543210fedcba9876543210"));
+    }
+
+    #[test]
+    fn test_record_ids_for_key() {
+        let ids: Vec<i32> = record_ids_for_key("0123456789abcdef0123456789abcdef").collect();
+
+        assert_eq!(8, ids.len());
+        assert_eq!(
+            record_id_for_key("0123456789abcdef0123456789abcdef"),
+            ids[0]
+        );
+        assert_eq!(
+            ids,
+            record_ids_for_key("0123456789abcdef0123456789abcdef").collect::<Vec<i32>>()
+        );
+        assert_ne!(ids[0], ids[1]);
+    }
+}
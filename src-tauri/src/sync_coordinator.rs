@@ -0,0 +1,172 @@
+use crate::state::ServiceAccess;
+use crate::sync_local;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+use tokio::sync::Notify;
+
+#[derive(Clone, Default)]
+pub struct SyncCancellation {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl SyncCancellation {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub async fn cancelled(&self) {
+        let mut notified = pin!(self.notify.notified());
+
+        // Register as a waiter before checking the flag so a cancel in between is not missed
+        notified.as_mut().enable();
+
+        if self.is_cancelled() {
+            return;
+        }
+
+        notified.await;
+    }
+}
+
+#[derive(Default)]
+struct CoordinatorState {
+    running: bool,
+    pending: bool,
+    cancellation: SyncCancellation,
+}
+
+#[derive(Clone, Default)]
+pub struct SyncCoordinator {
+    state: Arc<Mutex<CoordinatorState>>,
+}
+
+impl SyncCoordinator {
+    // Queues a single follow-up when a run is already in flight
+    pub fn request(&self) -> Option<SyncCancellation> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.running {
+            state.pending = true;
+            return None;
+        }
+
+        state.running = true;
+        state.cancellation = SyncCancellation::default();
+
+        Some(state.cancellation.clone())
+    }
+
+    pub fn finish(&self) -> Option<SyncCancellation> {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.pending {
+            state.running = false;
+            return None;
+        }
+
+        state.pending = false;
+        state.cancellation = SyncCancellation::default();
+
+        Some(state.cancellation.clone())
+    }
+
+    pub fn cancel(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.running {
+            return false;
+        }
+
+        state.pending = false;
+        state.cancellation.cancel();
+
+        true
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state.lock().unwrap().running
+    }
+}
+
+pub fn request_sync(app_handle: AppHandle) -> bool {
+    let cancellation = match app_handle.sync_coordinator().request() {
+        Some(cancellation) => cancellation,
+        None => return false,
+    };
+
+    tauri::async_runtime::spawn(run_sync(app_handle, cancellation));
+
+    true
+}
+
+async fn run_sync(app_handle: AppHandle, cancellation: SyncCancellation) {
+    let coordinator = app_handle.sync_coordinator();
+    let mut next_run = Some(cancellation);
+
+    while let Some(cancellation) = next_run {
+        sync_local::sync_all_accounts(&app_handle, cancellation).await;
+
+        let report = app_handle.sync_report();
+        app_handle.sync_scheduler().record_run(
+            app_handle.now(),
+            report.cancelled || report.success == Some(true),
+        );
+
+        next_run = coordinator.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync_coordinator::SyncCoordinator;
+
+    #[test]
+    fn test_single_run_at_a_time() {
+        let coordinator = SyncCoordinator::default();
+
+        assert_eq!(true, coordinator.request().is_some());
+        assert_eq!(true, coordinator.is_running());
+        assert_eq!(true, coordinator.request().is_none());
+    }
+
+    #[test]
+    fn test_requests_coalesce_into_one_follow_up() {
+        let coordinator = SyncCoordinator::default();
+
+        coordinator.request();
+        coordinator.request();
+        coordinator.request();
+
+        assert_eq!(true, coordinator.finish().is_some());
+        assert_eq!(true, coordinator.is_running());
+        assert_eq!(true, coordinator.finish().is_none());
+        assert_eq!(false, coordinator.is_running());
+    }
+
+    #[test]
+    fn test_cancel_in_flight_run() {
+        let coordinator = SyncCoordinator::default();
+
+        assert_eq!(false, coordinator.cancel());
+
+        let cancellation = coordinator.request().unwrap();
+        coordinator.request();
+
+        assert_eq!(false, cancellation.is_cancelled());
+        assert_eq!(true, coordinator.cancel());
+        assert_eq!(true, cancellation.is_cancelled());
+        assert_eq!(true, coordinator.finish().is_none());
+
+        let next_run = coordinator.request().unwrap();
+
+        assert_eq!(false, next_run.is_cancelled());
+    }
+}
@@ -0,0 +1,295 @@
+use crate::database::{Account, SyncAccount};
+use crate::sync_api::{Record, ResponseError, ResponseErrorKind, SyncManifest, VerboseRecord};
+use crate::sync_backend::{
+    decrypt_record, encrypt_record, is_replayed_record, record_id_exhausted_error,
+    record_ids_for_key, PassphraseManifest, SyncBackend,
+};
+use chacha20poly1305::Key;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[cfg(test)]
+#[path = "./sync_folder_test.rs"]
+mod tests;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const RECORD_DIRECTORY_NAME: &str = "records";
+const RECORD_FILE_EXTENSION: &str = "record";
+
+pub struct FolderBackend {
+    account: SyncAccount,
+    path: PathBuf,
+    key: Key,
+}
+
+impl FolderBackend {
+    pub fn open(account: SyncAccount) -> Result<FolderBackend, ResponseError> {
+        let passphrase = folder_passphrase(&account)?;
+        let path = PathBuf::from(&account.url);
+
+        if !path.is_dir() {
+            return Err(storage_error("Sync folder does not exist"));
+        }
+
+        if fs::create_dir_all(path.join(RECORD_DIRECTORY_NAME)).is_err() {
+            return Err(storage_error("Could not create records folder"));
+        }
+
+        let manifest = match read_manifest(&path) {
+            Ok(Some(manifest)) => manifest,
+            Ok(None) => {
+                let manifest = PassphraseManifest::new(&passphrase);
+
+                if let Err(e) = write_manifest(&path, &manifest) {
+                    return Err(e);
+                }
+
+                manifest
+            }
+            Err(e) => return Err(e),
+        };
+
+        let key = unlock_manifest(&manifest, &passphrase)?;
+
+        Ok(FolderBackend { account, path, key })
+    }
+
+    // Checks the folder and passphrase without writing anything, the manifest is only created once the account syncs
+    pub fn validate(account: &SyncAccount) -> Result<(), ResponseError> {
+        let passphrase = folder_passphrase(account)?;
+        let path = PathBuf::from(&account.url);
+
+        if !path.is_dir() {
+            return Err(storage_error("Sync folder does not exist"));
+        }
+
+        match read_manifest(&path)? {
+            Some(manifest) => unlock_manifest(&manifest, &passphrase).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    fn record_directory(&self) -> PathBuf {
+        self.path.join(RECORD_DIRECTORY_NAME)
+    }
+
+    fn record_path(&self, id: &i32) -> PathBuf {
+        self.record_directory()
+            .join(format!("{}.{}", id, RECORD_FILE_EXTENSION))
+    }
+
+    // Each device only touches its own record files, so the listing comes from the folder itself
+    fn read_record(&self, id: &i32) -> Result<(Record, String), ResponseError> {
+        let record_path = self.record_path(id);
+
+        let encrypted = match fs::read_to_string(&record_path) {
+            Ok(encrypted) => encrypted,
+            Err(_) => return Err(not_found_error()),
+        };
+        let updated_at = match fs::metadata(&record_path) {
+            Ok(metadata) => modified_timestamp(&metadata),
+            Err(_) => return Err(not_found_error()),
+        };
+
+        Ok((
+            Record {
+                id: *id,
+                sync_hash: hash_contents(&encrypted),
+                updated_at,
+            },
+            encrypted,
+        ))
+    }
+
+    fn write_record(
+        &self,
+        id: i32,
+        account: &Account,
+        idempotency_key: Option<&str>,
+    ) -> Result<Record, ResponseError> {
+        let encrypted = match encrypt_record(&self.key, account, idempotency_key) {
+            Ok(encrypted) => encrypted,
+            Err(e) => return Err(storage_error(&e)),
+        };
+
+        if let Err(e) = write_atomically(&self.record_path(&id), &encrypted) {
+            return Err(e);
+        }
+
+        self.read_record(&id).map(|(record, _)| record)
+    }
+}
+
+impl SyncBackend for FolderBackend {
+    fn sync_account(&self) -> &SyncAccount {
+        &self.account
+    }
+
+    async fn list_manifest(&mut self) -> Result<Vec<SyncManifest>, ResponseError> {
+        let entries = match fs::read_dir(self.record_directory()) {
+            Ok(entries) => entries,
+            Err(_) => return Err(storage_error("Could not read records folder")),
+        };
+
+        Ok(entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+
+                if path.extension()? != RECORD_FILE_EXTENSION {
+                    return None;
+                }
+
+                Some(SyncManifest {
+                    id: path.file_stem()?.to_str()?.parse::<i32>().ok()?,
+                    updated_at: modified_timestamp(&entry.metadata().ok()?),
+                })
+            })
+            .collect())
+    }
+
+    async fn fetch_record(&mut self, id: &i32) -> Result<VerboseRecord, ResponseError> {
+        let (record, encrypted) = match self.read_record(id) {
+            Ok(record) => record,
+            Err(e) => return Err(e),
+        };
+        match decrypt_record(&self.key, &encrypted, record) {
+            Ok(record) => Ok(record),
+            Err(e) => Err(storage_error(&e)),
+        }
+    }
+
+    async fn create_record(
+        &mut self,
+        account: &Account,
+        idempotency_key: &str,
+    ) -> Result<Record, ResponseError> {
+        for id in record_ids_for_key(idempotency_key) {
+            let (record, encrypted) = match self.read_record(&id) {
+                Ok(existing) => existing,
+                Err(_) => return self.write_record(id, account, Some(idempotency_key)),
+            };
+
+            // A replayed create returns the record written by the first attempt
+            if is_replayed_record(&self.key, &encrypted, idempotency_key) {
+                return Ok(record);
+            }
+        }
+
+        Err(record_id_exhausted_error())
+    }
+
+    async fn update_record(&mut self, account: &Account) -> Result<Record, ResponseError> {
+        match account.external_id {
+            Some(id) => self.write_record(id, account, None),
+            None => Err(ResponseError::new(
+                "400",
+                "Missing External Id",
+                ResponseErrorKind::Request,
+            )),
+        }
+    }
+
+    async fn delete_record(&mut self, id: &i32) -> Result<bool, ResponseError> {
+        let record_path = self.record_path(id);
+
+        if !record_path.exists() {
+            return Err(not_found_error());
+        }
+
+        match fs::remove_file(record_path) {
+            Ok(_) => Ok(true),
+            Err(_) => Err(storage_error("Could not delete record file")),
+        }
+    }
+}
+
+fn folder_passphrase(account: &SyncAccount) -> Result<String, ResponseError> {
+    match &account.passphrase {
+        Some(passphrase) if !passphrase.is_empty() => Ok(passphrase.clone()),
+        _ => Err(ResponseError::new(
+            "400",
+            "Missing sync folder passphrase",
+            ResponseErrorKind::Request,
+        )),
+    }
+}
+
+fn unlock_manifest(manifest: &PassphraseManifest, passphrase: &str) -> Result<Key, ResponseError> {
+    match manifest.unlock(passphrase) {
+        Some(key) => Ok(key),
+        None => Err(ResponseError::new(
+            "401",
+            "Incorrect sync folder passphrase",
+            ResponseErrorKind::Authentication,
+        )),
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<Option<PassphraseManifest>, ResponseError> {
+    let manifest_path = path.join(MANIFEST_FILE_NAME);
+
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+
+    let contents = match fs::read_to_string(manifest_path) {
+        Ok(contents) => contents,
+        Err(_) => return Err(storage_error("Could not read sync folder manifest")),
+    };
+
+    match serde_json::from_str::<PassphraseManifest>(&contents) {
+        Ok(manifest) => Ok(Some(manifest)),
+        Err(_) => Err(storage_error("Could not parse sync folder manifest")),
+    }
+}
+
+fn write_manifest(path: &Path, manifest: &PassphraseManifest) -> Result<(), ResponseError> {
+    match serde_json::to_string_pretty(manifest) {
+        Ok(contents) => write_atomically(&path.join(MANIFEST_FILE_NAME), &contents),
+        Err(_) => Err(storage_error("Could not serialise sync folder manifest")),
+    }
+}
+
+fn write_atomically(path: &Path, contents: &str) -> Result<(), ResponseError> {
+    let temporary_path = path.with_extension("tmp");
+
+    if fs::write(&temporary_path, contents).is_err() {
+        return Err(storage_error("Could not write to sync folder"));
+    }
+
+    match fs::rename(&temporary_path, path) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(storage_error("Could not write to sync folder")),
+    }
+}
+
+fn hash_contents(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn modified_timestamp(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn storage_error(message: &str) -> ResponseError {
+    ResponseError::new("500", message, ResponseErrorKind::Storage)
+}
+
+fn not_found_error() -> ResponseError {
+    ResponseError::new(
+        "404",
+        "Record not found in sync folder",
+        ResponseErrorKind::Storage,
+    )
+}
@@ -0,0 +1,353 @@
+use crate::database::{Account, AccountAlgorithm, SyncAccount, SyncAuthType, SyncType};
+use crate::sync_api::RecordUpsert;
+use crate::sync_backend::{record_id_for_key, record_ids_for_key, SyncBackend};
+use crate::sync_folder::FolderBackend;
+use std::fs;
+use std::path::PathBuf;
+
+const IDEMPOTENCY_KEY: &str = "0123456789abcdef0123456789abcdef";
+
+#[test]
+fn test_open_missing_folder() {
+    let account = folder_account(PathBuf::from("./bin/sync_folder/missing"), "passphrase");
+
+    let result = FolderBackend::open(account);
+
+    assert_eq!(true, result.is_err());
+    assert_eq!(
+        "Error 500 Sync folder does not exist",
+        result.err().unwrap().formatted_message()
+    );
+}
+
+#[test]
+fn test_open_creates_manifest() {
+    let path = reset_folder("creates_manifest");
+
+    let result = FolderBackend::open(folder_account(path.clone(), "passphrase"));
+
+    assert_eq!(true, result.is_ok());
+    assert_eq!(true, path.join("manifest.json").exists());
+    assert_eq!(true, path.join("records").is_dir());
+}
+
+#[test]
+fn test_open_wrong_passphrase() {
+    let path = reset_folder("wrong_passphrase");
+
+    FolderBackend::open(folder_account(path.clone(), "passphrase")).unwrap();
+
+    let result = FolderBackend::open(folder_account(path, "incorrect"));
+
+    assert_eq!(true, result.is_err());
+
+    let error = result.err().unwrap();
+
+    assert_eq!(true, error.is_authentication_error());
+    assert_eq!(
+        "Error 401 Incorrect sync folder passphrase",
+        error.formatted_message()
+    );
+}
+
+#[test]
+fn test_open_missing_passphrase() {
+    let path = reset_folder("missing_passphrase");
+
+    let result = FolderBackend::open(folder_account(path, ""));
+
+    assert_eq!(
+        "Error 400 Missing sync folder passphrase",
+        result.err().unwrap().formatted_message()
+    );
+}
+
+#[test]
+fn test_validate_does_not_write() {
+    let path = reset_folder("validate_read_only");
+
+    let result = FolderBackend::validate(&folder_account(path.clone(), "passphrase"));
+
+    assert_eq!(true, result.is_ok());
+    assert_eq!(0, fs::read_dir(&path).unwrap().count());
+}
+
+#[test]
+fn test_validate_wrong_passphrase() {
+    let path = reset_folder("validate_wrong_passphrase");
+
+    FolderBackend::open(folder_account(path.clone(), "passphrase")).unwrap();
+
+    let result = FolderBackend::validate(&folder_account(path, "incorrect"));
+
+    assert_eq!(
+        "Error 401 Incorrect sync folder passphrase",
+        result.err().unwrap().formatted_message()
+    );
+}
+
+#[tokio::test]
+async fn test_create_and_fetch_record() {
+    let path = reset_folder("create_and_fetch");
+    let mut backend = FolderBackend::open(folder_account(path.clone(), "passphrase")).unwrap();
+
+    let record = backend
+        .create_record(&test_account(None), IDEMPOTENCY_KEY)
+        .await
+        .unwrap();
+
+    assert_eq!(true, record.id > 0);
+    assert_eq!(64, record.sync_hash.len());
+
+    let manifest = backend.list_manifest().await.unwrap();
+
+    assert_eq!(1, manifest.len());
+    assert_eq!(record.id, manifest[0].id);
+    assert_eq!(record.updated_at, manifest[0].updated_at);
+
+    let stored = fs::read_to_string(path.join(format!("records/{}.record", record.id))).unwrap();
+
+    assert_eq!(false, stored.contains("JBSWY3DPEHPK3PXP"));
+
+    let mut other_device = FolderBackend::open(folder_account(path, "passphrase")).unwrap();
+    let fetched = other_device.fetch_record(&record.id).await.unwrap();
+
+    assert_eq!(record.id, fetched.id);
+    assert_eq!("Folder Test", fetched.name);
+    assert_eq!("JBSWY3DPEHPK3PXP", fetched.secret);
+    assert_eq!(Some("ff0000".to_string()), fetched.colour);
+    assert_eq!(30, fetched.totp_step);
+    assert_eq!(6, fetched.otp_digits);
+    assert_eq!(Some(AccountAlgorithm::SHA256), fetched.algorithm);
+    assert_eq!(record.sync_hash, fetched.sync_hash);
+}
+
+#[tokio::test]
+async fn test_update_record() {
+    let path = reset_folder("update");
+    let mut backend = FolderBackend::open(folder_account(path, "passphrase")).unwrap();
+
+    let record = backend
+        .create_record(&test_account(None), IDEMPOTENCY_KEY)
+        .await
+        .unwrap();
+
+    let mut updated_account = test_account(Some(record.id));
+    updated_account.name = "Folder Test Updated".to_string();
+
+    let updated = backend.update_record(&updated_account).await.unwrap();
+
+    assert_eq!(record.id, updated.id);
+    assert_ne!(record.sync_hash, updated.sync_hash);
+    assert_eq!(1, backend.list_manifest().await.unwrap().len());
+    assert_eq!(
+        "Folder Test Updated",
+        backend.fetch_record(&record.id).await.unwrap().name
+    );
+}
+
+#[tokio::test]
+async fn test_update_record_missing_external() {
+    let path = reset_folder("update_missing_external");
+    let mut backend = FolderBackend::open(folder_account(path, "passphrase")).unwrap();
+
+    let result = backend.update_record(&test_account(None)).await;
+
+    assert_eq!(true, result.is_err());
+    assert_eq!(
+        "Error 400 Missing External Id",
+        result.err().unwrap().formatted_message()
+    );
+}
+
+#[tokio::test]
+async fn test_delete_record() {
+    let path = reset_folder("delete");
+    let mut backend = FolderBackend::open(folder_account(path.clone(), "passphrase")).unwrap();
+
+    let record = backend
+        .create_record(&test_account(None), IDEMPOTENCY_KEY)
+        .await
+        .unwrap();
+
+    assert_eq!(true, backend.delete_record(&record.id).await.unwrap());
+    assert_eq!(0, backend.list_manifest().await.unwrap().len());
+    assert_eq!(
+        false,
+        path.join(format!("records/{}.record", record.id)).exists()
+    );
+
+    let result = backend.fetch_record(&record.id).await;
+
+    assert_eq!(true, result.is_err());
+    assert_eq!(
+        "Error 404 Record not found in sync folder",
+        result.err().unwrap().formatted_message()
+    );
+    assert_eq!(true, backend.delete_record(&record.id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_records_listed_without_rewriting_manifest() {
+    let path = reset_folder("listed_without_manifest");
+    let mut backend = FolderBackend::open(folder_account(path.clone(), "passphrase")).unwrap();
+    let mut other_device = FolderBackend::open(folder_account(path.clone(), "passphrase")).unwrap();
+    let manifest = fs::read_to_string(path.join("manifest.json")).unwrap();
+
+    let record = backend
+        .create_record(&test_account(None), IDEMPOTENCY_KEY)
+        .await
+        .unwrap();
+    let other_record = other_device
+        .create_record(&test_account(None), "fedcba9876543210fedcba9876543210")
+        .await
+        .unwrap();
+    fs::write(path.join("records/notes.txt"), "ignored").unwrap();
+
+    assert_eq!(
+        manifest,
+        fs::read_to_string(path.join("manifest.json")).unwrap()
+    );
+
+    let mut listed: Vec<i32> = backend
+        .list_manifest()
+        .await
+        .unwrap()
+        .iter()
+        .map(|item| item.id)
+        .collect();
+    let mut expected = vec![record.id, other_record.id];
+    listed.sort();
+    expected.sort();
+
+    assert_eq!(expected, listed);
+}
+
+#[tokio::test]
+async fn test_replayed_create_record() {
+    let path = reset_folder("replayed_create");
+    let mut backend = FolderBackend::open(folder_account(path, "passphrase")).unwrap();
+
+    let record = backend
+        .create_record(&test_account(None), IDEMPOTENCY_KEY)
+        .await
+        .unwrap();
+    let replayed = backend
+        .create_record(&test_account(None), IDEMPOTENCY_KEY)
+        .await
+        .unwrap();
+
+    assert_eq!(record.id, replayed.id);
+    assert_eq!(record.sync_hash, replayed.sync_hash);
+    assert_eq!(1, backend.list_manifest().await.unwrap().len());
+
+    backend
+        .create_record(&test_account(None), "fedcba9876543210fedcba9876543210")
+        .await
+        .unwrap();
+
+    assert_eq!(2, backend.list_manifest().await.unwrap().len());
+}
+
+#[tokio::test]
+async fn test_create_record_skips_colliding_record() {
+    let path = reset_folder("colliding_create");
+    let mut backend = FolderBackend::open(folder_account(path.clone(), "passphrase")).unwrap();
+    let records = path.join("records");
+    let colliding_id = record_id_for_key(IDEMPOTENCY_KEY);
+
+    let other = backend
+        .create_record(&test_account(None), "fedcba9876543210fedcba9876543210")
+        .await
+        .unwrap();
+    fs::copy(
+        records.join(format!("{}.record", other.id)),
+        records.join(format!("{}.record", colliding_id)),
+    )
+    .unwrap();
+
+    let record = backend
+        .create_record(&test_account(None), IDEMPOTENCY_KEY)
+        .await
+        .unwrap();
+    let replayed = backend
+        .create_record(&test_account(None), IDEMPOTENCY_KEY)
+        .await
+        .unwrap();
+
+    assert_eq!(record_ids_for_key(IDEMPOTENCY_KEY).nth(1), Some(record.id));
+    assert_eq!(record.id, replayed.id);
+    assert_eq!(3, backend.list_manifest().await.unwrap().len());
+}
+
+#[tokio::test]
+async fn test_batch_calls_fall_back_to_single_records() {
+    let path = reset_folder("batch_fallback");
+    let mut backend = FolderBackend::open(folder_account(path, "passphrase")).unwrap();
+
+    let created = backend
+        .upsert_records(&[RecordUpsert {
+            account: test_account(None),
+            idempotency_key: Some(IDEMPOTENCY_KEY.to_string()),
+        }])
+        .await;
+    let record_id = created[0].as_ref().unwrap().id;
+
+    let updated = backend
+        .upsert_records(&[RecordUpsert {
+            account: test_account(Some(record_id)),
+            idempotency_key: None,
+        }])
+        .await;
+
+    assert_eq!(record_id, updated[0].as_ref().unwrap().id);
+
+    let fetched = backend.fetch_records(&[record_id, 404]).await;
+
+    assert_eq!("Folder Test", fetched[0].as_ref().unwrap().name);
+    assert_eq!(true, fetched[1].is_err());
+
+    let deleted = backend.delete_records(&[record_id]).await;
+
+    assert_eq!(true, *deleted[0].as_ref().unwrap());
+    assert_eq!(0, backend.list_manifest().await.unwrap().len());
+}
+
+fn reset_folder(name: &str) -> PathBuf {
+    let path = PathBuf::from("./bin/sync_folder").join(name);
+
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+
+    path
+}
+
+fn folder_account(path: PathBuf, passphrase: &str) -> SyncAccount {
+    SyncAccount {
+        id: 1,
+        username: "".to_string(),
+        password: "".to_string(),
+        url: path.to_str().unwrap().to_string(),
+        token: None,
+        sync_type: SyncType::FOLDER,
+        passphrase: Some(passphrase.to_string()),
+        auth_type: SyncAuthType::BASIC,
+    }
+}
+
+fn test_account(external_id: Option<i32>) -> Account {
+    Account {
+        id: 1,
+        name: "Folder Test".to_string(),
+        secret: "JBSWY3DPEHPK3PXP".to_string(),
+        totp_step: 30,
+        otp_digits: 6,
+        colour: "ff0000".to_string(),
+        algorithm: Some(AccountAlgorithm::SHA256),
+        external_id,
+        external_last_updated: None,
+        external_hash: None,
+        deleted_at: None,
+        sync_account_id: None,
+    }
+}
@@ -1,11 +1,33 @@
-use crate::database::{Account, SyncAccount, SyncLog, SyncLogType};
-use crate::state::ServiceAccess;
-use crate::sync_api::{
-    get_record, get_single_record, remove_record, update_record, Record, SyncManifest,
+use crate::database::{
+    Account, OutboxEntry, OutboxOperation, RevisionOrigin, SyncAccount, SyncFailure, SyncLog, SyncLogContext,
+    SyncLogType, SyncOperation,
 };
+use crate::encryption::DataKey;
+use crate::state::ServiceAccess;
+use crate::sync_api::{RecordUpsert, ResponseError, SyncManifest, VerboseRecord};
+use crate::sync_backend::{SyncBackend, SyncTarget};
+use crate::sync_coordinator::SyncCancellation;
 use crate::{database, encryption, sync_api};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::Serialize;
 use std::cmp::PartialEq;
-use tauri::AppHandle;
+use std::collections::HashMap;
+use std::future::Future;
+
+const REMOTE_DELETION_THRESHOLD_PERCENT: usize = 50;
+const REMOTE_DELETION_THRESHOLD_MINIMUM: usize = 2;
+const SYNC_LOG_RETENTION_SECONDS: u64 = 30 * 24 * 60 * 60;
+const SYNC_LOG_MAXIMUM_ENTRIES: usize = 1000;
+const SESSION_ID_LENGTH: usize = 8;
+const SYNC_CANCELLED_MESSAGE: &str = "Sync cancelled";
+const MAXIMUM_ITEM_ATTEMPTS: u32 = 5;
+const HELD_ITEM_RETRY_SECONDS: u64 = 24 * 60 * 60;
+const DEFAULT_ACCOUNT_COLOUR: &str = "5c636a";
+
+pub const SYNC_STARTED_EVENT: &str = "sync-started";
+pub const SYNC_PROGRESS_EVENT: &str = "sync-progress";
+pub const SYNC_COMPLETED_EVENT: &str = "sync-completed";
 
 #[derive(PartialEq, Eq, Debug)]
 enum SyncStatus {
@@ -15,112 +37,852 @@ enum SyncStatus {
     RemoteMissing,
 }
 
-pub async fn sync_all_accounts(app_handle: AppHandle, sync_account: SyncAccount) {
-    let decrypted_sync_account = encryption::decrypt_sync_account(&encryption::get_key_directory(&app_handle), sync_account);
-    let authenticated_account = match sync_api::authenticate_account(decrypted_sync_account.clone()).await {
-        Ok(account) => account,
-        Err(err) => {
-            handle_error_log(&app_handle, err.formatted_message());
-            return;
+#[derive(Serialize, Clone, Default)]
+pub struct SyncReport {
+    pub session_id: Option<String>,
+    pub running: bool,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub success: Option<bool>,
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub failed: usize,
+    pub cancelled: bool,
+    pub error: Option<String>,
+    pub failures: Vec<SyncItemFailure>,
+    pub held_deletions: Vec<HeldDeletion>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HeldDeletion {
+    pub sync_account_id: i32,
+    pub account_id: i32,
+    pub external_id: Option<i32>,
+    pub name: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SyncItemFailure {
+    pub sync_account_id: i32,
+    pub account_id: Option<i32>,
+    pub external_id: Option<i32>,
+    pub name: Option<String>,
+    pub operation: SyncOperation,
+    pub error: String,
+    pub attempts: u32,
+    pub held: bool,
+}
+
+// Either a local account, or a remote record with no local copy yet
+struct SyncItem {
+    account_id: Option<i32>,
+    external_id: Option<i32>,
+    name: Option<String>,
+}
+
+impl SyncItem {
+    fn local(account: &Account) -> SyncItem {
+        SyncItem {
+            account_id: Some(account.id),
+            external_id: account.external_id,
+            name: Some(account.name.clone()),
+        }
+    }
+
+    fn remote(external_id: i32) -> SyncItem {
+        SyncItem {
+            account_id: None,
+            external_id: Some(external_id),
+            name: None,
         }
+    }
+
+    // Local accounts are tracked by id alone, a failure survives the account gaining an external id
+    fn failure_key(&self) -> (Option<i32>, Option<i32>) {
+        match self.account_id {
+            Some(account_id) => (Some(account_id), None),
+            None => (None, self.external_id),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct SyncProgress {
+    pub session_id: String,
+    pub sync_account_id: i32,
+    pub completed: usize,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub failed: usize,
+    pub error: Option<String>,
+}
+
+struct SyncSession {
+    id: String,
+    sync_account_id: Option<i32>,
+    created: usize,
+    updated: usize,
+    deleted: usize,
+    failed: usize,
+    failures: Vec<SyncItemFailure>,
+    held_deletions: Vec<HeldDeletion>,
+    cancellation: SyncCancellation,
+}
+
+impl SyncSession {
+    fn new(
+        id: &str,
+        sync_account_id: Option<i32>,
+        cancellation: SyncCancellation,
+    ) -> SyncSession {
+        SyncSession {
+            id: id.to_string(),
+            sync_account_id,
+            created: 0,
+            updated: 0,
+            deleted: 0,
+            failed: 0,
+            failures: vec![],
+            held_deletions: vec![],
+            cancellation,
+        }
+    }
+
+    fn phase_failure(&mut self, operation: SyncOperation, error: String) {
+        self.failures.push(SyncItemFailure {
+            sync_account_id: self.sync_account_id.unwrap_or_default(),
+            account_id: None,
+            external_id: None,
+            name: None,
+            operation,
+            error,
+            attempts: 0,
+            held: false,
+        });
+    }
+
+    fn check_cancelled(&self) -> Result<(), String> {
+        match self.cancellation.is_cancelled() {
+            true => Err(SYNC_CANCELLED_MESSAGE.to_string()),
+            false => Ok(()),
+        }
+    }
+
+    async fn cancellable<T>(&self, request: impl Future<Output = T>) -> Result<T, String> {
+        tokio::select! {
+            result = request => Ok(result),
+            _ = self.cancellation.cancelled() => Err(SYNC_CANCELLED_MESSAGE.to_string()),
+        }
+    }
+
+    fn context(
+        &self,
+        operation: SyncOperation,
+        account_id: Option<i32>,
+        external_id: Option<i32>,
+    ) -> SyncLogContext {
+        SyncLogContext {
+            session_id: Some(self.id.clone()),
+            sync_account_id: self.sync_account_id,
+            account_id,
+            external_id,
+            operation: Some(operation),
+        }
+    }
+
+    fn account_context(&self, operation: SyncOperation, account: &Account) -> SyncLogContext {
+        self.context(operation, Some(account.id), account.external_id)
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "Sync complete: {} created, {} updated, {} deleted",
+            self.created, self.updated, self.deleted
+        )
+    }
+}
+
+pub async fn sync_all_accounts(app_handle: &impl ServiceAccess, cancellation: SyncCancellation) {
+    let sync_accounts = app_handle.db(database::get_sync_accounts).unwrap();
+    let session_id = generate_session_id();
+    let total = sync_accounts.len();
+    let mut report = SyncReport {
+        session_id: Some(session_id.clone()),
+        running: true,
+        started_at: Some(app_handle.now()),
+        ..Default::default()
     };
+    let mut errors = Vec::new();
 
-    let soft_deleted_accounts = app_handle.db(database::get_soft_deleted_accounts).unwrap();
+    app_handle.set_sync_report(report.clone());
+    app_handle.emit_event(SYNC_STARTED_EVENT, report.clone());
 
-    for account in soft_deleted_accounts {
-        match remove_local_account(&app_handle, &account, &authenticated_account).await {
-            Ok(_) => continue,
-            Err(err) => {
-                handle_error_log(&app_handle, err);
-                continue;
+    purge_expired_trash(app_handle);
+    prune_sync_logs(app_handle);
+
+    for (index, sync_account) in sync_accounts.into_iter().enumerate() {
+        if cancellation.is_cancelled() {
+            break;
+        }
+
+        let sync_account_id = sync_account.id;
+        let mut session =
+            SyncSession::new(&session_id, Some(sync_account_id), cancellation.clone());
+        let result = sync_target(app_handle, sync_account, &mut session).await;
+
+        report.created += session.created;
+        report.updated += session.updated;
+        report.deleted += session.deleted;
+        report.failed += session.failed;
+        report.failures.append(&mut session.failures);
+        report.held_deletions.append(&mut session.held_deletions);
+
+        if let Err(err) = &result {
+            if !cancellation.is_cancelled() {
+                errors.push(err.clone());
             }
         }
+
+        app_handle.emit_event(
+            SYNC_PROGRESS_EVENT,
+            SyncProgress {
+                session_id: session_id.clone(),
+                sync_account_id,
+                completed: index + 1,
+                total,
+                created: session.created,
+                updated: session.updated,
+                deleted: session.deleted,
+                failed: session.failed,
+                error: result.err(),
+            },
+        );
     }
 
-    let accounts_without_external = app_handle.db(database::get_accounts_without_external_id).unwrap();
+    if cancellation.is_cancelled() {
+        handle_warn_log(
+            app_handle,
+            SyncLogContext {
+                session_id: Some(session_id.clone()),
+                operation: Some(SyncOperation::SUMMARY),
+                ..Default::default()
+            },
+            SYNC_CANCELLED_MESSAGE.to_string(),
+        );
+        errors.push(SYNC_CANCELLED_MESSAGE.to_string());
+    }
 
-    for account in accounts_without_external {
-        if account.external_id.is_none() {
-            match create_new_local_account(&app_handle, &account, &authenticated_account).await {
-                Ok(_) => continue,
-                Err(err) => {
-                    handle_error_log(&app_handle, err);
-                    return;
-                }
-            };
+    report.running = false;
+    report.cancelled = cancellation.is_cancelled();
+    report.finished_at = Some(app_handle.now());
+    report.success = Some(errors.is_empty() && report.failed == 0);
+    report.error = match errors.is_empty() {
+        true => None,
+        false => Some(errors.join("; ")),
+    };
+
+    app_handle.set_sync_report(report.clone());
+    app_handle.emit_event(SYNC_COMPLETED_EVENT, report);
+}
+
+async fn sync_target(
+    app_handle: &impl ServiceAccess,
+    sync_account: SyncAccount,
+    session: &mut SyncSession,
+) -> Result<(), String> {
+    let client = app_handle.sync_client();
+    let decrypted_sync_account = match app_handle.data_key().and_then(|key| encryption::decrypt_sync_account(&key, sync_account)) {
+        Ok(account) => account,
+        Err(err) => {
+            session.phase_failure(SyncOperation::CONNECT, err.clone());
+            handle_error_log(app_handle, session.context(SyncOperation::CONNECT, None, None), err.clone());
+            return Err(err);
+        }
+    };
+    let mut backend = match session.cancellable(SyncTarget::connect(client, decrypted_sync_account.clone())).await? {
+        Ok(backend) => backend,
+        Err(err) => {
+            session.phase_failure(SyncOperation::CONNECT, err.formatted_message());
+            handle_error_log(app_handle, session.context(SyncOperation::CONNECT, None, None), err.formatted_message());
+            return Err(err.formatted_message());
         }
+    };
+
+    let result = sync_with_remote(app_handle, &mut backend, session).await;
+
+    if result.is_ok() {
+        handle_info_log(app_handle, session.context(SyncOperation::SUMMARY, None, None), session.summary());
     }
 
-    let manifest_result = sync_api::get_manifest(&authenticated_account).await;
+    if let Err(err) = cache_token(app_handle, &decrypted_sync_account, backend.sync_account()) {
+        handle_error_log(app_handle, session.context(SyncOperation::CONNECT, None, None), err);
+    }
 
-    let manifest = match manifest_result {
-        Ok(manifest) => manifest,
+    result
+}
+
+async fn sync_with_remote<B: SyncBackend>(
+    app_handle: &impl ServiceAccess,
+    backend: &mut B,
+    session: &mut SyncSession,
+) -> Result<(), String> {
+    let sync_account_id = backend.sync_account().id;
+
+    // Phase 1: push local changes, each account isolated from the others
+    drain_outbox(app_handle, backend, session).await?;
+
+    // Phase 2: without a manifest there is nothing safe to pull or trash
+    let since = app_handle
+        .db(|db| database::get_manifest_cursor(&sync_account_id, db))
+        .unwrap();
+
+    let changes = match session.cancellable(backend.list_manifest_changes(since.as_deref())).await? {
+        Ok(changes) => changes,
         Err(err) => {
-            handle_error_log(&app_handle, err.formatted_message());
-            return;
+            session.phase_failure(SyncOperation::MANIFEST, err.formatted_message());
+            handle_error_log(app_handle, session.context(SyncOperation::MANIFEST, None, None), err.formatted_message());
+            return Err(err.formatted_message());
         },
     };
+    let manifest_ids: Vec<i32> = changes.records.iter().map(|item| item.id).collect();
+    let failures_before_pull = session.failures.len();
 
-    let mut manifest_ids = Vec::new();
+    // Phase 3: pull remote changes
+    pull_remote_changes(app_handle, backend, changes.records, session).await?;
 
-    for manifest_item in manifest {
-        let potential_account = app_handle
-            .db(|db| database::get_account_by_external_id(&manifest_item.id, db))
+    // Phase 4: trash accounts removed remotely
+    let removed_accounts = match changes.complete {
+        true => {
+            app_handle
+                .db(|db| database::clear_stale_sync_failures(manifest_ids.clone(), &sync_account_id, db))
+                .unwrap();
+
+            app_handle
+                .db(|db| database::get_accounts_missing_from_manifest(manifest_ids, &sync_account_id, db))
+                .unwrap()
+        }
+        false => app_handle
+            .db(|db| database::get_accounts_by_external_ids(changes.removed, &sync_account_id, db))
+            .unwrap(),
+    };
+
+    session.check_cancelled()?;
+    let deletions_held = trash_remote_deletions(app_handle, removed_accounts, &sync_account_id, session);
+
+    // Failed items and held deletions have to appear in the next delta, so only move the cursor past a clean pull
+    if session.failures.len() == failures_before_pull && !deletions_held {
+        app_handle
+            .db(|db| database::save_manifest_cursor(&sync_account_id, changes.cursor, db))
             .unwrap();
+    }
+
+    Ok(())
+}
+
+async fn pull_remote_changes<B: SyncBackend>(
+    app_handle: &impl ServiceAccess,
+    backend: &mut B,
+    manifest: Vec<SyncManifest>,
+    session: &mut SyncSession,
+) -> Result<(), String> {
+    let sync_account_id = backend.sync_account().id;
+    let manifest_ids: Vec<i32> = manifest.iter().map(|item| item.id).collect();
 
-        //Log manifest id to check what items need removing
-        manifest_ids.push(manifest_item.id);
-
-        if potential_account.is_none() {
-            //Get external and create
-            match copy_account_from_remote(&app_handle, &manifest_item, &authenticated_account)
-                .await
-            {
-                Ok(_) => continue,
-                Err(err) => {
-                    handle_error_log(&app_handle, err);
-                    continue;
+    // One lookup for the whole manifest rather than one per record
+    let mut local_accounts: HashMap<i32, Account> = app_handle
+        .db(|db| database::get_accounts_by_external_ids(manifest_ids, &sync_account_id, db))
+        .unwrap()
+        .into_iter()
+        .filter_map(|account| account.external_id.map(|external_id| (external_id, account)))
+        .collect();
+
+    let mut pulls: Vec<(i32, Option<Account>)> = Vec::new();
+    let mut pushes: Vec<Account> = Vec::new();
+
+    for manifest_item in manifest {
+        let account = match local_accounts.remove(&manifest_item.id) {
+            Some(account) => account,
+            None => {
+                if !is_held(app_handle, &SyncItem::remote(manifest_item.id), session) {
+                    pulls.push((manifest_item.id, None));
                 }
-            };
-        }
+                continue;
+            }
+        };
 
-        let account = potential_account.unwrap();
         let sync_status = get_sync_status(&account, &manifest_item);
 
-        if sync_status == SyncStatus::LocalOutOfDate {
-            match update_existing_account(
-                &app_handle,
-                &account,
-                &manifest_item,
-                &authenticated_account,
-            )
-            .await
-            {
-                Ok(_) => continue,
-                Err(err) => {
-                    handle_error_log(&app_handle, err);
-                    continue;
-                }
+        if sync_status == SyncStatus::UpToDate || sync_status == SyncStatus::RemoteMissing {
+            continue;
+        }
+
+        if is_held(app_handle, &SyncItem::local(&account), session) {
+            continue;
+        }
+
+        match sync_status {
+            SyncStatus::LocalOutOfDate => pulls.push((manifest_item.id, Some(account))),
+            _ => pushes.push(account),
+        }
+    }
+
+    session.check_cancelled()?;
+
+    let pull_ids: Vec<i32> = pulls.iter().map(|(id, _)| *id).collect();
+    let records = session.cancellable(backend.fetch_records(&pull_ids)).await?;
+
+    for ((external_id, account), record) in pulls.into_iter().zip(records) {
+        let item = match &account {
+            Some(account) => SyncItem::local(account),
+            None => SyncItem::remote(external_id),
+        };
+
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                record_item_failure(app_handle, &item, SyncOperation::PULL, err.formatted_message(), session);
+                continue;
+            }
+        };
+
+        let result = match account {
+            Some(account) => update_existing_account(app_handle, &account, &record).map(|_| session.updated += 1),
+            None => copy_account_from_remote(app_handle, &record, &sync_account_id).map(|_| session.created += 1),
+        };
+
+        match result {
+            Ok(_) => record_item_success(app_handle, &item, session),
+            Err(err) => record_item_failure(app_handle, &item, SyncOperation::PULL, err, session),
+        }
+    }
+
+    session.check_cancelled()?;
+
+    let key = phase_key(app_handle, SyncOperation::UPDATE, session)?;
+    let mut upserts: Vec<RecordUpsert> = Vec::new();
+
+    pushes.retain(|account| match encryption::decrypt_account(&key, account) {
+        Ok(decrypted) => {
+            upserts.push(RecordUpsert {
+                account: decrypted,
+                idempotency_key: None,
+            });
+            true
+        }
+        Err(err) => {
+            record_item_failure(app_handle, &SyncItem::local(account), SyncOperation::UPDATE, err, session);
+            false
+        }
+    });
+
+    let results = session.cancellable(backend.upsert_records(&upserts)).await?;
+
+    for (account, result) in pushes.into_iter().zip(results) {
+        let item = SyncItem::local(&account);
+
+        match result {
+            Ok(record) => {
+                app_handle
+                    .db(|db| database::set_remote_account(db, &account, &record))
+                    .unwrap();
+                session.updated += 1;
+                record_item_success(app_handle, &item, session);
             }
+            Err(err) => record_item_failure(app_handle, &item, SyncOperation::UPDATE, err.formatted_message(), session),
+        }
+    }
+
+    Ok(())
+}
+
+async fn drain_outbox<B: SyncBackend>(
+    app_handle: &impl ServiceAccess,
+    backend: &mut B,
+    session: &mut SyncSession,
+) -> Result<(), String> {
+    let sync_account_id = backend.sync_account().id;
+
+    queue_pending_changes(app_handle, &sync_account_id);
+
+    let entries = app_handle
+        .db(|db| database::get_outbox_entries(&sync_account_id, db))
+        .unwrap();
+
+    let mut deletions: Vec<(OutboxEntry, Account)> = Vec::new();
+    let mut upserts: Vec<(OutboxEntry, Account)> = Vec::new();
+
+    for entry in entries {
+        let account = app_handle
+            .db(|db| database::get_account_details_by_id(entry.account_id as u32, db))
+            .unwrap();
+
+        // Held entries stay queued and are picked up again once their retry is due
+        if is_held(app_handle, &SyncItem::local(&account), session) {
+            continue;
         }
 
-        if sync_status == SyncStatus::RemoteOutOfDate {
-            match update_existing_remote_account(&app_handle, &account, &authenticated_account)
-                .await
-            {
-                Ok(_) => continue,
-                Err(err) => {
-                    handle_error_log(&app_handle, err);
-                    continue;
+        match entry.operation {
+            OutboxOperation::DELETE => deletions.push((entry, account)),
+            _ => upserts.push((entry, account)),
+        }
+    }
+
+    session.check_cancelled()?;
+
+    let remote_ids: Vec<i32> = deletions
+        .iter()
+        .filter_map(|(_, account)| account.external_id)
+        .collect();
+    let mut delete_results = session.cancellable(backend.delete_records(&remote_ids)).await?.into_iter();
+
+    for (entry, account) in deletions {
+        let result = match account.external_id {
+            Some(_) => delete_results.next().unwrap_or_else(|| Err(sync_api::handle_invalid_response_body())),
+            None => Ok(false),
+        };
+
+        let result = remove_local_account(app_handle, &account, result, session).map(|_| account.external_id.is_some());
+        finish_outbox_entry(app_handle, &entry, &account, SyncOperation::DELETE, result, session);
+    }
+
+    session.check_cancelled()?;
+
+    let key = phase_key(app_handle, SyncOperation::UPDATE, session)?;
+    let mut requests: Vec<RecordUpsert> = Vec::new();
+
+    upserts.retain(|(entry, account)| match encryption::decrypt_account(&key, account) {
+        Ok(decrypted) => {
+            requests.push(RecordUpsert {
+                account: decrypted,
+                idempotency_key: Some(entry.idempotency_key.clone()),
+            });
+            true
+        }
+        Err(err) => {
+            let operation = match account.external_id {
+                Some(_) => SyncOperation::UPDATE,
+                None => SyncOperation::CREATE,
+            };
+
+            finish_outbox_entry(app_handle, entry, account, operation, Err(err), session);
+            false
+        }
+    });
+
+    let results = session.cancellable(backend.upsert_records(&requests)).await?;
+
+    for ((entry, account), result) in upserts.into_iter().zip(results) {
+        let operation = match account.external_id {
+            Some(_) => SyncOperation::UPDATE,
+            None => SyncOperation::CREATE,
+        };
+
+        let result = match result {
+            Ok(record) => {
+                app_handle
+                    .db(|db| database::set_remote_account(db, &account, &record))
+                    .unwrap();
+                Ok(true)
+            }
+            Err(err) => Err(err.formatted_message()),
+        };
+
+        finish_outbox_entry(app_handle, &entry, &account, operation, result, session);
+    }
+
+    Ok(())
+}
+
+fn finish_outbox_entry(
+    app_handle: &impl ServiceAccess,
+    entry: &OutboxEntry,
+    account: &Account,
+    operation: SyncOperation,
+    result: Result<bool, String>,
+    session: &mut SyncSession,
+) {
+    let item = SyncItem::local(account);
+
+    match result {
+        Ok(changed) => {
+            let sent_payload_hash = database::account_payload_hash(account);
+
+            app_handle
+                .db(|db| database::complete_outbox_entry(entry, &sent_payload_hash, db))
+                .unwrap();
+            record_item_success(app_handle, &item, session);
+
+            if changed {
+                match operation {
+                    SyncOperation::DELETE => session.deleted += 1,
+                    SyncOperation::CREATE => session.created += 1,
+                    _ => session.updated += 1,
                 }
             }
         }
+        Err(err) => {
+            app_handle
+                .db(|db| database::fail_outbox_entry(&entry.id, &err, db))
+                .unwrap();
+            record_item_failure(app_handle, &item, operation, err, session);
+        }
+    }
+}
+
+// Items that keep failing are held back until the hold expires
+fn is_held(app_handle: &impl ServiceAccess, item: &SyncItem, session: &mut SyncSession) -> bool {
+    let sync_account_id = session.sync_account_id.unwrap_or_default();
+    let (account_id, external_id) = item.failure_key();
+    let failure = app_handle
+        .db(|db| database::get_sync_failure(&sync_account_id, account_id, external_id, db))
+        .unwrap();
+
+    let failure = match failure {
+        Some(failure) if is_held_failure(&failure, app_handle.now()) => failure,
+        _ => return false,
+    };
+
+    session.failures.push(SyncItemFailure {
+        sync_account_id,
+        account_id: item.account_id,
+        external_id: item.external_id,
+        name: item.name.clone(),
+        operation: failure.operation,
+        error: failure.error,
+        attempts: failure.attempts,
+        held: true,
+    });
+
+    true
+}
+
+fn is_held_failure(failure: &SyncFailure, now: u64) -> bool {
+    failure.attempts >= MAXIMUM_ITEM_ATTEMPTS && now < failure.last_attempt_at + HELD_ITEM_RETRY_SECONDS
+}
+
+fn phase_key(
+    app_handle: &impl ServiceAccess,
+    operation: SyncOperation,
+    session: &mut SyncSession,
+) -> Result<DataKey, String> {
+    match app_handle.data_key() {
+        Ok(key) => Ok(key),
+        Err(err) => {
+            session.phase_failure(operation.clone(), err.clone());
+            handle_error_log(app_handle, session.context(operation, None, None), err.clone());
+            Err(err)
+        }
+    }
+}
+
+fn record_item_failure(
+    app_handle: &impl ServiceAccess,
+    item: &SyncItem,
+    operation: SyncOperation,
+    error: String,
+    session: &mut SyncSession,
+) {
+    let sync_account_id = session.sync_account_id.unwrap_or_default();
+    let (account_id, external_id) = item.failure_key();
+    let now = app_handle.now();
+    let failure = app_handle
+        .db(|db| database::record_sync_failure(&sync_account_id, account_id, external_id, operation.clone(), &error, now, db))
+        .unwrap();
+
+    session.failed += 1;
+    handle_error_log(app_handle, session.context(operation.clone(), item.account_id, item.external_id), error.clone());
+
+    session.failures.push(SyncItemFailure {
+        sync_account_id,
+        account_id: item.account_id,
+        external_id: item.external_id,
+        name: item.name.clone(),
+        operation,
+        error,
+        attempts: failure.attempts,
+        held: false,
+    });
+}
+
+fn record_item_success(app_handle: &impl ServiceAccess, item: &SyncItem, session: &SyncSession) {
+    let sync_account_id = session.sync_account_id.unwrap_or_default();
+    let (account_id, external_id) = item.failure_key();
+
+    app_handle
+        .db(|db| database::clear_sync_failure(&sync_account_id, account_id, external_id, db))
+        .unwrap();
+}
+
+// Picks up changes no local command queued, such as accounts moved between targets
+fn queue_pending_changes(app_handle: &impl ServiceAccess, sync_account_id: &i32) {
+    let now = app_handle.now();
+    let soft_deleted_accounts = app_handle
+        .db(|db| database::get_soft_deleted_accounts(sync_account_id, db))
+        .unwrap();
+
+    for account in soft_deleted_accounts {
+        if account.external_id.is_none() {
+            continue;
+        }
+
+        app_handle
+            .db(|db| database::queue_outbox_entry(&account.id, OutboxOperation::DELETE, now, db))
+            .unwrap();
+    }
+
+    let accounts_without_external = app_handle
+        .db(|db| database::get_accounts_without_external_id(sync_account_id, db))
+        .unwrap();
+
+    for account in accounts_without_external {
+        app_handle
+            .db(|db| database::queue_outbox_entry(&account.id, OutboxOperation::CREATE, now, db))
+            .unwrap();
+    }
+}
+
+fn trash_remote_deletions(
+    app_handle: &impl ServiceAccess,
+    missing_accounts: Vec<Account>,
+    sync_account_id: &i32,
+    session: &mut SyncSession,
+) -> bool {
+    if missing_accounts.is_empty() {
+        return false;
+    }
+
+    let synced_accounts = app_handle
+        .db(|db| database::count_synced_accounts(sync_account_id, db))
+        .unwrap();
+
+    if exceeds_deletion_threshold(missing_accounts.len(), synced_accounts) {
+        handle_warn_log(
+            app_handle,
+            session.context(SyncOperation::TRASH, None, None),
+            format!(
+                "Remote removed {} of {} accounts, holding deletions until they are confirmed",
+                missing_accounts.len(),
+                synced_accounts
+            ),
+        );
+
+        session.held_deletions = missing_accounts
+            .into_iter()
+            .map(|account| HeldDeletion {
+                sync_account_id: *sync_account_id,
+                account_id: account.id,
+                external_id: account.external_id,
+                name: account.name,
+            })
+            .collect();
+
+        return true;
+    }
+
+    for account in missing_accounts {
+        trash_removed_account(app_handle, &account, session.account_context(SyncOperation::TRASH, &account));
+        session.deleted += 1;
+    }
+
+    false
+}
+
+pub fn confirm_held_deletions(app_handle: &impl ServiceAccess, sync_account_id: i32) -> Result<usize, String> {
+    let mut report = app_handle.sync_report();
+    let (confirmed, remaining): (Vec<HeldDeletion>, Vec<HeldDeletion>) = report
+        .held_deletions
+        .into_iter()
+        .partition(|held| held.sync_account_id == sync_account_id);
+    let mut trashed = 0;
+
+    for held in confirmed {
+        let account = app_handle
+            .db(|db| database::get_account_details_by_id(held.account_id as u32, db))
+            .map_err(|err| err.to_string())?;
+
+        // Skip anything restored, moved or re-linked since the deletion was held
+        if account.deleted_at.is_some()
+            || account.sync_account_id != Some(sync_account_id)
+            || account.external_id != held.external_id
+        {
+            continue;
+        }
+
+        trash_removed_account(
+            app_handle,
+            &account,
+            SyncLogContext {
+                sync_account_id: Some(sync_account_id),
+                account_id: Some(account.id),
+                external_id: account.external_id,
+                operation: Some(SyncOperation::TRASH),
+                ..Default::default()
+            },
+        );
+        trashed += 1;
     }
 
-    //Remove accounts not in manifest list
+    report.held_deletions = remaining;
+    report.deleted += trashed;
+    app_handle.set_sync_report(report);
+
+    Ok(trashed)
+}
+
+fn trash_removed_account(app_handle: &impl ServiceAccess, account: &Account, context: SyncLogContext) {
+    let now = app_handle.now();
+
     app_handle
-        .db(|db| database::delete_accounts_without_external_ids(manifest_ids, db))
+        .db(|db| database::trash_account(account, now, db))
         .unwrap();
+
+    handle_info_log(
+        app_handle,
+        context,
+        format!("{} was removed remotely and moved to the trash", account.name),
+    );
+}
+
+fn exceeds_deletion_threshold(missing_accounts: usize, synced_accounts: usize) -> bool {
+    if missing_accounts < REMOTE_DELETION_THRESHOLD_MINIMUM {
+        return false;
+    }
+
+    missing_accounts * 100 > synced_accounts * REMOTE_DELETION_THRESHOLD_PERCENT
+}
+
+fn purge_expired_trash(app_handle: &impl ServiceAccess) {
+    let now = app_handle.now();
+    let cutoff = app_handle.db(|db| database::get_trash_cutoff(now, db)).unwrap();
+
+    app_handle
+        .db(|db| database::purge_trashed_accounts(cutoff, db))
+        .unwrap();
+}
+
+fn prune_sync_logs(app_handle: &impl ServiceAccess) {
+    let now = app_handle.now();
+
+    app_handle
+        .db(|db| {
+            database::prune_sync_logs(
+                now.saturating_sub(SYNC_LOG_RETENTION_SECONDS),
+                SYNC_LOG_MAXIMUM_ENTRIES,
+                db,
+            )
+        })
+        .unwrap();
+}
+
+fn generate_session_id() -> String {
+    let mut bytes = [0u8; SESSION_ID_LENGTH];
+
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
 }
 
 fn get_sync_status(account: &Account, sync_manifest: &SyncManifest) -> SyncStatus {
@@ -141,53 +903,36 @@ fn get_sync_status(account: &Account, sync_manifest: &SyncManifest) -> SyncStatu
     SyncStatus::UpToDate
 }
 
-async fn remove_local_account(
-    app_handle: &AppHandle,
+fn remove_local_account(
+    app_handle: &impl ServiceAccess,
     account: &Account,
-    authenticated_account: &SyncAccount,
+    remote_result: Result<bool, ResponseError>,
+    session: &SyncSession,
 ) -> Result<bool, String> {
-    if account.external_id.is_some() {
-        match remove_record(&account.external_id.unwrap(), &authenticated_account).await {
-            Err(err) => return Err(err.formatted_message()),
-            _ => {}
+    match remote_result {
+        // Already removed remotely, nothing left to delete
+        Err(err) if err.status().starts_with("404") => {
+            handle_warn_log(
+                app_handle,
+                session.account_context(SyncOperation::DELETE, account),
+                format!("{} was already removed remotely", account.name),
+            );
         }
+        Err(err) => return Err(err.formatted_message()),
+        _ => {}
     }
 
+    // The local copy stays in the trash until it is restored or expires
     Ok(app_handle
-        .db(|db| database::delete_account(account, db))
+        .db(|db| database::clear_remote_account(account, db))
         .unwrap())
 }
 
-async fn create_new_local_account(
-    app_handle: &AppHandle,
-    account: &Account,
-    authenticated_account: &SyncAccount,
-) -> Result<Record, String> {
-    let full_account_details = app_handle
-        .db(|db| database::get_account_details_by_id(account.id as u32, db))
-        .unwrap();
-    let decrypted_account = encryption::decrypt_account(&encryption::get_key_directory(app_handle), &full_account_details);
-
-    let record = match get_record(&decrypted_account, authenticated_account).await {
-        Ok(record) => record,
-        Err(err) => return Err(err.formatted_message()),
-    };
-    app_handle
-        .db(|db| database::set_remote_account(db, &decrypted_account, &record))
-        .unwrap();
-
-    Ok(record)
-}
-
-async fn copy_account_from_remote(
-    app_handle: &AppHandle,
-    manifest_item: &SyncManifest,
-    sync_account: &SyncAccount,
+fn copy_account_from_remote(
+    app_handle: &impl ServiceAccess,
+    new_account_record: &VerboseRecord,
+    sync_account_id: &i32,
 ) -> Result<Account, String> {
-    let new_account_record = match get_single_record(&manifest_item.id, sync_account).await {
-        Ok(record) => record,
-        Err(response_error) => return Err(response_error.formatted_message()),
-    };
     let mut new_account_algo = "".to_string();
 
     if new_account_record.algorithm.is_some() {
@@ -198,15 +943,17 @@ async fn copy_account_from_remote(
             .algorithm_to_string();
     }
 
+    let secret = app_handle.data_key()?.encrypt(&new_account_record.secret)?;
     let new_account = app_handle
         .db(|db| {
             database::create_new_account(
                 &new_account_record.name,
-                &encryption::encrypt(&encryption::get_key_directory(app_handle), &new_account_record.secret).unwrap(),
+                &secret,
                 &new_account_record.otp_digits,
                 &new_account_record.totp_step,
-                &new_account_record.colour,
+                new_account_record.colour.as_deref().unwrap_or(DEFAULT_ACCOUNT_COLOUR),
                 &new_account_algo,
+                Some(*sync_account_id),
                 db,
             )
         })
@@ -218,16 +965,11 @@ async fn copy_account_from_remote(
     Ok(new_account)
 }
 
-async fn update_existing_account(
-    app_handle: &AppHandle,
+fn update_existing_account(
+    app_handle: &impl ServiceAccess,
     account: &Account,
-    manifest_item: &SyncManifest,
-    sync_account: &SyncAccount,
+    existing_record: &VerboseRecord,
 ) -> Result<Account, String> {
-    let existing_record = match get_single_record(&manifest_item.id, sync_account).await {
-        Ok(record) => record,
-        Err(response_error) => return Err(response_error.formatted_message()),
-    };
     let mut new_account_algo = "".to_string();
 
     if existing_record.algorithm.is_some() {
@@ -238,16 +980,21 @@ async fn update_existing_account(
             .algorithm_to_string();
     }
 
+    let secret = app_handle.data_key()?.encrypt(&existing_record.secret)?;
+    let now = app_handle.now();
     let updated_account = app_handle
         .db(|db| {
             database::update_existing_account(
                 &account.id,
                 &existing_record.name,
-                &encryption::encrypt(&encryption::get_key_directory(app_handle), &existing_record.secret).unwrap(),
+                &secret,
                 existing_record.otp_digits,
                 existing_record.totp_step,
-                "5c636a", // While Server has not been updated
+                existing_record.colour.as_deref().unwrap_or(&account.colour),
                 &new_account_algo,
+                RevisionOrigin::SYNC,
+                None,
+                now,
                 db,
             )
         })
@@ -260,35 +1007,65 @@ async fn update_existing_account(
     Ok(updated_account)
 }
 
-async fn update_existing_remote_account(
-    app_handle: &AppHandle,
-    account: &Account,
-    sync_account: &SyncAccount,
-) -> Result<Record, String> {
-    let decrypted_record = encryption::decrypt_account(&encryption::get_key_directory(app_handle), account);
-    let updated_record_details = match update_record(&decrypted_record, sync_account).await {
-        Ok(record) => record,
-        Err(response_error) => return Err(response_error.formatted_message()),
+fn cache_token(
+    app_handle: &impl ServiceAccess,
+    original_account: &SyncAccount,
+    authenticated_account: &SyncAccount,
+) -> Result<(), String> {
+    if original_account.token == authenticated_account.token {
+        return Ok(());
+    }
+
+    let encrypted_token = match &authenticated_account.token {
+        Some(token) => Some(app_handle.data_key()?.encrypt(token)?),
+        None => None,
     };
 
     app_handle
-        .db(|db| database::set_remote_account(db, account, &updated_record_details))
+        .db(|db| database::update_sync_account_token(authenticated_account.id, encrypted_token, db))
         .unwrap();
 
-    Ok(updated_record_details)
+    Ok(())
 }
 
-fn handle_error_log(app_handle: &AppHandle, log: String) -> SyncLog {
+fn handle_error_log(app_handle: &impl ServiceAccess, context: SyncLogContext, log: String) -> SyncLog {
+    let now = app_handle.now();
+
     app_handle
-        .db(|db| database::create_sync_log(db, log, SyncLogType::ERROR))
+        .db(|db| database::create_sync_log(db, log, SyncLogType::ERROR, context, now))
         .unwrap()
 }
 
+fn handle_warn_log(app_handle: &impl ServiceAccess, context: SyncLogContext, log: String) -> SyncLog {
+    let now = app_handle.now();
+
+    app_handle
+        .db(|db| database::create_sync_log(db, log, SyncLogType::WARN, context, now))
+        .unwrap()
+}
+
+fn handle_info_log(app_handle: &impl ServiceAccess, context: SyncLogContext, log: String) -> SyncLog {
+    let now = app_handle.now();
+
+    app_handle
+        .db(|db| database::create_sync_log(db, log, SyncLogType::INFO, context, now))
+        .unwrap()
+}
+
+#[cfg(test)]
+#[path = "./sync_local_test.rs"]
+mod device_tests;
+
 #[cfg(test)]
 mod tests {
     use crate::database::Account;
     use crate::sync_api::SyncManifest;
-    use crate::sync_local::{get_sync_status, SyncStatus};
+    use crate::database::{SyncFailure, SyncOperation};
+    use crate::sync_coordinator::SyncCancellation;
+    use crate::sync_local::{
+        exceeds_deletion_threshold, generate_session_id, get_sync_status, is_held_failure, SyncItem,
+        SyncSession, SyncStatus,
+    };
 
     #[test]
     fn test_external_date_missing() {
@@ -304,6 +1081,7 @@ mod tests {
             external_last_updated: None,
             external_hash: Option::from("HELLOWORLD".to_string()),
             deleted_at: None,
+            sync_account_id: None,
         };
 
         let manifest = SyncManifest {
@@ -331,6 +1109,7 @@ mod tests {
             external_last_updated: Option::from(1725483730),
             external_hash: Option::from("HELLOWORLD".to_string()),
             deleted_at: None,
+            sync_account_id: None,
         };
 
         let manifest = SyncManifest {
@@ -358,6 +1137,7 @@ mod tests {
             external_last_updated: Option::from(1725483734),
             external_hash: Option::from("HELLOWORLD".to_string()),
             deleted_at: None,
+            sync_account_id: None,
         };
 
         let manifest = SyncManifest {
@@ -385,6 +1165,7 @@ mod tests {
             external_last_updated: Option::from(1725483734),
             external_hash: Option::from("HELLOWORLD".to_string()),
             deleted_at: None,
+            sync_account_id: None,
         };
 
         let manifest = SyncManifest {
@@ -394,4 +1175,116 @@ mod tests {
 
         assert_eq!(SyncStatus::UpToDate, get_sync_status(&account, &manifest));
     }
+
+    #[test]
+    fn test_single_remote_deletion_allowed() {
+        assert_eq!(false, exceeds_deletion_threshold(1, 1));
+    }
+
+    #[test]
+    fn test_remote_deletion_within_threshold() {
+        assert_eq!(false, exceeds_deletion_threshold(2, 10));
+        assert_eq!(false, exceeds_deletion_threshold(5, 10));
+    }
+
+    #[test]
+    fn test_remote_deletion_over_threshold() {
+        assert_eq!(true, exceeds_deletion_threshold(6, 10));
+        assert_eq!(true, exceeds_deletion_threshold(10, 10));
+    }
+
+    #[test]
+    fn test_session_id_format() {
+        let session_id = generate_session_id();
+
+        assert_eq!(16, session_id.len());
+        assert_eq!(true, session_id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(session_id, generate_session_id());
+    }
+
+    #[test]
+    fn test_session_context() {
+        let mut session = SyncSession::new("abc123", Some(4), SyncCancellation::default());
+        session.created = 2;
+        session.deleted = 1;
+
+        let context = session.context(SyncOperation::PULL, Some(12), Some(1234));
+
+        assert_eq!(Some("abc123".to_string()), context.session_id);
+        assert_eq!(Some(4), context.sync_account_id);
+        assert_eq!(Some(12), context.account_id);
+        assert_eq!(Some(1234), context.external_id);
+        assert_eq!(Some(SyncOperation::PULL), context.operation);
+        assert_eq!("Sync complete: 2 created, 0 updated, 1 deleted", session.summary());
+    }
+
+    #[test]
+    fn test_session_cancelled() {
+        let cancellation = SyncCancellation::default();
+        let session = SyncSession::new("abc123", Some(4), cancellation.clone());
+
+        assert_eq!(true, session.check_cancelled().is_ok());
+
+        cancellation.cancel();
+
+        assert_eq!(Err("Sync cancelled".to_string()), session.check_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_interrupts_in_flight_request() {
+        let cancellation = SyncCancellation::default();
+        let session = SyncSession::new("abc123", Some(4), cancellation.clone());
+
+        let (result, _) = tokio::join!(
+            session.cancellable(std::future::pending::<()>()),
+            async { cancellation.cancel() }
+        );
+
+        assert_eq!(Err("Sync cancelled".to_string()), result);
+    }
+
+    #[test]
+    fn test_failed_item_held_after_repeated_attempts() {
+        let mut failure = SyncFailure {
+            id: 1,
+            sync_account_id: 4,
+            account_id: Some(12),
+            external_id: None,
+            operation: SyncOperation::UPDATE,
+            error: "Bad Request".to_string(),
+            attempts: 4,
+            last_attempt_at: 1000,
+        };
+
+        assert_eq!(false, is_held_failure(&failure, 1001));
+
+        failure.attempts = 5;
+
+        assert_eq!(true, is_held_failure(&failure, 1001));
+        assert_eq!(false, is_held_failure(&failure, 1000 + 24 * 60 * 60));
+    }
+
+    #[test]
+    fn test_item_failure_key() {
+        let local_item = SyncItem {
+            account_id: Some(12),
+            external_id: Some(1234),
+            name: None,
+        };
+
+        assert_eq!((Some(12), None), local_item.failure_key());
+        assert_eq!((None, Some(1234)), SyncItem::remote(1234).failure_key());
+    }
+
+    #[test]
+    fn test_session_phase_failure() {
+        let mut session = SyncSession::new("abc123", Some(4), SyncCancellation::default());
+
+        session.phase_failure(SyncOperation::MANIFEST, "Unauthorized".to_string());
+
+        assert_eq!(1, session.failures.len());
+        assert_eq!(4, session.failures[0].sync_account_id);
+        assert_eq!(SyncOperation::MANIFEST, session.failures[0].operation);
+        assert_eq!(0, session.failed);
+    }
 }
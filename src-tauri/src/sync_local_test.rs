@@ -0,0 +1,400 @@
+use crate::database::{Account, SyncAccount, SyncAuthType, SyncType};
+use crate::local_api::LocalApi;
+use crate::state::{AppState, ServiceAccess};
+use crate::sync_api::SyncClient;
+use crate::sync_coordinator::{SyncCancellation, SyncCoordinator};
+use crate::sync_local::{confirm_held_deletions, sync_all_accounts, SyncReport};
+use crate::sync_scheduler::SyncScheduler;
+use crate::test_support::FixedClock;
+use crate::vault::{Clock, SystemClock, Vault};
+use crate::{database, test_support};
+use phoenix_sync_server::ServerState;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::net::TcpListener;
+
+const SERVER_USERNAME: &str = "devices@phoenix.local";
+const SERVER_PASSWORD: &str = "device-password";
+const TEST_SECRET: &str = "JBSWY3DPEHPK3PXP";
+const TEST_TIME: u64 = 1_700_000_000;
+
+struct SimulatedDevice {
+    state: AppState,
+    events: Mutex<Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Eq, Ord)]
+struct DeviceAccount {
+    name: String,
+    secret: String,
+    otp_digits: i32,
+    totp_step: i32,
+}
+
+impl SimulatedDevice {
+    fn new(scenario: &str, name: &str, server_url: &str) -> SimulatedDevice {
+        SimulatedDevice::with_clock(scenario, name, server_url, Box::new(SystemClock))
+    }
+
+    fn with_clock(
+        scenario: &str,
+        name: &str,
+        server_url: &str,
+        clock: Box<dyn Clock>,
+    ) -> SimulatedDevice {
+        let key_directory = PathBuf::from("./bin/sync_devices")
+            .join(scenario)
+            .join(name);
+        let vault = test_support::test_vault(key_directory, clock);
+
+        let device = SimulatedDevice {
+            state: AppState {
+                vault: Mutex::new(Some(vault)),
+                sync_client: SyncClient::new(),
+                sync_report: Default::default(),
+                sync_coordinator: Default::default(),
+                sync_scheduler: Default::default(),
+                local_api: Default::default(),
+            },
+            events: Mutex::new(Vec::new()),
+        };
+
+        device
+            .vault(|vault| {
+                vault.save_sync_account(SyncAccount {
+                    id: 0,
+                    username: SERVER_USERNAME.to_string(),
+                    password: SERVER_PASSWORD.to_string(),
+                    url: server_url.to_string(),
+                    token: None,
+                    sync_type: SyncType::PHOENIX,
+                    passphrase: None,
+                    auth_type: SyncAuthType::BASIC,
+                })
+            })
+            .unwrap();
+
+        device
+    }
+
+    fn create_account(&self, name: &str) -> Account {
+        self.vault(|vault| vault.create_account(name, TEST_SECRET, 6, 30, "ff0000", "SHA1"))
+            .unwrap()
+    }
+
+    fn edit_account(&self, name: &str, new_name: &str, totp_step: i32) {
+        let account = self.find_account(name);
+
+        self.vault(|vault| {
+            vault.edit_account(
+                account.id,
+                new_name,
+                account.otp_digits,
+                totp_step,
+                &account.colour,
+                "SHA1",
+            )
+        })
+        .unwrap();
+    }
+
+    fn delete_account(&self, name: &str) {
+        let account = self.find_account(name);
+
+        assert_eq!(
+            true,
+            self.vault(|vault| vault.delete_account(account.id as u32))
+        );
+    }
+
+    fn find_account(&self, name: &str) -> Account {
+        self.vault(|vault| {
+            let account = vault
+                .get_all_accounts("")
+                .into_iter()
+                .find(|account| account.name == name)
+                .unwrap_or_else(|| panic!("{} is missing", name));
+
+            vault.get_account(account.id as u32).unwrap()
+        })
+    }
+
+    fn accounts(&self) -> Vec<DeviceAccount> {
+        let key = self.data_key().unwrap();
+        let mut accounts: Vec<DeviceAccount> = self
+            .vault(|vault| vault.get_all_accounts(""))
+            .into_iter()
+            .map(|account| {
+                let account = self.find_account(&account.name);
+
+                DeviceAccount {
+                    name: account.name,
+                    secret: key.decrypt(&account.secret).unwrap(),
+                    otp_digits: account.otp_digits,
+                    totp_step: account.totp_step,
+                }
+            })
+            .collect();
+
+        accounts.sort();
+        accounts
+    }
+
+    fn trashed_account_names(&self) -> Vec<String> {
+        self.db(|db| database::get_trashed_accounts(0, db))
+            .unwrap()
+            .into_iter()
+            .map(|account| account.name)
+            .collect()
+    }
+
+    async fn sync(&self) -> SyncReport {
+        sync_all_accounts(self, SyncCancellation::default()).await;
+
+        let report = self.sync_report();
+
+        assert_eq!(
+            Some(true),
+            report.success,
+            "Sync failed: {:?}",
+            report.error
+        );
+        report
+    }
+}
+
+impl ServiceAccess for SimulatedDevice {
+    fn vault<F, TResult>(&self, operation: F) -> TResult
+    where
+        F: FnOnce(&Vault) -> TResult,
+    {
+        operation(self.state.vault.lock().unwrap().as_ref().unwrap())
+    }
+
+    fn vault_mut<F, TResult>(&self, operation: F) -> TResult
+    where
+        F: FnOnce(&mut Vault) -> TResult,
+    {
+        operation(self.state.vault.lock().unwrap().as_mut().unwrap())
+    }
+
+    fn sync_client(&self) -> SyncClient {
+        self.state.sync_client.clone()
+    }
+
+    fn sync_report(&self) -> SyncReport {
+        self.state.sync_report.lock().unwrap().clone()
+    }
+
+    fn set_sync_report(&self, report: SyncReport) {
+        *self.state.sync_report.lock().unwrap() = report;
+    }
+
+    fn sync_coordinator(&self) -> SyncCoordinator {
+        self.state.sync_coordinator.clone()
+    }
+
+    fn sync_scheduler(&self) -> SyncScheduler {
+        self.state.sync_scheduler.clone()
+    }
+
+    fn local_api(&self) -> LocalApi {
+        self.state.local_api.clone()
+    }
+
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, _payload: S) {
+        self.events.lock().unwrap().push(event.to_string());
+    }
+}
+
+async fn start_server() -> String {
+    let state = ServerState::new(
+        phoenix_sync_server::database::open_in_memory().unwrap(),
+        "device-test-secret",
+    );
+    state.add_user(SERVER_USERNAME, SERVER_PASSWORD).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(phoenix_sync_server::serve(listener, state));
+
+    url
+}
+
+async fn paired_devices(scenario: &str) -> (SimulatedDevice, SimulatedDevice) {
+    let url = start_server().await;
+
+    (
+        SimulatedDevice::new(scenario, "first", &url),
+        SimulatedDevice::new(scenario, "second", &url),
+    )
+}
+
+fn assert_converged(first: &SimulatedDevice, second: &SimulatedDevice) {
+    assert_eq!(first.accounts(), second.accounts());
+}
+
+#[tokio::test]
+async fn test_created_account_reaches_other_device() {
+    let (first, second) = paired_devices("create").await;
+
+    first.create_account("GitHub");
+
+    let report = first.sync().await;
+    assert_eq!(1, report.created);
+
+    let report = second.sync().await;
+    assert_eq!(1, report.created);
+
+    assert_converged(&first, &second);
+    assert_eq!(TEST_SECRET, second.accounts()[0].secret);
+    assert_eq!(
+        vec!["sync-started", "sync-progress", "sync-completed"],
+        *second.events.lock().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_accounts_created_offline_on_both_devices() {
+    let (first, second) = paired_devices("create_offline").await;
+
+    first.create_account("GitHub");
+    second.create_account("GitLab");
+
+    first.sync().await;
+    second.sync().await;
+    first.sync().await;
+
+    assert_converged(&first, &second);
+    assert_eq!(2, first.accounts().len());
+}
+
+#[tokio::test]
+async fn test_edit_reaches_other_device() {
+    let (first, second) = paired_devices("edit").await;
+
+    first.create_account("GitHub");
+    first.sync().await;
+    second.sync().await;
+
+    second.edit_account("GitHub", "GitHub Work", 60);
+    second.sync().await;
+
+    let report = first.sync().await;
+    assert_eq!(1, report.updated);
+
+    assert_converged(&first, &second);
+    assert_eq!("GitHub Work", first.accounts()[0].name);
+    assert_eq!(60, first.accounts()[0].totp_step);
+}
+
+#[tokio::test]
+async fn test_deletion_moves_account_to_trash_on_other_device() {
+    let (first, second) = paired_devices("delete").await;
+
+    first.create_account("GitHub");
+    first.create_account("GitLab");
+    first.sync().await;
+    second.sync().await;
+
+    first.delete_account("GitHub");
+
+    let report = first.sync().await;
+    assert_eq!(1, report.deleted);
+
+    let report = second.sync().await;
+    assert_eq!(1, report.deleted);
+
+    assert_converged(&first, &second);
+    assert_eq!(vec!["GitHub".to_string()], second.trashed_account_names());
+}
+
+#[tokio::test]
+async fn test_mass_deletion_held_until_confirmed() {
+    let (first, second) = paired_devices("held").await;
+
+    first.create_account("GitHub");
+    first.create_account("GitLab");
+    first.create_account("Bitbucket");
+    first.sync().await;
+    second.sync().await;
+
+    first.delete_account("GitHub");
+    first.delete_account("GitLab");
+    first.sync().await;
+
+    let report = second.sync().await;
+    assert_eq!(0, report.deleted);
+    assert_eq!(2, report.held_deletions.len());
+    assert_eq!(true, second.trashed_account_names().is_empty());
+
+    let sync_account_id = report.held_deletions[0].sync_account_id;
+    assert_eq!(Ok(2), confirm_held_deletions(&second, sync_account_id));
+    assert_eq!(true, second.sync_report().held_deletions.is_empty());
+
+    let mut trashed = second.trashed_account_names();
+    trashed.sort();
+    assert_eq!(vec!["GitHub".to_string(), "GitLab".to_string()], trashed);
+
+    let report = second.sync().await;
+    assert_eq!(true, report.held_deletions.is_empty());
+    assert_converged(&first, &second);
+}
+
+#[tokio::test]
+async fn test_conflicting_edits_converge_on_last_push() {
+    let (first, second) = paired_devices("conflict").await;
+
+    first.create_account("GitHub");
+    first.sync().await;
+    second.sync().await;
+
+    first.edit_account("GitHub", "GitHub First", 30);
+    second.edit_account("GitHub", "GitHub Second", 60);
+
+    first.sync().await;
+    second.sync().await;
+    first.sync().await;
+
+    assert_converged(&first, &second);
+    assert_eq!("GitHub Second", first.accounts()[0].name);
+    assert_eq!(60, first.accounts()[0].totp_step);
+}
+
+#[tokio::test]
+async fn test_repeated_sync_is_stable() {
+    let (first, second) = paired_devices("stable").await;
+
+    first.create_account("GitHub");
+    first.sync().await;
+    second.sync().await;
+
+    for device in [&first, &second] {
+        let report = device.sync().await;
+
+        assert_eq!(0, report.created);
+        assert_eq!(0, report.updated);
+        assert_eq!(0, report.deleted);
+    }
+
+    assert_converged(&first, &second);
+}
+
+#[tokio::test]
+async fn test_sync_uses_vault_clock() {
+    let url = start_server().await;
+    let device =
+        SimulatedDevice::with_clock("clock", "first", &url, Box::new(FixedClock(TEST_TIME)));
+
+    device.create_account("GitHub");
+
+    let report = device.sync().await;
+    assert_eq!(Some(TEST_TIME), report.started_at);
+    assert_eq!(Some(TEST_TIME), report.finished_at);
+
+    let logs = device.vault(|vault| vault.get_sync_logs(None)).logs;
+    assert_eq!(false, logs.is_empty());
+    assert_eq!(true, logs.iter().all(|log| log.timestamp == TEST_TIME));
+}
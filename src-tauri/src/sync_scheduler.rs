@@ -0,0 +1,210 @@
+use crate::database;
+use crate::state::ServiceAccess;
+use crate::sync_coordinator;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::AppHandle;
+
+const SCHEDULER_TICK: Duration = Duration::from_secs(5);
+const EDIT_SYNC_DELAY_SECONDS: u64 = 10;
+const FAILURE_RETRY_SECONDS: u64 = 60;
+const MAXIMUM_BACKOFF_EXPONENT: u32 = 6;
+const MAXIMUM_BACKOFF_SECONDS: u64 = 6 * 60 * 60;
+const SECONDS_IN_MINUTE: u64 = 60;
+
+#[derive(Default)]
+struct ScheduleState {
+    last_run_at: u64,
+    failures: u32,
+    edit_due_at: Option<u64>,
+    offline: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct SyncScheduler {
+    state: Arc<Mutex<ScheduleState>>,
+}
+
+impl SyncScheduler {
+    pub fn request_soon(&self, now: u64) {
+        let mut state = self.state.lock().unwrap();
+        let due_at = now + EDIT_SYNC_DELAY_SECONDS;
+
+        state.edit_due_at = Some(match state.edit_due_at {
+            Some(existing) => existing.min(due_at),
+            None => due_at,
+        });
+    }
+
+    pub fn record_run(&self, now: u64, success: bool) {
+        let mut state = self.state.lock().unwrap();
+
+        state.last_run_at = now;
+        state.failures = match success {
+            true => 0,
+            false => state.failures + 1,
+        };
+    }
+
+    pub fn set_online(&self, online: bool, now: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        if online && state.offline {
+            state.failures = 0;
+            state.edit_due_at = Some(now + EDIT_SYNC_DELAY_SECONDS);
+        }
+
+        state.offline = !online;
+    }
+
+    pub fn take_due(&self, now: u64, interval_minutes: u32) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if state.offline {
+            return false;
+        }
+
+        let edit_due = match state.edit_due_at {
+            Some(due_at) => {
+                now >= due_at
+                    && now
+                        >= state.last_run_at + backoff_delay(FAILURE_RETRY_SECONDS, state.failures)
+            }
+            None => false,
+        };
+
+        let interval_due = interval_minutes > 0
+            && now
+                >= state.last_run_at
+                    + backoff_delay(interval_minutes as u64 * SECONDS_IN_MINUTE, state.failures);
+
+        if !edit_due && !interval_due {
+            return false;
+        }
+
+        state.edit_due_at = None;
+        state.last_run_at = now;
+
+        true
+    }
+}
+
+fn backoff_delay(base_seconds: u64, failures: u32) -> u64 {
+    if failures == 0 {
+        return base_seconds;
+    }
+
+    let backoff = base_seconds << failures.min(MAXIMUM_BACKOFF_EXPONENT);
+
+    backoff.min(MAXIMUM_BACKOFF_SECONDS.max(base_seconds))
+}
+
+pub fn start(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+
+            let sync_accounts = app_handle.db(database::get_sync_accounts).unwrap();
+
+            if sync_accounts.is_empty() {
+                continue;
+            }
+
+            let settings = app_handle.db(database::get_settings).unwrap();
+
+            if app_handle
+                .sync_scheduler()
+                .take_due(app_handle.now(), settings.sync_interval_minutes)
+            {
+                sync_coordinator::request_sync(app_handle.clone());
+            }
+        }
+    });
+}
+
+pub fn sync_after_edit(app_handle: &AppHandle) {
+    app_handle
+        .sync_scheduler()
+        .request_soon(app_handle.now());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync_scheduler::{backoff_delay, SyncScheduler};
+
+    #[test]
+    fn test_interval_due() {
+        let scheduler = SyncScheduler::default();
+
+        scheduler.record_run(1000, true);
+
+        assert_eq!(false, scheduler.take_due(1000 + 14 * 60, 15));
+        assert_eq!(true, scheduler.take_due(1000 + 15 * 60, 15));
+        assert_eq!(false, scheduler.take_due(1000 + 15 * 60 + 5, 15));
+    }
+
+    #[test]
+    fn test_interval_disabled() {
+        let scheduler = SyncScheduler::default();
+
+        scheduler.record_run(1000, true);
+
+        assert_eq!(false, scheduler.take_due(1000 + 24 * 60 * 60, 0));
+    }
+
+    #[test]
+    fn test_sync_shortly_after_edit() {
+        let scheduler = SyncScheduler::default();
+
+        scheduler.record_run(1000, true);
+        scheduler.request_soon(1100);
+        scheduler.request_soon(1105);
+
+        assert_eq!(false, scheduler.take_due(1105, 15));
+        assert_eq!(true, scheduler.take_due(1110, 15));
+        assert_eq!(false, scheduler.take_due(1120, 15));
+    }
+
+    #[test]
+    fn test_backoff_after_failures() {
+        let scheduler = SyncScheduler::default();
+
+        scheduler.record_run(1000, false);
+        scheduler.record_run(1000, false);
+
+        assert_eq!(false, scheduler.take_due(1000 + 45 * 60, 15));
+        assert_eq!(true, scheduler.take_due(1000 + 60 * 60, 15));
+
+        scheduler.record_run(5000, false);
+        scheduler.request_soon(5000);
+
+        assert_eq!(false, scheduler.take_due(5010, 15));
+
+        scheduler.record_run(6000, true);
+
+        assert_eq!(true, scheduler.take_due(6000 + 15 * 60, 15));
+    }
+
+    #[test]
+    fn test_backoff_delay_capped() {
+        assert_eq!(900, backoff_delay(900, 0));
+        assert_eq!(3600, backoff_delay(900, 2));
+        assert_eq!(6 * 60 * 60, backoff_delay(900, 20));
+        assert_eq!(12 * 60 * 60, backoff_delay(12 * 60 * 60, 3));
+    }
+
+    #[test]
+    fn test_paused_while_offline() {
+        let scheduler = SyncScheduler::default();
+
+        scheduler.record_run(99_500, false);
+        scheduler.set_online(false, 99_500);
+
+        assert_eq!(false, scheduler.take_due(99_500 + 24 * 60 * 60, 15));
+
+        scheduler.set_online(true, 100_000);
+
+        assert_eq!(false, scheduler.take_due(100_005, 15));
+        assert_eq!(true, scheduler.take_due(100_010, 15));
+    }
+}
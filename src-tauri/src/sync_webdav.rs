@@ -0,0 +1,408 @@
+use crate::database::{Account, SyncAccount, SyncAuthType};
+use crate::sync_api::{
+    handle_invalid_response_body, Record, ResponseError, ResponseErrorKind, SyncClient,
+    SyncManifest, VerboseRecord,
+};
+use crate::sync_backend::{
+    decrypt_record, encrypt_record, is_replayed_record, record_id_exhausted_error,
+    record_ids_for_key, PassphraseManifest, SyncBackend,
+};
+use chacha20poly1305::Key;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::header::{CONTENT_TYPE, IF_MATCH, IF_NONE_MATCH};
+use reqwest::{Method, RequestBuilder};
+use std::time::UNIX_EPOCH;
+
+#[cfg(test)]
+#[path = "./sync_webdav_test.rs"]
+mod tests;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const RECORD_FILE_EXTENSION: &str = "record";
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+    <d:prop>
+        <d:getetag/>
+        <d:getlastmodified/>
+    </d:prop>
+</d:propfind>"#;
+
+#[derive(Debug, PartialEq)]
+pub struct WebDavResource {
+    pub href: String,
+    pub etag: String,
+    pub last_modified: u64,
+}
+
+pub struct WebDavBackend {
+    client: SyncClient,
+    account: SyncAccount,
+    key: Key,
+}
+
+impl WebDavBackend {
+    pub async fn connect(
+        client: SyncClient,
+        account: SyncAccount,
+    ) -> Result<WebDavBackend, ResponseError> {
+        let passphrase = match &account.passphrase {
+            Some(passphrase) if !passphrase.is_empty() => passphrase.clone(),
+            _ => {
+                return Err(ResponseError::new(
+                    "400",
+                    "Missing WebDAV encryption passphrase",
+                    ResponseErrorKind::Request,
+                ))
+            }
+        };
+        let mut backend = WebDavBackend {
+            client: client.verified(),
+            account,
+            key: Key::default(),
+        };
+        let collection_url = backend.collection_url();
+
+        match backend.propfind(&collection_url, "0").await {
+            Ok(_) => {}
+            Err(e) if e.status().starts_with("404") => {
+                let request_builder =
+                    backend.request(Method::from_bytes(b"MKCOL").unwrap(), &collection_url);
+
+                if let Err(e) = backend.client.send(request_builder, false).await {
+                    return Err(e);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+
+        let manifest = match backend.load_manifest(&passphrase).await {
+            Ok(manifest) => manifest,
+            Err(e) => return Err(e),
+        };
+
+        match manifest.unlock(&passphrase) {
+            Some(key) => backend.key = key,
+            None => {
+                return Err(ResponseError::new(
+                    "401",
+                    "Incorrect WebDAV passphrase",
+                    ResponseErrorKind::Authentication,
+                ))
+            }
+        }
+
+        Ok(backend)
+    }
+
+    async fn read_manifest(&self) -> Result<Option<PassphraseManifest>, ResponseError> {
+        let request_builder = self.request(Method::GET, &self.manifest_url());
+
+        let response = match self.client.send(request_builder, true).await {
+            Ok(response) => response,
+            Err(e) if e.status().starts_with("404") => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        match response.json::<PassphraseManifest>().await {
+            Ok(manifest) => Ok(Some(manifest)),
+            Err(_) => Err(handle_invalid_response_body()),
+        }
+    }
+
+    async fn load_manifest(&self, passphrase: &str) -> Result<PassphraseManifest, ResponseError> {
+        match self.read_manifest().await {
+            Ok(Some(manifest)) => return Ok(manifest),
+            Ok(None) => {}
+            Err(e) => return Err(e),
+        }
+
+        let manifest = PassphraseManifest::new(passphrase);
+        let request_builder = self
+            .request(Method::PUT, &self.manifest_url())
+            .header(IF_NONE_MATCH, "*")
+            .json(&manifest);
+
+        match self.client.send(request_builder, true).await {
+            Ok(_) => Ok(manifest),
+            // Another device created the manifest first, so use theirs
+            Err(e) if e.status().starts_with("412") => match self.read_manifest().await {
+                Ok(Some(manifest)) => Ok(manifest),
+                Ok(None) => Err(handle_invalid_response_body()),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    fn collection_url(&self) -> String {
+        format!("{}/", self.account.url.trim_end_matches('/'))
+    }
+
+    fn manifest_url(&self) -> String {
+        format!("{}{}", self.collection_url(), MANIFEST_FILE_NAME)
+    }
+
+    fn record_url(&self, id: &i32) -> String {
+        format!("{}{}.{}", self.collection_url(), id, RECORD_FILE_EXTENSION)
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let request_builder = self.client.request(method, url);
+
+        match self.account.auth_type {
+            SyncAuthType::BASIC => {
+                request_builder.basic_auth(&self.account.username, Some(&self.account.password))
+            }
+            SyncAuthType::BEARER => request_builder.bearer_auth(&self.account.password),
+        }
+    }
+
+    async fn propfind(&self, url: &str, depth: &str) -> Result<Vec<WebDavResource>, ResponseError> {
+        let request_builder = self
+            .request(Method::from_bytes(b"PROPFIND").unwrap(), url)
+            .header("Depth", depth)
+            .header(CONTENT_TYPE, "application/xml")
+            .body(PROPFIND_BODY);
+
+        let response = match self.client.send(request_builder, true).await {
+            Ok(response) => response,
+            Err(e) => return Err(e),
+        };
+
+        match response.text().await {
+            Ok(body) => parse_multistatus(&body),
+            Err(_) => Err(handle_invalid_response_body()),
+        }
+    }
+
+    async fn stat_record(&self, id: &i32) -> Result<Record, ResponseError> {
+        let resources = match self.propfind(&self.record_url(id), "0").await {
+            Ok(resources) => resources,
+            Err(e) => return Err(e),
+        };
+
+        match resources.iter().find_map(resource_to_manifest) {
+            Some((manifest, sync_hash)) => Ok(Record {
+                id: manifest.id,
+                sync_hash,
+                updated_at: manifest.updated_at,
+            }),
+            None => Err(handle_invalid_response_body()),
+        }
+    }
+
+    async fn get_record(&self, id: &i32) -> Result<String, ResponseError> {
+        let request_builder = self.request(Method::GET, &self.record_url(id));
+        let response = match self.client.send(request_builder, true).await {
+            Ok(response) => response,
+            Err(e) => return Err(e),
+        };
+
+        match response.text().await {
+            Ok(encrypted) => Ok(encrypted),
+            Err(_) => Err(handle_invalid_response_body()),
+        }
+    }
+
+    async fn put_record(
+        &self,
+        id: i32,
+        account: &Account,
+        idempotency_key: Option<&str>,
+    ) -> Result<Record, ResponseError> {
+        let encrypted = match encrypt_record(&self.key, account, idempotency_key) {
+            Ok(encrypted) => encrypted,
+            Err(e) => return Err(ResponseError::new("500", &e, ResponseErrorKind::Storage)),
+        };
+
+        let mut request_builder = self
+            .request(Method::PUT, &self.record_url(&id))
+            .header(CONTENT_TYPE, "text/plain")
+            .body(encrypted);
+
+        // Creates must not replace an existing file, updates must not replace another device's edit
+        match (idempotency_key, &account.external_hash) {
+            (Some(_), _) => request_builder = request_builder.header(IF_NONE_MATCH, "*"),
+            (None, Some(etag)) => {
+                request_builder = request_builder.header(IF_MATCH, format!("\"{}\"", etag))
+            }
+            (None, None) => {}
+        }
+
+        match self.client.send(request_builder, true).await {
+            Ok(_) => self.stat_record(&id).await,
+            Err(e) if idempotency_key.is_none() && e.status().starts_with("412") => {
+                Err(conflict_error())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl SyncBackend for WebDavBackend {
+    fn sync_account(&self) -> &SyncAccount {
+        &self.account
+    }
+
+    async fn list_manifest(&mut self) -> Result<Vec<SyncManifest>, ResponseError> {
+        match self.propfind(&self.collection_url(), "1").await {
+            Ok(resources) => Ok(resources
+                .iter()
+                .filter_map(resource_to_manifest)
+                .map(|(manifest, _)| manifest)
+                .collect()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn fetch_record(&mut self, id: &i32) -> Result<VerboseRecord, ResponseError> {
+        let record = match self.stat_record(id).await {
+            Ok(record) => record,
+            Err(e) => return Err(e),
+        };
+
+        let encrypted = match self.get_record(id).await {
+            Ok(encrypted) => encrypted,
+            Err(e) => return Err(e),
+        };
+
+        match decrypt_record(&self.key, &encrypted, record) {
+            Ok(record) => Ok(record),
+            Err(_) => Err(handle_invalid_response_body()),
+        }
+    }
+
+    async fn create_record(
+        &mut self,
+        account: &Account,
+        idempotency_key: &str,
+    ) -> Result<Record, ResponseError> {
+        for id in record_ids_for_key(idempotency_key) {
+            match self.put_record(id, account, Some(idempotency_key)).await {
+                Err(e) if e.status().starts_with("412") => {}
+                result => return result,
+            }
+
+            let encrypted = match self.get_record(&id).await {
+                Ok(encrypted) => encrypted,
+                Err(e) => return Err(e),
+            };
+
+            // A replayed create finds the record written by the first attempt
+            if is_replayed_record(&self.key, &encrypted, idempotency_key) {
+                return self.stat_record(&id).await;
+            }
+        }
+
+        Err(record_id_exhausted_error())
+    }
+
+    async fn update_record(&mut self, account: &Account) -> Result<Record, ResponseError> {
+        match account.external_id {
+            Some(id) => self.put_record(id, account, None).await,
+            None => Err(ResponseError::new(
+                "400",
+                "Missing External Id",
+                ResponseErrorKind::Request,
+            )),
+        }
+    }
+
+    async fn delete_record(&mut self, id: &i32) -> Result<bool, ResponseError> {
+        let request_builder = self.request(Method::DELETE, &self.record_url(id));
+
+        match self.client.send(request_builder, true).await {
+            Ok(_) => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn conflict_error() -> ResponseError {
+    ResponseError::new(
+        "409",
+        "Record was changed on another device",
+        ResponseErrorKind::Request,
+    )
+}
+
+pub fn parse_multistatus(body: &str) -> Result<Vec<WebDavResource>, ResponseError> {
+    let mut reader = Reader::from_str(body);
+    reader.config_mut().trim_text(true);
+
+    let mut resources = Vec::new();
+    let mut current_element = Vec::new();
+    let mut href = String::new();
+    let mut etag = String::new();
+    let mut last_modified = String::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => {
+                current_element = element.local_name().as_ref().to_vec();
+
+                if current_element == b"response" {
+                    href.clear();
+                    etag.clear();
+                    last_modified.clear();
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let value = match text.unescape() {
+                    Ok(value) => value.to_string(),
+                    Err(_) => return Err(handle_invalid_response_body()),
+                };
+
+                match current_element.as_slice() {
+                    b"href" => href = value,
+                    b"getetag" => etag = value.trim_matches('"').to_string(),
+                    b"getlastmodified" => last_modified = value,
+                    _ => {}
+                }
+            }
+            Ok(Event::End(element)) => {
+                current_element.clear();
+
+                if element.local_name().as_ref() == b"response" {
+                    resources.push(WebDavResource {
+                        href: href.clone(),
+                        etag: etag.clone(),
+                        last_modified: parse_http_date(&last_modified),
+                    });
+                }
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(_) => return Err(handle_invalid_response_body()),
+        }
+    }
+
+    Ok(resources)
+}
+
+fn resource_to_manifest(resource: &WebDavResource) -> Option<(SyncManifest, String)> {
+    let file_name = resource.href.trim_end_matches('/').rsplit('/').next()?;
+    let id = file_name
+        .strip_suffix(&format!(".{}", RECORD_FILE_EXTENSION))?
+        .parse::<i32>()
+        .ok()?;
+
+    Some((
+        SyncManifest {
+            id,
+            updated_at: resource.last_modified,
+        },
+        resource.etag.clone(),
+    ))
+}
+
+fn parse_http_date(value: &str) -> u64 {
+    match httpdate::parse_http_date(value) {
+        Ok(time) => time
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0),
+        Err(_) => 0,
+    }
+}
@@ -0,0 +1,22 @@
+use crate::database;
+use crate::vault::{Clock, Vault};
+use std::fs;
+use std::path::PathBuf;
+
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
+
+pub fn test_vault(key_directory: PathBuf, clock: Box<dyn Clock>) -> Vault {
+    let _ = fs::remove_dir_all(&key_directory);
+    fs::create_dir_all(&key_directory).unwrap();
+
+    let db =
+        database::initialize_database(PathBuf::from(":memory:"), key_directory.clone()).unwrap();
+
+    Vault::new(db, key_directory, clock)
+}
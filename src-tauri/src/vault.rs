@@ -0,0 +1,708 @@
+pub use crate::database::Account;
+use crate::database::{
+    AccountRevision, LocalApiClient, OutboxOperation, RevisionOrigin, Setting, SyncAccount,
+    SyncLogFilter, SyncLogPage, SyncType, Theme,
+};
+use crate::encryption::DataKey;
+use crate::otp_exporter::account_to_url;
+use crate::otp_parser::{is_valid_url, parse_url};
+use crate::{database, encryption};
+use libotp::{hotp, hotp_override, totp};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_SYNC_LOGS_PER_PAGE: u32 = 100;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Could not generate UNIX time")
+            .as_secs()
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct OneTimePassword {
+    pub code: String,
+    pub next_code: String,
+    pub period: u64,
+    pub period_start: u64,
+    pub seconds_remaining: u64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct AccountCode {
+    pub account_id: i32,
+    #[serde(flatten)]
+    pub otp: Option<OneTimePassword>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub struct Vault {
+    db: Connection,
+    key_directory: PathBuf,
+    key: OnceLock<DataKey>,
+    clock: Box<dyn Clock>,
+}
+
+impl Vault {
+    pub fn new(db: Connection, key_directory: PathBuf, clock: Box<dyn Clock>) -> Vault {
+        Vault {
+            db,
+            key_directory,
+            key: OnceLock::new(),
+            clock,
+        }
+    }
+
+    pub fn open(data_directory: PathBuf) -> Result<Vault, rusqlite::Error> {
+        let db =
+            database::initialize_prod_database(data_directory.clone(), data_directory.clone())?;
+
+        Ok(Vault::new(db, data_directory, Box::new(SystemClock)))
+    }
+
+    pub fn exists(data_directory: &Path) -> bool {
+        data_directory.join(database::SQLITE_NAME).exists()
+    }
+
+    pub fn db<F, TResult>(&self, operation: F) -> TResult
+    where
+        F: FnOnce(&Connection) -> TResult,
+    {
+        operation(&self.db)
+    }
+
+    pub fn db_mut<F, TResult>(&mut self, operation: F) -> TResult
+    where
+        F: FnOnce(&mut Connection) -> TResult,
+    {
+        operation(&mut self.db)
+    }
+
+    pub fn key_directory(&self) -> &Path {
+        &self.key_directory
+    }
+
+    pub fn data_key(&self) -> Result<&DataKey, String> {
+        if let Some(key) = self.key.get() {
+            return Ok(key);
+        }
+
+        let key = encryption::load_key(&self.key_directory)?;
+
+        Ok(self.key.get_or_init(|| key))
+    }
+
+    pub fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    pub fn get_one_time_password(&self, account_id: u32) -> Result<OneTimePassword, String> {
+        let account = self
+            .get_account(account_id)
+            .ok_or("Failed to generate OTP")?;
+        let decrypted_secret = self.data_key()?.decrypt(&account.secret)?;
+
+        generate_one_time_password(&account, &decrypted_secret, self.now())
+    }
+
+    // Unknown and trashed accounts are left out, an account that can't produce a code carries the reason instead
+    pub fn get_codes_for_accounts(
+        &self,
+        account_ids: Vec<i32>,
+    ) -> Result<Vec<AccountCode>, String> {
+        let key = self.data_key()?;
+        let accounts = self
+            .db(|db| database::get_accounts_by_ids(account_ids, db))
+            .map_err(|err| err.to_string())?;
+        let now = self.now();
+
+        Ok(accounts
+            .iter()
+            .map(|account| {
+                let otp = key
+                    .decrypt(&account.secret)
+                    .and_then(|secret| generate_one_time_password(account, &secret, now));
+
+                match otp {
+                    Ok(otp) => AccountCode {
+                        account_id: account.id,
+                        otp: Some(otp),
+                        error: None,
+                    },
+                    Err(error) => AccountCode {
+                        account_id: account.id,
+                        otp: None,
+                        error: Some(error),
+                    },
+                }
+            })
+            .collect())
+    }
+
+    pub fn get_account(&self, account_id: u32) -> Option<Account> {
+        let account = self
+            .db(|db| database::get_account_details_by_id(account_id, db))
+            .unwrap();
+
+        // Trashed accounts keep their row but must not hand out codes or be edited
+        match account.id {
+            0 => None,
+            _ if account.deleted_at.is_some() => None,
+            _ => Some(account),
+        }
+    }
+
+    pub fn get_all_accounts(&self, filter: &str) -> Vec<Account> {
+        self.db(|db| database::get_all_accounts(db, filter))
+            .unwrap()
+    }
+
+    // Matches an id, then an exact name, then a partial name, then the letters of the name in order
+    pub fn find_account(&self, query: &str) -> Result<Account, String> {
+        if let Ok(account_id) = query.parse::<u32>() {
+            if let Some(account) = self.get_account(account_id) {
+                return Ok(account);
+            }
+        }
+
+        let lowercase_query = query.to_lowercase();
+        let accounts = self.get_all_accounts("");
+        let matchers: [&dyn Fn(&str) -> bool; 3] = [
+            &|name: &str| name == lowercase_query,
+            &|name: &str| name.contains(&lowercase_query),
+            &|name: &str| is_subsequence(&lowercase_query, name),
+        ];
+
+        for matcher in matchers {
+            let matches: Vec<&Account> = accounts
+                .iter()
+                .filter(|account| matcher(&account.name.to_lowercase()))
+                .collect();
+
+            match matches.as_slice() {
+                [] => continue,
+                [account] => {
+                    return self
+                        .get_account(account.id as u32)
+                        .ok_or_else(|| "Invalid account id".to_string())
+                }
+                _ => {
+                    let names: Vec<&str> = matches
+                        .iter()
+                        .map(|account| account.name.as_str())
+                        .collect();
+
+                    return Err(format!("Multiple accounts match: {}", names.join(", ")));
+                }
+            }
+        }
+
+        Err(format!("No account matches: {}", query))
+    }
+
+    pub fn create_account(
+        &self,
+        name: &str,
+        secret: &str,
+        digits: i32,
+        step: i32,
+        colour: &str,
+        algorithm: &str,
+    ) -> Result<Account, String> {
+        if self.account_name_exists(name) {
+            return Err(format!("Account already exists: {}", name));
+        }
+
+        if totp(secret, digits as u32, step as u64, 0).is_none() {
+            return Err("Invalid 2FA Secret".to_string());
+        }
+
+        let encryption_secret = self.data_key()?.encrypt(secret)?;
+        let sync_account_id = self
+            .get_main_sync_account()
+            .map(|sync_account| sync_account.id);
+
+        let account = self
+            .db(|db| {
+                database::create_new_account(
+                    name,
+                    &encryption_secret,
+                    &digits,
+                    &step,
+                    colour,
+                    algorithm,
+                    sync_account_id,
+                    db,
+                )
+            })
+            .unwrap();
+
+        self.queue_sync_operation(&account.id, OutboxOperation::CREATE);
+
+        Ok(account)
+    }
+
+    pub fn edit_account(
+        &self,
+        id: i32,
+        name: &str,
+        digits: i32,
+        step: i32,
+        colour: &str,
+        algorithm: &str,
+    ) -> Result<Account, String> {
+        let account = self.get_account(id as u32).ok_or("Invalid account id")?;
+
+        let now = self.now();
+        let account = self
+            .db(|db| {
+                database::update_existing_account(
+                    &id,
+                    name,
+                    &account.secret,
+                    digits,
+                    step,
+                    colour,
+                    algorithm,
+                    RevisionOrigin::LOCAL,
+                    Some(now),
+                    now,
+                    db,
+                )
+            })
+            .unwrap();
+
+        self.queue_sync_operation(&account.id, OutboxOperation::UPDATE);
+
+        Ok(account)
+    }
+
+    pub fn delete_account(&self, account_id: u32) -> bool {
+        let account = self
+            .db(|db| database::get_account_details_by_id(account_id, db))
+            .unwrap();
+        let deleted = self
+            .db(|db| database::delete_account(&account, self.now(), db))
+            .unwrap();
+
+        if deleted {
+            self.queue_sync_operation(&account.id, OutboxOperation::DELETE);
+        }
+
+        deleted
+    }
+
+    // Secrets are left out, a revision is only ever restored by id
+    pub fn get_account_revisions(&self, account_id: i32) -> Vec<AccountRevision> {
+        let mut revisions = self
+            .db(|db| database::get_account_revisions(account_id, db))
+            .unwrap();
+
+        for revision in revisions.iter_mut() {
+            revision.secret = "".to_string();
+        }
+
+        revisions
+    }
+
+    pub fn restore_account_revision(&self, revision_id: i32) -> Result<bool, String> {
+        let revision = self
+            .db(|db| database::get_account_revision(revision_id, db))
+            .unwrap()
+            .ok_or("Revision does not exist")?;
+        let account = self
+            .get_account(revision.account_id as u32)
+            .ok_or("Account does not exist")?;
+
+        if account.name != revision.name && self.account_name_exists(&revision.name) {
+            return Err(format!("Account already exists: {}", revision.name));
+        }
+
+        let algorithm = match &revision.algorithm {
+            Some(algorithm) => algorithm.algorithm_to_string(),
+            None => "".to_string(),
+        };
+
+        let now = self.now();
+        let account = self
+            .db(|db| {
+                database::update_existing_account(
+                    &account.id,
+                    &revision.name,
+                    &revision.secret,
+                    revision.otp_digits,
+                    revision.totp_step,
+                    &revision.colour,
+                    &algorithm,
+                    RevisionOrigin::LOCAL,
+                    Some(now),
+                    now,
+                    db,
+                )
+            })
+            .unwrap();
+
+        self.queue_sync_operation(&account.id, OutboxOperation::UPDATE);
+
+        Ok(true)
+    }
+
+    pub fn get_trashed_accounts(&self) -> Vec<Account> {
+        let cutoff = self
+            .db(|db| database::get_trash_cutoff(self.now(), db))
+            .unwrap();
+
+        self.db(|db| database::get_trashed_accounts(cutoff, db))
+            .unwrap()
+    }
+
+    pub fn restore_account(&self, account_id: u32) -> Result<bool, String> {
+        let account = self
+            .db(|db| database::get_account_details_by_id(account_id, db))
+            .unwrap();
+
+        if account.id == 0 || account.deleted_at.is_none() {
+            return Err("Account is not in the trash".to_string());
+        }
+
+        if self.account_name_exists(&account.name) {
+            return Err(format!("Account already exists: {}", account.name));
+        }
+
+        let restored = self
+            .db(|db| database::restore_account(&account, self.now(), db))
+            .unwrap();
+
+        if restored {
+            self.queue_sync_operation(&account.id, OutboxOperation::UPDATE);
+        }
+
+        Ok(restored)
+    }
+
+    pub fn purge_account(&self, account_id: u32) -> bool {
+        let account = self
+            .db(|db| database::get_account_details_by_id(account_id, db))
+            .unwrap();
+
+        self.db(|db| database::purge_account(&account, db)).unwrap()
+    }
+
+    pub fn import_url(&self, url: &str) -> Result<Account, String> {
+        if !is_valid_url(url) {
+            return Err("Invalid OTP URL".to_string());
+        }
+
+        let account = parse_url(url);
+        let algorithm = match &account.algorithm {
+            Some(algorithm) => algorithm.algorithm_to_string(),
+            None => "".to_string(),
+        };
+        let existing = self
+            .get_all_accounts("")
+            .into_iter()
+            .find(|existing| existing.name == account.name);
+
+        if let Some(existing) = existing {
+            return self.reimport_account(existing.id, &account, &algorithm);
+        }
+
+        self.create_account(
+            &account.name,
+            &account.secret,
+            account.otp_digits,
+            account.totp_step,
+            &account.colour,
+            &algorithm,
+        )
+    }
+
+    pub fn export_accounts(&self) -> Result<String, String> {
+        let key = self.data_key()?;
+        let mut otps: String = "".to_owned();
+
+        for base_account in self.get_all_accounts("") {
+            let verbose_account = self
+                .db(|db| database::get_account_details_by_id(base_account.id as u32, db))
+                .unwrap();
+
+            otps.push_str(&account_to_url(encryption::decrypt_account(
+                key,
+                &verbose_account,
+            )?));
+            otps.push('\n');
+        }
+
+        Ok(otps)
+    }
+
+    // The flag is set when existing details changed and the account should sync again
+    pub fn save_sync_account(
+        &self,
+        sync_account: SyncAccount,
+    ) -> Result<(SyncAccount, bool), String> {
+        let existing_account = self
+            .db(|db| database::get_sync_account_by_id(sync_account.id, db))
+            .unwrap();
+        let key = self.data_key()?;
+        let encrypted_password = key.encrypt(&sync_account.password)?;
+        let encrypted_passphrase = match &sync_account.passphrase {
+            Some(passphrase) => Some(key.encrypt(passphrase)?),
+            None => None,
+        };
+
+        if existing_account.id == 0 {
+            let is_first_sync_account = self.db(database::get_sync_accounts).unwrap().is_empty();
+            let new_account = self
+                .db(|db| {
+                    database::create_sync_account(
+                        &sync_account.username,
+                        &encrypted_password,
+                        &sync_account.url,
+                        &sync_account.sync_type,
+                        encrypted_passphrase.as_deref(),
+                        &sync_account.auth_type,
+                        db,
+                    )
+                })
+                .unwrap();
+
+            if is_first_sync_account {
+                self.db(|db| database::assign_unassigned_accounts(new_account.id, db))
+                    .unwrap();
+            }
+
+            return Ok((new_account, false));
+        }
+
+        let updated_sync_account = SyncAccount {
+            id: existing_account.id,
+            password: encrypted_password,
+            token: None,
+            passphrase: encrypted_passphrase,
+            ..sync_account.clone()
+        };
+
+        self.db(|db| database::update_sync_account(updated_sync_account, db))
+            .unwrap();
+
+        Ok((
+            SyncAccount {
+                id: existing_account.id,
+                token: None,
+                ..sync_account
+            },
+            true,
+        ))
+    }
+
+    pub fn get_main_sync_account(&self) -> Option<SyncAccount> {
+        let sync_account = self.db(database::get_main_sync_account).unwrap();
+
+        match sync_account.id {
+            0 => None,
+            _ => Some(redact_sync_account(sync_account)),
+        }
+    }
+
+    pub fn get_sync_accounts(&self) -> Vec<SyncAccount> {
+        self.db(database::get_sync_accounts)
+            .unwrap()
+            .into_iter()
+            .map(redact_sync_account)
+            .collect()
+    }
+
+    pub fn remove_sync_account(&self, id: i32) -> bool {
+        self.db(|db| database::delete_sync_account(id, db)).unwrap()
+    }
+
+    pub fn set_account_sync_target(&self, account_id: i32, sync_account_id: Option<i32>) -> bool {
+        if let Some(id) = sync_account_id {
+            let sync_account = self
+                .db(|db| database::get_sync_account_by_id(id, db))
+                .unwrap();
+
+            if sync_account.id == 0 {
+                return false;
+            }
+        }
+
+        self.db(|db| {
+            database::set_account_sync_account(account_id, sync_account_id, self.now(), db)
+        })
+        .unwrap()
+    }
+
+    pub fn get_sync_logs(&self, filter: Option<SyncLogFilter>) -> SyncLogPage {
+        let mut filter = filter.unwrap_or_default();
+        filter.per_page = filter.per_page.clamp(1, MAX_SYNC_LOGS_PER_PAGE);
+
+        self.db(|db| database::get_sync_logs(db, &filter)).unwrap()
+    }
+
+    pub fn get_settings(&self) -> Setting {
+        self.db(database::get_settings).unwrap()
+    }
+
+    pub fn save_theme(&self, theme: Theme) -> Setting {
+        self.db(|db| database::save_settings(db, theme)).unwrap()
+    }
+
+    pub fn save_trash_retention(&self, trash_retention_days: u32) -> Setting {
+        self.db(|db| database::save_trash_retention(db, trash_retention_days))
+            .unwrap()
+    }
+
+    pub fn save_sync_interval(&self, sync_interval_minutes: u32) -> Setting {
+        self.db(|db| database::save_sync_interval(db, sync_interval_minutes))
+            .unwrap()
+    }
+
+    pub fn save_local_api_enabled(&self, local_api_enabled: bool) -> Setting {
+        self.db(|db| database::save_local_api_enabled(db, local_api_enabled))
+            .unwrap()
+    }
+
+    pub fn get_local_api_clients(&self) -> Vec<LocalApiClient> {
+        self.db(database::get_local_api_clients).unwrap()
+    }
+
+    pub fn local_api_client_allowed(&self, client_token: &str) -> bool {
+        self.db(|db| database::local_api_client_allowed(client_token, db))
+            .unwrap()
+    }
+
+    pub fn pair_local_api_client(&self, name: &str) -> Result<String, String> {
+        let client_token = encryption::generate_token();
+
+        self.db(|db| database::allow_local_api_client(name, &client_token, self.now(), db))
+            .map_err(|err| err.to_string())?;
+
+        Ok(client_token)
+    }
+
+    pub fn remove_local_api_client(&self, id: i32) -> bool {
+        self.db(|db| database::remove_local_api_client(id, db))
+            .unwrap()
+    }
+
+    // Importing a name that already exists replaces its secret and settings, keeping the old values as a revision
+    fn reimport_account(
+        &self,
+        id: i32,
+        account: &Account,
+        algorithm: &str,
+    ) -> Result<Account, String> {
+        if totp(
+            &account.secret,
+            account.otp_digits as u32,
+            account.totp_step as u64,
+            0,
+        )
+        .is_none()
+        {
+            return Err("Invalid 2FA Secret".to_string());
+        }
+
+        let existing = self.get_account(id as u32).ok_or("Invalid account id")?;
+        let encryption_secret = self.data_key()?.encrypt(&account.secret)?;
+
+        let now = self.now();
+        let account = self
+            .db(|db| {
+                database::update_existing_account(
+                    &id,
+                    &existing.name,
+                    &encryption_secret,
+                    account.otp_digits,
+                    account.totp_step,
+                    &existing.colour,
+                    algorithm,
+                    RevisionOrigin::IMPORT,
+                    Some(now),
+                    now,
+                    db,
+                )
+            })
+            .map_err(|err| err.to_string())?;
+
+        self.queue_sync_operation(&account.id, OutboxOperation::UPDATE);
+
+        Ok(account)
+    }
+
+    fn account_name_exists(&self, name: &str) -> bool {
+        self.db(|db| database::account_name_exists(name, db))
+            .unwrap()
+    }
+
+    fn queue_sync_operation(&self, account_id: &i32, operation: OutboxOperation) {
+        self.db(|db| database::queue_outbox_entry(account_id, operation, self.now(), db))
+            .unwrap();
+    }
+}
+
+// Both codes and the window come from the one timestamp
+pub fn generate_one_time_password(
+    account: &Account,
+    secret: &str,
+    timestamp: u64,
+) -> Result<OneTimePassword, String> {
+    let period = account.totp_step.max(1) as u64;
+    let counter = timestamp / period;
+    let period_start = counter * period;
+
+    Ok(OneTimePassword {
+        code: generate_code(account, secret, counter)?,
+        next_code: generate_code(account, secret, counter + 1)?,
+        period,
+        period_start,
+        seconds_remaining: period_start + period - timestamp,
+    })
+}
+
+fn generate_code(account: &Account, secret: &str, counter: u64) -> Result<String, String> {
+    let digits = account.otp_digits as u32;
+    let otp = match &account.algorithm {
+        Some(algorithm) => hotp_override(secret, digits, counter, algorithm.to_hotp_algorithm()),
+        None => hotp(secret, digits, counter),
+    };
+
+    otp.map(|otp| format!("{:0width$}", otp, width = digits as usize))
+        .ok_or_else(|| "Failed to generate OTP".to_string())
+}
+
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut name = name.chars();
+
+    query
+        .chars()
+        .all(|letter| name.any(|candidate| candidate == letter))
+}
+
+fn redact_sync_account(sync_account: SyncAccount) -> SyncAccount {
+    SyncAccount {
+        password: "".to_string(),
+        token: None,
+        passphrase: None,
+        ..sync_account
+    }
+}
+
+#[cfg(test)]
+#[path = "./vault_test.rs"]
+mod tests;
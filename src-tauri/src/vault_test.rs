@@ -0,0 +1,432 @@
+use crate::database::{Account, AccountAlgorithm};
+use crate::database::{OutboxOperation, RevisionOrigin, SyncAccount, SyncAuthType, SyncType};
+use crate::test_support::FixedClock;
+use crate::vault::{generate_one_time_password, OneTimePassword, Vault};
+use crate::{database, test_support};
+use std::fs;
+use std::path::PathBuf;
+
+const TEST_SECRET: &str = "JBSWY3DPEHPK3PXP";
+const TEST_TIME: u64 = 1_700_000_000;
+
+#[test]
+fn test_clock_is_injected() {
+    let vault = test_vault("clock");
+
+    assert_eq!(TEST_TIME, vault.now());
+}
+
+#[test]
+fn test_trash_and_revisions_use_vault_clock() {
+    let vault = test_vault("clock_trash");
+    let account = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    vault
+        .edit_account(account.id, "GitHub Work", 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    assert_eq!(
+        TEST_TIME,
+        vault.get_account_revisions(account.id)[0].created_at
+    );
+
+    // Only inside the retention window when the cutoff comes from the same clock
+    assert_eq!(true, vault.delete_account(account.id as u32));
+    assert_eq!(Some(TEST_TIME), vault.get_trashed_accounts()[0].deleted_at);
+}
+
+#[test]
+fn test_create_account_encrypts_secret() {
+    let vault = test_vault("create");
+
+    let account = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    assert_ne!(TEST_SECRET, account.secret);
+    assert_eq!(
+        TEST_SECRET,
+        vault.data_key().unwrap().decrypt(&account.secret).unwrap()
+    );
+    assert_eq!(true, vault.get_one_time_password(account.id as u32).is_ok());
+}
+
+#[test]
+fn test_one_time_password_uses_vault_clock() {
+    let vault = test_vault("one_time_password");
+
+    let account = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    let otp = vault.get_one_time_password(account.id as u32).unwrap();
+
+    assert_eq!(6, otp.code.len());
+    assert_eq!(30, otp.period);
+    assert_eq!(TEST_TIME - TEST_TIME % 30, otp.period_start);
+    assert_eq!(30 - TEST_TIME % 30, otp.seconds_remaining);
+    assert_ne!(otp.code, otp.next_code);
+    assert_eq!(
+        Err("Failed to generate OTP".to_string()),
+        vault.get_one_time_password(42)
+    );
+}
+
+#[test]
+fn test_codes_for_accounts() {
+    let vault = test_vault("codes");
+
+    let github = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    let gitlab = vault
+        .create_account("GitLab", TEST_SECRET, 8, 60, "ff0000", "SHA256")
+        .unwrap();
+    let trashed = vault
+        .create_account("Trashed", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    vault.delete_account(trashed.id as u32);
+
+    let codes = vault
+        .get_codes_for_accounts(vec![gitlab.id, github.id, trashed.id, 42])
+        .unwrap();
+
+    assert_eq!(2, codes.len());
+    assert_eq!(github.id, codes[0].account_id);
+    assert_eq!(
+        Some(vault.get_one_time_password(github.id as u32).unwrap()),
+        codes[0].otp
+    );
+    assert_eq!(gitlab.id, codes[1].account_id);
+
+    let gitlab_otp = codes[1].otp.as_ref().unwrap();
+    assert_eq!(8, gitlab_otp.code.len());
+    assert_eq!(60 - TEST_TIME % 60, gitlab_otp.seconds_remaining);
+}
+
+#[test]
+fn test_codes_for_accounts_reports_corrupt_secret() {
+    let vault = test_vault("codes_corrupt");
+
+    let github = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    let gitlab = vault
+        .create_account("GitLab", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    vault
+        .db(|db| {
+            db.execute(
+                "UPDATE accounts SET secret = ? WHERE id = ?",
+                ("A".repeat(32), gitlab.id),
+            )
+        })
+        .unwrap();
+
+    let codes = vault
+        .get_codes_for_accounts(vec![github.id, gitlab.id])
+        .unwrap();
+
+    assert_eq!(2, codes.len());
+    assert_eq!(github.id, codes[0].account_id);
+    assert_eq!(true, codes[0].otp.is_some());
+    assert_eq!(None, codes[0].error);
+    assert_eq!(gitlab.id, codes[1].account_id);
+    assert_eq!(None, codes[1].otp);
+    assert_eq!(Some("Could not decrypt value".to_string()), codes[1].error);
+}
+
+#[test]
+fn test_codes_for_accounts_reports_key_error() {
+    let vault = test_vault("codes_key_error");
+    fs::write(vault.key_directory().join("validator"), [1u8; 16]).unwrap();
+
+    let result = vault.get_codes_for_accounts(vec![1]);
+
+    assert_eq!(
+        Err("Key file should be 32 bytes but is 16".to_string()),
+        result.map(|_| ())
+    );
+}
+
+#[test]
+fn test_generate_one_time_password_rfc_vectors() {
+    let account = Account {
+        id: 1,
+        name: "RFC 6238".to_string(),
+        secret: "".to_string(),
+        totp_step: 30,
+        otp_digits: 8,
+        colour: "ff0000".to_string(),
+        algorithm: Some(AccountAlgorithm::SHA1),
+        external_id: None,
+        external_last_updated: None,
+        external_hash: None,
+        deleted_at: None,
+        sync_account_id: None,
+    };
+    let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    assert_eq!(
+        Ok(OneTimePassword {
+            code: "07081804".to_string(),
+            next_code: "14050471".to_string(),
+            period: 30,
+            period_start: 1111111080,
+            seconds_remaining: 1,
+        }),
+        generate_one_time_password(&account, secret, 1111111109)
+    );
+
+    let otp = generate_one_time_password(&account, secret, 59).unwrap();
+    assert_eq!("94287082", otp.code);
+    assert_eq!(1, otp.seconds_remaining);
+
+    let otp = generate_one_time_password(&account, secret, 1111111110).unwrap();
+    assert_eq!("14050471", otp.code);
+    assert_eq!(30, otp.seconds_remaining);
+}
+
+#[test]
+fn test_create_account_rejects_duplicate_name() {
+    let vault = test_vault("duplicate");
+
+    vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    let result = vault.create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1");
+
+    assert_eq!(
+        Err("Account already exists: GitHub".to_string()),
+        result.map(|_| ())
+    );
+}
+
+#[test]
+fn test_create_account_rejects_invalid_secret() {
+    let vault = test_vault("invalid_secret");
+
+    let result = vault.create_account("GitHub", "not base32!", 6, 30, "ff0000", "SHA1");
+
+    assert_eq!(Err("Invalid 2FA Secret".to_string()), result.map(|_| ()));
+    assert_eq!(0, vault.get_all_accounts("").len());
+}
+
+#[test]
+fn test_changes_queued_for_sync() {
+    let vault = test_vault("queue");
+    let (sync_account, _) = vault
+        .save_sync_account(sync_account(0, "password"))
+        .unwrap();
+
+    let account = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    assert_eq!(Some(sync_account.id), account.sync_account_id);
+
+    vault
+        .edit_account(account.id, "GitHub Work", 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    let entries = vault
+        .db(|db| database::get_outbox_entries(&sync_account.id, db))
+        .unwrap();
+
+    // The create has not been sent yet, so the edit only refreshes it
+    assert_eq!(1, entries.len());
+    assert_eq!(OutboxOperation::CREATE, entries[0].operation);
+}
+
+#[test]
+fn test_edit_account_marks_local_change() {
+    let vault = test_vault("edit_marks_change");
+    let account = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    vault
+        .edit_account(account.id, "GitHub Work", 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    let edited = vault
+        .db(|db| database::get_account_details_by_id(account.id as u32, db))
+        .unwrap();
+
+    assert_eq!("GitHub Work", edited.name);
+    assert_eq!(Some(TEST_TIME), edited.external_last_updated);
+}
+
+#[test]
+fn test_edit_missing_account() {
+    let vault = test_vault("edit_missing");
+
+    let result = vault.edit_account(42, "GitHub", 6, 30, "ff0000", "SHA1");
+
+    assert_eq!(Err("Invalid account id".to_string()), result.map(|_| ()));
+}
+
+#[test]
+fn test_restore_account_from_trash() {
+    let vault = test_vault("restore");
+    let account = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    assert_eq!(
+        Err("Account is not in the trash".to_string()),
+        vault.restore_account(account.id as u32)
+    );
+
+    assert_eq!(true, vault.delete_account(account.id as u32));
+    assert_eq!(0, vault.get_all_accounts("").len());
+    assert_eq!(1, vault.get_trashed_accounts().len());
+
+    assert_eq!(Ok(true), vault.restore_account(account.id as u32));
+    assert_eq!(1, vault.get_all_accounts("").len());
+}
+
+#[test]
+fn test_trashed_account_has_no_code() {
+    let vault = test_vault("trashed_code");
+    let account = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    assert_eq!(true, vault.delete_account(account.id as u32));
+    assert_eq!(None, vault.get_account(account.id as u32).map(|_| ()));
+    assert_eq!(
+        Err("Failed to generate OTP".to_string()),
+        vault.get_one_time_password(account.id as u32).map(|_| ())
+    );
+    assert_eq!(
+        Err(format!("No account matches: {}", account.id)),
+        vault.find_account(&account.id.to_string()).map(|_| ())
+    );
+}
+
+#[test]
+fn test_sync_accounts_are_redacted() {
+    let vault = test_vault("redacted");
+
+    let (created, updated) = vault
+        .save_sync_account(sync_account(0, "password"))
+        .unwrap();
+    assert_eq!(false, updated);
+
+    let main_account = vault.get_main_sync_account().unwrap();
+    assert_eq!(created.id, main_account.id);
+    assert_eq!("", main_account.password);
+    assert_eq!("", vault.get_sync_accounts()[0].password);
+
+    let (_, updated) = vault
+        .save_sync_account(sync_account(created.id, "changed"))
+        .unwrap();
+    assert_eq!(true, updated);
+}
+
+#[test]
+fn test_export_accounts() {
+    let vault = test_vault("export");
+
+    vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    assert_eq!(
+        "otpauth://totp/GitHub?secret=JBSWY3DPEHPK3PXP&period=30&digits=6&algorithm=SHA1\n",
+        vault.export_accounts().unwrap()
+    );
+}
+
+#[test]
+fn test_find_account() {
+    let vault = test_vault("find");
+
+    let github = vault
+        .create_account("GitHub", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    vault
+        .create_account("GitHub Work", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+    let gitlab = vault
+        .create_account("GitLab", TEST_SECRET, 6, 30, "ff0000", "SHA1")
+        .unwrap();
+
+    assert_eq!(github.id, vault.find_account("github").unwrap().id);
+    assert_eq!(gitlab.id, vault.find_account("lab").unwrap().id);
+    assert_eq!(gitlab.id, vault.find_account("gtlb").unwrap().id);
+    assert_eq!(
+        gitlab.id,
+        vault.find_account(&gitlab.id.to_string()).unwrap().id
+    );
+    assert_eq!(
+        Err("Multiple accounts match: GitHub, GitHub Work, GitLab".to_string()),
+        vault.find_account("git").map(|_| ())
+    );
+    assert_eq!(
+        Err("No account matches: Bitbucket".to_string()),
+        vault.find_account("Bitbucket").map(|_| ())
+    );
+}
+
+#[test]
+fn test_import_url() {
+    let vault = test_vault("import");
+
+    let account = vault
+        .import_url(
+            "otpauth://totp/GitHub?secret=JBSWY3DPEHPK3PXP&digits=8&period=60&algorithm=SHA256",
+        )
+        .unwrap();
+
+    assert_eq!("GitHub", account.name);
+    assert_eq!(8, account.otp_digits);
+    assert_eq!(60, account.totp_step);
+    assert_eq!(
+        Err("Invalid OTP URL".to_string()),
+        vault.import_url("https://github.com").map(|_| ())
+    );
+}
+
+#[test]
+fn test_import_url_replaces_existing_account() {
+    let vault = test_vault("reimport");
+
+    let original = vault
+        .import_url("otpauth://totp/GitHub?secret=JBSWY3DPEHPK3PXP")
+        .unwrap();
+    let reimported = vault
+        .import_url("otpauth://totp/GitHub?secret=GEZDGNBVGY3TQOJQ&digits=8")
+        .unwrap();
+
+    assert_eq!(original.id, reimported.id);
+    assert_eq!(8, reimported.otp_digits);
+    assert_eq!(1, vault.get_all_accounts("").len());
+
+    let revisions = vault.get_account_revisions(original.id);
+
+    assert_eq!(1, revisions.len());
+    assert_eq!(RevisionOrigin::IMPORT, revisions[0].origin);
+    assert_eq!(6, revisions[0].otp_digits);
+}
+
+fn sync_account(id: i32, password: &str) -> SyncAccount {
+    SyncAccount {
+        id,
+        username: "user".to_string(),
+        password: password.to_string(),
+        url: "https://sync.local".to_string(),
+        token: None,
+        sync_type: SyncType::PHOENIX,
+        passphrase: None,
+        auth_type: SyncAuthType::BASIC,
+    }
+}
+
+fn test_vault(name: &str) -> Vault {
+    test_support::test_vault(
+        PathBuf::from("./bin/vault").join(name),
+        Box::new(FixedClock(TEST_TIME)),
+    )
+}
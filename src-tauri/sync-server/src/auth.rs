@@ -0,0 +1,135 @@
+use crate::database::to_hex;
+use crate::{database, ApiError, ServerState};
+use axum::extract::State;
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TOKEN_LIFETIME_SECONDS: u64 = 60 * 60;
+const PASSWORD_ITERATIONS: u32 = 100_000;
+const SALT_LENGTH: usize = 16;
+// Unknown usernames are still hashed against this salt so they take as long as a wrong password
+const DUMMY_SALT: &str = "00000000000000000000000000000000";
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    sub: i32,
+    username: String,
+    exp: u64,
+}
+
+pub async fn login_check(
+    State(state): State<ServerState>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Json<Value>, ApiError> {
+    let user = {
+        let db = state.db.lock().unwrap();
+        database::get_user(&request.username, &db)?
+    };
+
+    let user = match user {
+        Some(user) if verify_password(&request.password, &user.salt, &user.password_hash) => user,
+        Some(_) => return Err(invalid_credentials()),
+        None => {
+            verify_password(&request.password, DUMMY_SALT, "");
+            return Err(invalid_credentials());
+        }
+    };
+
+    let claims = Claims {
+        sub: user.id,
+        username: request.username,
+        exp: current_timestamp() + TOKEN_LIFETIME_SECONDS,
+    };
+
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(state.secret.as_bytes()),
+    )
+    .map_err(|_| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Could not issue token"))?;
+
+    Ok(Json(json!({ "token": token })))
+}
+
+pub fn authenticate(state: &ServerState, headers: &HeaderMap) -> Result<i32, ApiError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "JWT Token not found"))?;
+
+    match decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(state.secret.as_bytes()),
+        &Validation::default(),
+    ) {
+        Ok(data) => Ok(data.claims.sub),
+        Err(_) => Err(ApiError::new(StatusCode::UNAUTHORIZED, "Invalid JWT Token")),
+    }
+}
+
+pub fn add_user(username: &str, password: &str, db: &Connection) -> Result<i32, rusqlite::Error> {
+    let mut salt = [0u8; SALT_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = to_hex(&salt);
+
+    database::create_user(username, &hash_password(password, &salt), &salt, db)
+}
+
+fn hash_password(password: &str, salt: &str) -> String {
+    let mut hash = [0u8; 32];
+
+    pbkdf2_hmac::<Sha256>(
+        password.as_bytes(),
+        salt.as_bytes(),
+        PASSWORD_ITERATIONS,
+        &mut hash,
+    );
+
+    to_hex(&hash)
+}
+
+fn verify_password(password: &str, salt: &str, password_hash: &str) -> bool {
+    constant_time_eq(
+        hash_password(password, salt).as_bytes(),
+        password_hash.as_bytes(),
+    )
+}
+
+fn constant_time_eq(first: &[u8], second: &[u8]) -> bool {
+    if first.len() != second.len() {
+        return false;
+    }
+
+    first
+        .iter()
+        .zip(second)
+        .fold(0u8, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+fn invalid_credentials() -> ApiError {
+    ApiError::new(StatusCode::UNAUTHORIZED, "Invalid credentials.")
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Could not generate UNIX time")
+        .as_secs()
+}
@@ -0,0 +1,385 @@
+use rusqlite::{named_params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordPayload {
+    pub name: String,
+    pub secret: String,
+    #[serde(default)]
+    pub colour: String,
+    pub otp_digits: i32,
+    pub totp_step: i32,
+    pub totp_algorithm: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+    pub id: i32,
+    pub sync_hash: String,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VerboseRecord {
+    pub id: i32,
+    pub name: String,
+    pub secret: String,
+    pub colour: String,
+    pub totp_step: i32,
+    pub otp_digits: i32,
+    pub algorithm: Option<String>,
+    pub sync_hash: String,
+    pub updated_at: u64,
+}
+
+impl VerboseRecord {
+    pub fn to_record(&self) -> Record {
+        Record {
+            id: self.id,
+            sync_hash: self.sync_hash.clone(),
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestItem {
+    pub id: i32,
+    pub updated_at: u64,
+}
+
+pub struct User {
+    pub id: i32,
+    pub password_hash: String,
+    pub salt: String,
+}
+
+pub fn open(path: &str) -> Result<Connection, rusqlite::Error> {
+    let db = Connection::open(path)?;
+
+    db.pragma_update(None, "journal_mode", "WAL")?;
+    create_schema(&db)?;
+
+    Ok(db)
+}
+
+pub fn open_in_memory() -> Result<Connection, rusqlite::Error> {
+    let db = Connection::open_in_memory()?;
+
+    create_schema(&db)?;
+
+    Ok(db)
+}
+
+fn create_schema(db: &Connection) -> Result<(), rusqlite::Error> {
+    db.execute_batch(
+        "
+            CREATE TABLE IF NOT EXISTS users (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                username VARCHAR(255) NOT NULL UNIQUE,
+                password_hash TEXT NOT NULL,
+                salt TEXT NOT NULL,
+                revision INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS records (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                secret TEXT NOT NULL,
+                colour VARCHAR(6) NOT NULL,
+                totp_step INTEGER NOT NULL,
+                otp_digits INTEGER NOT NULL,
+                algorithm VARCHAR(10),
+                sync_hash TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                revision INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS records_user_revision ON records (user_id, revision);
+            CREATE TABLE IF NOT EXISTS removed_records (
+                id INTEGER PRIMARY KEY,
+                user_id INTEGER NOT NULL,
+                revision INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS idempotency_keys (
+                user_id INTEGER NOT NULL,
+                idempotency_key VARCHAR(64) NOT NULL,
+                record_id INTEGER NOT NULL,
+                PRIMARY KEY (user_id, idempotency_key)
+            );
+        ",
+    )
+}
+
+pub fn create_user(
+    username: &str,
+    password_hash: &str,
+    salt: &str,
+    db: &Connection,
+) -> Result<i32, rusqlite::Error> {
+    db.execute(
+        "INSERT INTO users (username, password_hash, salt) VALUES (@username, @password_hash, @salt)",
+        named_params! {"@username": username, "@password_hash": password_hash, "@salt": salt},
+    )?;
+
+    Ok(db.last_insert_rowid() as i32)
+}
+
+pub fn get_user(username: &str, db: &Connection) -> Result<Option<User>, rusqlite::Error> {
+    db.query_row(
+        "SELECT id, password_hash, salt FROM users WHERE username = ?",
+        [username],
+        |row| {
+            Ok(User {
+                id: row.get("id")?,
+                password_hash: row.get("password_hash")?,
+                salt: row.get("salt")?,
+            })
+        },
+    )
+    .optional()
+}
+
+pub fn current_revision(user_id: &i32, db: &Connection) -> Result<u64, rusqlite::Error> {
+    db.query_row(
+        "SELECT revision FROM users WHERE id = ?",
+        [user_id],
+        |row| row.get(0),
+    )
+}
+
+fn next_revision(user_id: &i32, db: &Connection) -> Result<u64, rusqlite::Error> {
+    db.query_row(
+        "UPDATE users SET revision = revision + 1 WHERE id = ? RETURNING revision",
+        [user_id],
+        |row| row.get(0),
+    )
+}
+
+pub fn get_manifest(
+    user_id: &i32,
+    since: u64,
+    after_id: i32,
+    limit: i64,
+    db: &Connection,
+) -> Result<Vec<ManifestItem>, rusqlite::Error> {
+    let mut statement = db.prepare("SELECT id, updated_at FROM records WHERE user_id = @user_id AND revision > @since AND id > @after_id ORDER BY id ASC LIMIT @limit")?;
+    let mut rows = statement.query(named_params! {"@user_id": user_id, "@since": since, "@after_id": after_id, "@limit": limit})?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        items.push(ManifestItem {
+            id: row.get("id")?,
+            updated_at: row.get("updated_at")?,
+        });
+    }
+
+    Ok(items)
+}
+
+pub fn get_removed_records(
+    user_id: &i32,
+    since: u64,
+    db: &Connection,
+) -> Result<Vec<i32>, rusqlite::Error> {
+    let mut statement = db.prepare(
+        "SELECT id FROM removed_records WHERE user_id = ? AND revision > ? ORDER BY id ASC",
+    )?;
+    let mut rows = statement.query(rusqlite::params![user_id, since])?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        items.push(row.get("id")?);
+    }
+
+    Ok(items)
+}
+
+pub fn get_record(
+    user_id: &i32,
+    id: &i32,
+    db: &Connection,
+) -> Result<Option<VerboseRecord>, rusqlite::Error> {
+    db.query_row(
+        "SELECT id, name, secret, colour, totp_step, otp_digits, algorithm, sync_hash, updated_at FROM records WHERE id = ? AND user_id = ?",
+        [id, user_id],
+        |row| {
+            Ok(VerboseRecord {
+                id: row.get("id")?,
+                name: row.get("name")?,
+                secret: row.get("secret")?,
+                colour: row.get("colour")?,
+                totp_step: row.get("totp_step")?,
+                otp_digits: row.get("otp_digits")?,
+                algorithm: row.get("algorithm")?,
+                sync_hash: row.get("sync_hash")?,
+                updated_at: row.get("updated_at")?,
+            })
+        },
+    )
+    .optional()
+}
+
+pub fn create_record(
+    user_id: &i32,
+    payload: &RecordPayload,
+    idempotency_key: Option<&str>,
+    db: &mut Connection,
+) -> Result<Record, rusqlite::Error> {
+    let tx = db.transaction()?;
+
+    if let Some(idempotency_key) = idempotency_key {
+        let existing: Option<i32> = tx
+            .query_row(
+                "SELECT record_id FROM idempotency_keys WHERE user_id = ? AND idempotency_key = ?",
+                rusqlite::params![user_id, idempotency_key],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        if let Some(record_id) = existing {
+            if let Some(record) = get_record(user_id, &record_id, &tx)? {
+                return Ok(record.to_record());
+            }
+        }
+    }
+
+    let revision = next_revision(user_id, &tx)?;
+    let sync_hash = payload_hash(payload);
+    let updated_at = current_timestamp();
+
+    tx.execute(
+        "INSERT INTO records (user_id, name, secret, colour, totp_step, otp_digits, algorithm, sync_hash, updated_at, revision) VALUES (@user_id, @name, @secret, @colour, @totp_step, @otp_digits, @algorithm, @sync_hash, @updated_at, @revision)",
+        named_params! {
+            "@user_id": user_id,
+            "@name": payload.name,
+            "@secret": payload.secret,
+            "@colour": payload.colour,
+            "@totp_step": payload.totp_step,
+            "@otp_digits": payload.otp_digits,
+            "@algorithm": payload.totp_algorithm,
+            "@sync_hash": sync_hash,
+            "@updated_at": updated_at,
+            "@revision": revision,
+        },
+    )?;
+    let id = tx.last_insert_rowid() as i32;
+
+    if let Some(idempotency_key) = idempotency_key {
+        tx.execute(
+            "INSERT INTO idempotency_keys (user_id, idempotency_key, record_id) VALUES (?, ?, ?)",
+            rusqlite::params![user_id, idempotency_key, id],
+        )?;
+    }
+
+    tx.commit()?;
+
+    Ok(Record {
+        id,
+        sync_hash,
+        updated_at,
+    })
+}
+
+pub fn update_record(
+    user_id: &i32,
+    id: &i32,
+    payload: &RecordPayload,
+    db: &mut Connection,
+) -> Result<Option<Record>, rusqlite::Error> {
+    let tx = db.transaction()?;
+
+    let existing = match get_record(user_id, id, &tx)? {
+        Some(existing) => existing,
+        None => return Ok(None),
+    };
+
+    // Clients compare timestamps to spot changes, so two writes within a second still have to move it forward
+    let revision = next_revision(user_id, &tx)?;
+    let sync_hash = payload_hash(payload);
+    let updated_at = current_timestamp().max(existing.updated_at + 1);
+
+    tx.execute(
+        "UPDATE records SET name = @name, secret = @secret, colour = @colour, totp_step = @totp_step, otp_digits = @otp_digits, algorithm = @algorithm, sync_hash = @sync_hash, updated_at = @updated_at, revision = @revision WHERE id = @id",
+        named_params! {
+            "@id": id,
+            "@name": payload.name,
+            "@secret": payload.secret,
+            "@colour": payload.colour,
+            "@totp_step": payload.totp_step,
+            "@otp_digits": payload.otp_digits,
+            "@algorithm": payload.totp_algorithm,
+            "@sync_hash": sync_hash,
+            "@updated_at": updated_at,
+            "@revision": revision,
+        },
+    )?;
+
+    tx.commit()?;
+
+    Ok(Some(Record {
+        id: *id,
+        sync_hash,
+        updated_at,
+    }))
+}
+
+// Leaves a tombstone for delta manifests
+pub fn remove_record(
+    user_id: &i32,
+    id: &i32,
+    db: &mut Connection,
+) -> Result<bool, rusqlite::Error> {
+    let tx = db.transaction()?;
+
+    if get_record(user_id, id, &tx)?.is_none() {
+        return Ok(false);
+    }
+
+    let revision = next_revision(user_id, &tx)?;
+
+    tx.execute("DELETE FROM records WHERE id = ?", [id])?;
+    tx.execute(
+        "INSERT INTO removed_records (id, user_id, revision) VALUES (?, ?, ?)",
+        rusqlite::params![id, user_id, revision],
+    )?;
+
+    tx.commit()?;
+
+    Ok(true)
+}
+
+fn payload_hash(payload: &RecordPayload) -> String {
+    let mut hasher = Sha256::new();
+
+    hasher.update(payload.name.as_bytes());
+    hasher.update(payload.secret.as_bytes());
+    hasher.update(payload.colour.as_bytes());
+    hasher.update(payload.totp_step.to_be_bytes());
+    hasher.update(payload.otp_digits.to_be_bytes());
+    hasher.update(
+        payload
+            .totp_algorithm
+            .clone()
+            .unwrap_or_default()
+            .as_bytes(),
+    );
+
+    to_hex(&hasher.finalize())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Could not generate UNIX time")
+        .as_secs()
+}
@@ -0,0 +1,93 @@
+mod auth;
+pub mod database;
+mod records;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rusqlite::Connection;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+pub use auth::add_user;
+
+pub const API_VERSION: i8 = 2;
+pub const MINIMUM_API_VERSION: i8 = 1;
+
+#[derive(Clone)]
+pub struct ServerState {
+    db: Arc<Mutex<Connection>>,
+    secret: Arc<String>,
+}
+
+impl ServerState {
+    pub fn new(db: Connection, secret: &str) -> ServerState {
+        ServerState {
+            db: Arc::new(Mutex::new(db)),
+            secret: Arc::new(secret.to_string()),
+        }
+    }
+
+    pub fn add_user(&self, username: &str, password: &str) -> Result<i32, rusqlite::Error> {
+        add_user(username, password, &self.db.lock().unwrap())
+    }
+}
+
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: &str) -> ApiError {
+        ApiError {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({ "code": self.status.as_u16(), "message": self.message });
+
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<rusqlite::Error> for ApiError {
+    fn from(error: rusqlite::Error) -> ApiError {
+        eprintln!("Database error: {}", error);
+
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+    }
+}
+
+pub fn router(state: ServerState) -> Router {
+    Router::new()
+        .route("/api/login_check", post(auth::login_check))
+        .route("/api/capabilities", get(records::capabilities))
+        .route("/api/records", post(records::create_record))
+        .route("/api/records/manifest", get(records::manifest))
+        .route("/api/records/batch/fetch", post(records::fetch_batch))
+        .route("/api/records/batch/upsert", post(records::upsert_batch))
+        .route("/api/records/batch/delete", post(records::delete_batch))
+        .route(
+            "/api/records/:id",
+            get(records::get_record)
+                .put(records::update_record)
+                .delete(records::remove_record),
+        )
+        .with_state(state)
+}
+
+pub async fn serve(listener: TcpListener, state: ServerState) -> std::io::Result<()> {
+    axum::serve(listener, router(state)).await
+}
+
+#[cfg(test)]
+#[path = "./server_test.rs"]
+mod tests;
@@ -0,0 +1,72 @@
+use phoenix_sync_server::{database, serve, ServerState};
+use std::env;
+use std::process::exit;
+use tokio::net::TcpListener;
+
+const DEFAULT_DATABASE: &str = "phoenix-sync.db";
+const DEFAULT_ADDRESS: &str = "0.0.0.0:8080";
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let database_path =
+        env::var("PHOENIX_SYNC_DATABASE").unwrap_or_else(|_| DEFAULT_DATABASE.to_string());
+
+    let db = match database::open(&database_path) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Could not open database {}: {}", database_path, e);
+            exit(1);
+        }
+    };
+
+    match args.first().map(String::as_str) {
+        Some("add-user") => {
+            let (username, password) = match (args.get(1), args.get(2)) {
+                (Some(username), Some(password)) => (username, password),
+                _ => {
+                    eprintln!("Usage: phoenix-sync-server add-user <username> <password>");
+                    exit(1);
+                }
+            };
+
+            match phoenix_sync_server::add_user(username, password, &db) {
+                Ok(_) => println!("Created user {}", username),
+                Err(e) => {
+                    eprintln!("Could not create user: {}", e);
+                    exit(1);
+                }
+            }
+        }
+        None | Some("serve") => {
+            let secret = match env::var("PHOENIX_SYNC_SECRET") {
+                Ok(secret) if !secret.is_empty() => secret,
+                _ => {
+                    eprintln!("PHOENIX_SYNC_SECRET must be set to sign tokens");
+                    exit(1);
+                }
+            };
+            let address =
+                env::var("PHOENIX_SYNC_ADDRESS").unwrap_or_else(|_| DEFAULT_ADDRESS.to_string());
+
+            let listener = match TcpListener::bind(&address).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("Could not listen on {}: {}", address, e);
+                    exit(1);
+                }
+            };
+
+            println!("Phoenix sync server listening on {}", address);
+
+            if let Err(e) = serve(listener, ServerState::new(db, &secret)).await {
+                eprintln!("Server stopped: {}", e);
+                exit(1);
+            }
+        }
+        Some(command) => {
+            eprintln!("Unknown command {}, expected serve or add-user", command);
+            exit(1);
+        }
+    }
+}
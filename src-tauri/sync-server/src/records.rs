@@ -0,0 +1,271 @@
+use crate::auth::authenticate;
+use crate::database::RecordPayload;
+use crate::{database, ApiError, ServerState, API_VERSION, MINIMUM_API_VERSION};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const MANIFEST_PAGE_SIZE: i64 = 100;
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+#[derive(Deserialize)]
+pub struct ManifestQuery {
+    since: Option<String>,
+    page: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BatchIds {
+    ids: Vec<i32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchUpsertItem {
+    id: Option<i32>,
+    idempotency_key: Option<String>,
+    #[serde(flatten)]
+    payload: RecordPayload,
+}
+
+#[derive(Deserialize)]
+pub struct BatchUpsert {
+    records: Vec<BatchUpsertItem>,
+}
+
+#[derive(Serialize)]
+struct BatchItem<T> {
+    status: u16,
+    record: Option<T>,
+    error: Option<String>,
+}
+
+impl<T> BatchItem<T> {
+    fn success(status: StatusCode, record: Option<T>) -> BatchItem<T> {
+        BatchItem {
+            status: status.as_u16(),
+            record,
+            error: None,
+        }
+    }
+
+    fn not_found() -> BatchItem<T> {
+        BatchItem {
+            status: StatusCode::NOT_FOUND.as_u16(),
+            record: None,
+            error: Some("Record not found".to_string()),
+        }
+    }
+}
+
+pub async fn capabilities() -> Json<Value> {
+    Json(json!({
+        "version": API_VERSION,
+        "data": {
+            "minVersion": MINIMUM_API_VERSION,
+            "maxVersion": API_VERSION,
+            "capabilities": ["colour", "paginatedManifest", "batchRecords"],
+        }
+    }))
+}
+
+// Version 1 clients send no cursor or page and get everything at once
+pub async fn manifest(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Query(query): Query<ManifestQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let user_id = authenticate(&state, &headers)?;
+    let db = state.db.lock().unwrap();
+    let current_revision = database::current_revision(&user_id, &db)?;
+
+    let since = match &query.since {
+        Some(cursor) => match cursor.parse::<u64>() {
+            Ok(since) if since <= current_revision => since,
+            _ => {
+                return Err(ApiError::new(
+                    StatusCode::GONE,
+                    "Manifest cursor has expired",
+                ))
+            }
+        },
+        None => 0,
+    };
+
+    let (after_id, snapshot) = match &query.page {
+        Some(page) => parse_page_token(page)?,
+        None => (0, current_revision),
+    };
+
+    let limit = match query.since.is_some() || query.page.is_some() {
+        true => MANIFEST_PAGE_SIZE,
+        false => i64::MAX - 1,
+    };
+
+    let mut items = database::get_manifest(&user_id, since, after_id, limit + 1, &db)?;
+    let has_more = items.len() as i64 > limit;
+    items.truncate(limit as usize);
+
+    let removed = match query.since.is_some() && after_id == 0 {
+        true => database::get_removed_records(&user_id, since, &db)?,
+        false => vec![],
+    };
+
+    // The cursor is pinned to the first page so changes made while paging are listed again next time
+    let (next_page, cursor) = match (has_more, items.last()) {
+        (true, Some(last)) => (Some(format!("{}.{}", last.id, snapshot)), None),
+        _ => (None, Some(snapshot.to_string())),
+    };
+
+    Ok(Json(json!({
+        "version": API_VERSION,
+        "data": items,
+        "removed": removed,
+        "nextPage": next_page,
+        "cursor": cursor,
+    })))
+}
+
+pub async fn create_record(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(payload): Json<RecordPayload>,
+) -> Result<Json<Value>, ApiError> {
+    let user_id = authenticate(&state, &headers)?;
+    let idempotency_key = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    let mut db = state.db.lock().unwrap();
+    let record = database::create_record(&user_id, &payload, idempotency_key, &mut db)?;
+
+    Ok(Json(json!({ "version": API_VERSION, "data": record })))
+}
+
+pub async fn get_record(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<Json<Value>, ApiError> {
+    let user_id = authenticate(&state, &headers)?;
+    let db = state.db.lock().unwrap();
+
+    match database::get_record(&user_id, &id, &db)? {
+        Some(record) => Ok(Json(json!({ "version": API_VERSION, "data": record }))),
+        None => Err(record_not_found()),
+    }
+}
+
+pub async fn update_record(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<RecordPayload>,
+) -> Result<Json<Value>, ApiError> {
+    let user_id = authenticate(&state, &headers)?;
+    let mut db = state.db.lock().unwrap();
+
+    match database::update_record(&user_id, &id, &payload, &mut db)? {
+        Some(record) => Ok(Json(json!({ "version": API_VERSION, "data": record }))),
+        None => Err(record_not_found()),
+    }
+}
+
+pub async fn remove_record(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = authenticate(&state, &headers)?;
+    let mut db = state.db.lock().unwrap();
+
+    match database::remove_record(&user_id, &id, &mut db)? {
+        true => Ok(StatusCode::NO_CONTENT),
+        false => Err(record_not_found()),
+    }
+}
+
+pub async fn fetch_batch(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(request): Json<BatchIds>,
+) -> Result<Json<Value>, ApiError> {
+    let user_id = authenticate(&state, &headers)?;
+    let db = state.db.lock().unwrap();
+    let mut items = Vec::with_capacity(request.ids.len());
+
+    for id in request.ids {
+        items.push(match database::get_record(&user_id, &id, &db)? {
+            Some(record) => BatchItem::success(StatusCode::OK, Some(record)),
+            None => BatchItem::not_found(),
+        });
+    }
+
+    Ok(Json(json!({ "version": API_VERSION, "data": items })))
+}
+
+pub async fn upsert_batch(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(request): Json<BatchUpsert>,
+) -> Result<Json<Value>, ApiError> {
+    let user_id = authenticate(&state, &headers)?;
+    let mut db = state.db.lock().unwrap();
+    let mut items = Vec::with_capacity(request.records.len());
+
+    for item in request.records {
+        items.push(match item.id {
+            Some(id) => match database::update_record(&user_id, &id, &item.payload, &mut db)? {
+                Some(record) => BatchItem::success(StatusCode::OK, Some(record)),
+                None => BatchItem::not_found(),
+            },
+            None => {
+                let record = database::create_record(
+                    &user_id,
+                    &item.payload,
+                    item.idempotency_key.as_deref(),
+                    &mut db,
+                )?;
+
+                BatchItem::success(StatusCode::CREATED, Some(record))
+            }
+        });
+    }
+
+    Ok(Json(json!({ "version": API_VERSION, "data": items })))
+}
+
+pub async fn delete_batch(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Json(request): Json<BatchIds>,
+) -> Result<Json<Value>, ApiError> {
+    let user_id = authenticate(&state, &headers)?;
+    let mut db = state.db.lock().unwrap();
+    let mut items: Vec<BatchItem<Value>> = Vec::with_capacity(request.ids.len());
+
+    for id in request.ids {
+        items.push(match database::remove_record(&user_id, &id, &mut db)? {
+            true => BatchItem::success(StatusCode::NO_CONTENT, None),
+            false => BatchItem::not_found(),
+        });
+    }
+
+    Ok(Json(json!({ "version": API_VERSION, "data": items })))
+}
+
+fn parse_page_token(page: &str) -> Result<(i32, u64), ApiError> {
+    let invalid_page = || ApiError::new(StatusCode::BAD_REQUEST, "Invalid page token");
+    let (after_id, snapshot) = page.split_once('.').ok_or_else(invalid_page)?;
+
+    match (after_id.parse::<i32>(), snapshot.parse::<u64>()) {
+        (Ok(after_id), Ok(snapshot)) => Ok((after_id, snapshot)),
+        _ => Err(invalid_page()),
+    }
+}
+
+fn record_not_found() -> ApiError {
+    ApiError::new(StatusCode::NOT_FOUND, "Record not found")
+}
@@ -0,0 +1,399 @@
+use crate::{database, serve, ServerState};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+const USERNAME: &str = "test@phoenix.local";
+const PASSWORD: &str = "correct horse battery staple";
+
+async fn start_server() -> (String, ServerState) {
+    let state = ServerState::new(database::open_in_memory().unwrap(), "test-secret");
+    state.add_user(USERNAME, PASSWORD).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(serve(listener, state.clone()));
+
+    (url, state)
+}
+
+async fn login(client: &Client, url: &str, username: &str, password: &str) -> String {
+    let response: Value = client
+        .post(format!("{}/api/login_check", url))
+        .json(&json!({ "username": username, "password": password }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    response["token"].as_str().unwrap().to_string()
+}
+
+fn record_payload(name: &str) -> Value {
+    json!({
+        "name": name,
+        "secret": "encrypted-secret",
+        "colour": "FFFFFF",
+        "otpDigits": 6,
+        "totpStep": 30,
+        "totpAlgorithm": "SHA1",
+    })
+}
+
+async fn create_record(client: &Client, url: &str, token: &str, name: &str) -> i32 {
+    let response: Value = client
+        .post(format!("{}/api/records", url))
+        .bearer_auth(token)
+        .json(&record_payload(name))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    response["data"]["id"].as_i64().unwrap() as i32
+}
+
+async fn get_json(client: &Client, url: String, token: &str) -> (StatusCode, Value) {
+    let response = client.get(url).bearer_auth(token).send().await.unwrap();
+    let status = response.status();
+
+    (status, response.json().await.unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_login_check() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+    assert_eq!(3, token.split('.').count());
+
+    let response = client
+        .post(format!("{}/api/login_check", url))
+        .json(&json!({ "username": USERNAME, "password": "wrong" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+}
+
+#[tokio::test]
+async fn test_login_check_unknown_user_matches_wrong_password() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+
+    let mut responses = Vec::new();
+    for username in [USERNAME, "unknown@phoenix.local"] {
+        let response = client
+            .post(format!("{}/api/login_check", url))
+            .json(&json!({ "username": username, "password": "wrong" }))
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        responses.push((status, response.json::<Value>().await.unwrap()));
+    }
+
+    assert_eq!(StatusCode::UNAUTHORIZED, responses[0].0);
+    assert_eq!(responses[0], responses[1]);
+}
+
+#[tokio::test]
+async fn test_requests_require_token() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+
+    let (status, _) = get_json(&client, format!("{}/api/records/manifest", url), "invalid").await;
+    assert_eq!(StatusCode::UNAUTHORIZED, status);
+
+    let response = client
+        .get(format!("{}/api/records/manifest", url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+}
+
+#[tokio::test]
+async fn test_capabilities() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+
+    let (status, response) = get_json(&client, format!("{}/api/capabilities", url), &token).await;
+
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!(2, response["version"]);
+    assert_eq!(1, response["data"]["minVersion"]);
+    assert_eq!(2, response["data"]["maxVersion"]);
+    assert_eq!(
+        json!(["colour", "paginatedManifest", "batchRecords"]),
+        response["data"]["capabilities"]
+    );
+}
+
+#[tokio::test]
+async fn test_record_lifecycle() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+
+    let id = create_record(&client, &url, &token, "Test Account").await;
+
+    let (status, record) = get_json(&client, format!("{}/api/records/{}", url, id), &token).await;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!("Test Account", record["data"]["name"]);
+    assert_eq!("SHA1", record["data"]["algorithm"]);
+    let original_hash = record["data"]["syncHash"].clone();
+
+    let updated: Value = client
+        .put(format!("{}/api/records/{}", url, id))
+        .bearer_auth(&token)
+        .json(&record_payload("Renamed Account"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(id, updated["data"]["id"].as_i64().unwrap() as i32);
+    assert_ne!(original_hash, updated["data"]["syncHash"]);
+
+    let response = client
+        .delete(format!("{}/api/records/{}", url, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let (status, _) = get_json(&client, format!("{}/api/records/{}", url, id), &token).await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+}
+
+#[tokio::test]
+async fn test_create_record_replays_idempotency_key() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+
+    let mut ids = vec![];
+
+    for _ in 0..2 {
+        let response: Value = client
+            .post(format!("{}/api/records", url))
+            .bearer_auth(&token)
+            .header("Idempotency-Key", "0123456789abcdef")
+            .json(&record_payload("Test Account"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        ids.push(response["data"]["id"].clone());
+    }
+
+    assert_eq!(ids[0], ids[1]);
+
+    let (_, manifest) = get_json(&client, format!("{}/api/records/manifest", url), &token).await;
+    assert_eq!(1, manifest["data"].as_array().unwrap().len());
+}
+
+#[tokio::test]
+async fn test_records_scoped_to_user() {
+    let (url, state) = start_server().await;
+    let client = Client::new();
+    state.add_user("other@phoenix.local", PASSWORD).unwrap();
+
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+    let other_token = login(&client, &url, "other@phoenix.local", PASSWORD).await;
+    let id = create_record(&client, &url, &token, "Test Account").await;
+
+    let (status, _) = get_json(&client, format!("{}/api/records/{}", url, id), &other_token).await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+
+    let (_, manifest) = get_json(
+        &client,
+        format!("{}/api/records/manifest", url),
+        &other_token,
+    )
+    .await;
+    assert_eq!(0, manifest["data"].as_array().unwrap().len());
+}
+
+#[tokio::test]
+async fn test_manifest_delta_lists_changes_and_removals() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+
+    let kept = create_record(&client, &url, &token, "Kept").await;
+    let removed = create_record(&client, &url, &token, "Removed").await;
+
+    let (_, full) = get_json(&client, format!("{}/api/records/manifest", url), &token).await;
+    assert_eq!(2, full["data"].as_array().unwrap().len());
+    assert_eq!(Value::Null, full["nextPage"]);
+    let cursor = full["cursor"].as_str().unwrap().to_string();
+
+    let (_, unchanged) = get_json(
+        &client,
+        format!("{}/api/records/manifest?since={}", url, cursor),
+        &token,
+    )
+    .await;
+    assert_eq!(0, unchanged["data"].as_array().unwrap().len());
+    assert_eq!(0, unchanged["removed"].as_array().unwrap().len());
+
+    client
+        .delete(format!("{}/api/records/{}", url, removed))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let added = create_record(&client, &url, &token, "Added").await;
+
+    let (_, delta) = get_json(
+        &client,
+        format!("{}/api/records/manifest?since={}", url, cursor),
+        &token,
+    )
+    .await;
+    let changed: Vec<i64> = delta["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["id"].as_i64().unwrap())
+        .collect();
+
+    assert_eq!(vec![added as i64], changed);
+    assert_eq!(json!([removed]), delta["removed"]);
+    assert_ne!(cursor, delta["cursor"].as_str().unwrap());
+    assert!(!changed.contains(&(kept as i64)));
+}
+
+#[tokio::test]
+async fn test_manifest_pages_large_listings() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+
+    for index in 0..150 {
+        create_record(&client, &url, &token, &format!("Account {}", index)).await;
+    }
+
+    let (_, first) = get_json(
+        &client,
+        format!("{}/api/records/manifest?since=0", url),
+        &token,
+    )
+    .await;
+    assert_eq!(100, first["data"].as_array().unwrap().len());
+    assert_eq!(Value::Null, first["cursor"]);
+    let page = first["nextPage"].as_str().unwrap().to_string();
+
+    let (_, second) = get_json(
+        &client,
+        format!("{}/api/records/manifest?since=0&page={}", url, page),
+        &token,
+    )
+    .await;
+    assert_eq!(50, second["data"].as_array().unwrap().len());
+    assert_eq!(Value::Null, second["nextPage"]);
+    assert_eq!("150", second["cursor"]);
+}
+
+#[tokio::test]
+async fn test_manifest_rejects_unknown_cursor() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+
+    let (status, _) = get_json(
+        &client,
+        format!("{}/api/records/manifest?since=99", url),
+        &token,
+    )
+    .await;
+    assert_eq!(StatusCode::GONE, status);
+
+    let (status, _) = get_json(
+        &client,
+        format!("{}/api/records/manifest?since=invalid", url),
+        &token,
+    )
+    .await;
+    assert_eq!(StatusCode::GONE, status);
+}
+
+#[tokio::test]
+async fn test_batch_records_report_per_item_results() {
+    let (url, _) = start_server().await;
+    let client = Client::new();
+    let token = login(&client, &url, USERNAME, PASSWORD).await;
+    let existing = create_record(&client, &url, &token, "Existing").await;
+
+    let mut new_record = record_payload("New");
+    new_record["idempotencyKey"] = json!("fedcba9876543210");
+    let mut updated_record = record_payload("Updated");
+    updated_record["id"] = json!(existing);
+    let mut missing_record = record_payload("Missing");
+    missing_record["id"] = json!(9999);
+
+    let upserted: Value = client
+        .post(format!("{}/api/records/batch/upsert", url))
+        .bearer_auth(&token)
+        .json(&json!({ "records": [new_record, updated_record, missing_record] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let items = upserted["data"].as_array().unwrap();
+
+    assert_eq!(3, items.len());
+    assert_eq!(201, items[0]["status"]);
+    assert_eq!(200, items[1]["status"]);
+    assert_eq!(existing as i64, items[1]["record"]["id"].as_i64().unwrap());
+    assert_eq!(404, items[2]["status"]);
+    assert_eq!("Record not found", items[2]["error"]);
+    let created = items[0]["record"]["id"].as_i64().unwrap();
+
+    let fetched: Value = client
+        .post(format!("{}/api/records/batch/fetch", url))
+        .bearer_auth(&token)
+        .json(&json!({ "ids": [existing, 9999, created] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let items = fetched["data"].as_array().unwrap();
+
+    assert_eq!("Updated", items[0]["record"]["name"]);
+    assert_eq!(404, items[1]["status"]);
+    assert_eq!("New", items[2]["record"]["name"]);
+
+    let deleted: Value = client
+        .post(format!("{}/api/records/batch/delete", url))
+        .bearer_auth(&token)
+        .json(&json!({ "ids": [existing, existing] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let items = deleted["data"].as_array().unwrap();
+
+    assert_eq!(204, items[0]["status"]);
+    assert_eq!(404, items[1]["status"]);
+}
@@ -1,4 +1,5 @@
 import {invoke} from "@tauri-apps/api/core";
+import {listen, UnlistenFn} from "@tauri-apps/api/event";
 import { save } from '@tauri-apps/plugin-dialog';
 import { writeTextFile } from "@tauri-apps/plugin-fs";
 
@@ -33,6 +34,7 @@ export interface EditableAccount {
     totp_step: number,
     otp_digits: number,
     algorithm: AccountAlgorithm,
+    sync_account_id?: number|null,
 }
 
 export interface DraftAccount {
@@ -59,9 +61,67 @@ interface AccountDeleteResponse {
     response: ResponseType,
 }
 
-interface TokenResponse {
+export interface TrashedAccount {
+    id: number,
+    name: string,
+    colour: string,
+    deleted_at: number,
+}
+
+interface TrashedAccountListResponse {
     response: ResponseType,
+    accounts: TrashedAccount[],
+}
+
+export enum RevisionOrigin {
+    LOCAL = "LOCAL",
+    SYNC = "SYNC",
+    IMPORT = "IMPORT",
+}
+
+export interface AccountRevision {
+    id: number,
+    account_id: number,
+    name: string,
+    colour: string,
+    totp_step: number,
+    otp_digits: number,
+    algorithm: AccountAlgorithm|null,
+    origin: RevisionOrigin,
+    created_at: number,
+}
+
+interface AccountRevisionListResponse {
+    response: ResponseType,
+    revisions: AccountRevision[],
+}
+
+interface AccountRestoreResponse {
+    response: ResponseType,
+    message: string,
+}
+
+interface OneTimePasswordResult {
+    code: string,
+    next_code: string,
+    period: number,
+    period_start: number,
+    seconds_remaining: number,
+}
+
+interface AccountCodeResult extends Partial<OneTimePasswordResult> {
+    account_id: number,
+    error?: string,
+}
+
+export interface AccountToken {
+    accountId: number,
     token: string,
+    nextToken: string,
+    period: number,
+    periodStart: number,
+    secondsRemaining: number,
+    error: string | null,
 }
 
 interface OptUrlResponse {
@@ -74,11 +134,34 @@ interface SyncValidationResponse {
     message: string
 }
 
-interface SyncAccount {
+export enum SYNC_TYPES {
+    PHOENIX,
+    FOLDER,
+    WEBDAV,
+}
+
+export enum SYNC_AUTH_TYPES {
+    BASIC,
+    BEARER,
+}
+
+export interface SyncAccount {
     id: number|null,
     username: string,
     password: string,
     url: string,
+    syncType: SYNC_TYPES,
+    passphrase?: string,
+    authType: SYNC_AUTH_TYPES,
+}
+
+interface SyncAccountResult {
+    id: number,
+    username: string,
+    password: string,
+    url: string,
+    sync_type?: string,
+    auth_type?: string,
 }
 
 interface SyncAccountResponse {
@@ -91,20 +174,91 @@ interface ExistingSyncAccountResponse {
     syncAccount: SyncAccount|null,
 }
 
+interface SyncAccountListResponse {
+    response: ResponseType,
+    syncAccounts: SyncAccount[],
+}
+
 export interface SyncLog {
     id: number,
     log: string,
     log_type: SyncLogType,
     timestamp: number,
+    session_id?: string|null,
+    sync_account_id?: number|null,
+    account_id?: number|null,
+    external_id?: number|null,
+    operation?: string|null,
 }
 
 export enum SyncLogType {
     ERROR = "ERROR",
+    INFO = "INFO",
+    WARN = "WARN",
+}
+
+export interface SyncLogFilter {
+    page?: number,
+    per_page?: number,
+    log_type?: SyncLogType|null,
+    session_id?: string|null,
+    account_id?: number|null,
 }
 
 interface SyncLogResponse {
     response: ResponseType,
     logs: SyncLog[],
+    total: number,
+}
+
+export const SYNC_STARTED_EVENT = "sync-started";
+export const SYNC_PROGRESS_EVENT = "sync-progress";
+export const SYNC_COMPLETED_EVENT = "sync-completed";
+
+export interface SyncReport {
+    session_id: string|null,
+    running: boolean,
+    started_at: number|null,
+    finished_at: number|null,
+    success: boolean|null,
+    created: number,
+    updated: number,
+    deleted: number,
+    failed: number,
+    cancelled: boolean,
+    error: string|null,
+    failures: SyncItemFailure[],
+    held_deletions: HeldDeletion[],
+}
+
+export interface HeldDeletion {
+    sync_account_id: number,
+    account_id: number,
+    external_id: number|null,
+    name: string,
+}
+
+export interface SyncItemFailure {
+    sync_account_id: number,
+    account_id: number|null,
+    external_id: number|null,
+    name: string|null,
+    operation: string,
+    error: string,
+    attempts: number,
+    held: boolean,
+}
+
+export interface SyncProgress {
+    session_id: string,
+    sync_account_id: number,
+    completed: number,
+    total: number,
+    created: number,
+    updated: number,
+    deleted: number,
+    failed: number,
+    error: string|null,
 }
 
 export enum THEME_MODES {
@@ -116,11 +270,23 @@ export enum THEME_MODES {
 export interface Setting {
     id: number,
     theme: THEME_MODES,
+    trashRetentionDays: number,
+    syncIntervalMinutes: number,
+    localApiEnabled: boolean,
 }
 
 interface SettingsResult {
     id: number,
     theme: string,
+    trash_retention_days?: number,
+    sync_interval_minutes?: number,
+    local_api_enabled?: boolean,
+}
+
+export interface LocalApiClient {
+    id: number,
+    name: string,
+    created_at: number,
 }
 
 interface SettingResponse {
@@ -235,23 +401,106 @@ export async function deleteAccount(accountId: number): Promise<AccountDeleteRes
     }
 }
 
-export async function generateToken(accountId: number): Promise<TokenResponse>
+export async function getTrashedAccounts(): Promise<TrashedAccountListResponse>
 {
-    const response = await invoke("get_one_time_password_for_account", { account: accountId });
+    const result = await invoke("get_trashed_accounts");
 
-    if (typeof response !== 'string') {
+    if (!Array.isArray(result)) {
+        return {
+            response: ResponseType.FAILURE,
+            accounts: [],
+        }
+    }
+
+    return {
+        response: ResponseType.SUCCESS,
+        accounts: result,
+    }
+}
+
+export async function restoreAccount(accountId: number): Promise<AccountRestoreResponse>
+{
+    try {
+        await invoke("restore_account", {accountId});
+
+        return {
+            response: ResponseType.SUCCESS,
+            message: 'Account Restored',
+        }
+    // eslint-disable-next-line @typescript-eslint/no-explicit-any
+    } catch (e: any) {
+        return {
+            response: ResponseType.FAILURE,
+            message: e,
+        }
+    }
+}
+
+export async function purgeAccount(accountId: number): Promise<boolean>
+{
+    return await invoke("purge_account", {accountId}) === true;
+}
+
+export async function getAccountRevisions(accountId: number): Promise<AccountRevisionListResponse>
+{
+    const result = await invoke("get_account_revisions", {accountId});
+
+    if (!Array.isArray(result)) {
         return {
             response: ResponseType.FAILURE,
-            token: '',
+            revisions: [],
         }
     }
 
     return {
         response: ResponseType.SUCCESS,
-        token: response,
+        revisions: result,
     }
 }
 
+export async function restoreAccountRevision(revisionId: number): Promise<AccountRestoreResponse>
+{
+    try {
+        await invoke("restore_account_revision", {revisionId});
+
+        return {
+            response: ResponseType.SUCCESS,
+            message: 'Revision Restored',
+        }
+    // eslint-disable-next-line @typescript-eslint/no-explicit-any
+    } catch (e: any) {
+        return {
+            response: ResponseType.FAILURE,
+            message: e,
+        }
+    }
+}
+
+export async function generateTokens(accountIds: number[]): Promise<AccountToken[]>
+{
+    let result;
+
+    try {
+        result = await invoke("get_codes_for_accounts", { accountIds });
+    } catch {
+        return [];
+    }
+
+    if (!Array.isArray(result)) {
+        return [];
+    }
+
+    return result.map((code: AccountCodeResult) => ({
+        accountId: code.account_id,
+        token: code.code ?? '',
+        nextToken: code.next_code ?? '',
+        period: code.period ?? 0,
+        periodStart: code.period_start ?? 0,
+        secondsRemaining: code.seconds_remaining ?? 0,
+        error: code.error ?? null,
+    }));
+}
+
 export async function parseOptUrl(url: string): Promise<OptUrlResponse>
 {
     const response = JSON.parse(await invoke("parse_otp_url", {otpUrl: url}));
@@ -292,10 +541,10 @@ export async function exportAccounts() {
     await writeTextFile(filePath, contents);
 }
 
-export async function validateSyncAccount(host: string, username: string, password: string): Promise<SyncValidationResponse>
+export async function validateSyncAccount(syncAccount: SyncAccount): Promise<SyncValidationResponse>
 {
     try {
-        await invoke("validate_sync_account", {host, username, password});
+        await invoke("validate_sync_account", {details: SyncAccountToDetails(syncAccount)});
 
         return {
             response: ResponseType.SUCCESS,
@@ -312,22 +561,22 @@ export async function validateSyncAccount(host: string, username: string, passwo
 
 export async function saveSyncAccount(syncAccount: SyncAccount): Promise<SyncAccountResponse>
 {
-    const response: SyncAccount = await invoke("save_sync_account", {host: syncAccount.url, username: syncAccount.username, password: syncAccount.password});
+    const response: SyncAccountResult = await invoke("save_sync_account", {id: syncAccount.id, details: SyncAccountToDetails(syncAccount)});
 
     return {
         response: ResponseType.SUCCESS,
-        syncAccount: response,
+        syncAccount: SyncAccountResultToSyncAccount(response),
     }
 }
 
 export async function getExistingAccount(): Promise<ExistingSyncAccountResponse>
 {
     try {
-        const response: SyncAccount = await invoke("get_existing_sync_account");
+        const response: SyncAccountResult = await invoke("get_existing_sync_account");
 
         return {
             response: ResponseType.SUCCESS,
-            syncAccount: response,
+            syncAccount: SyncAccountResultToSyncAccount(response),
         }
     } catch (e) {
         console.error(e)
@@ -338,28 +587,89 @@ export async function getExistingAccount(): Promise<ExistingSyncAccountResponse>
     }
 }
 
-export async function getSyncLogs(): Promise<SyncLogResponse>
+export async function getSyncAccounts(): Promise<SyncAccountListResponse>
 {
-    const result = JSON.parse(await invoke("get_sync_logs"));
+    const result = await invoke("get_sync_accounts");
 
-    if (typeof result !== "object") {
+    if (!Array.isArray(result)) {
+        return {
+            response: ResponseType.FAILURE,
+            syncAccounts: [],
+        }
+    }
+
+    return {
+        response: ResponseType.SUCCESS,
+        syncAccounts: result.map(SyncAccountResultToSyncAccount),
+    }
+}
+
+export async function removeSyncAccount(id: number): Promise<boolean>
+{
+    return await invoke("remove_sync_account", {id}) === true;
+}
+
+export async function setAccountSyncTarget(accountId: number, syncAccountId: number|null): Promise<boolean>
+{
+    return await invoke("set_account_sync_target", {accountId, syncAccountId}) === true;
+}
+
+export async function getSyncLogs(filter: SyncLogFilter = {}): Promise<SyncLogResponse>
+{
+    const result = JSON.parse(await invoke("get_sync_logs", {filter}));
+
+    if (typeof result !== "object" || !Array.isArray(result.logs)) {
         return {
             response: ResponseType.FAILURE,
             logs: [],
+            total: 0,
         }
     }
 
     return {
         response: ResponseType.SUCCESS,
-        logs: result
+        logs: result.logs,
+        total: result.total,
     }
 }
 
 export async function attemptSyncAccounts(): Promise<boolean>
 {
-    await invoke("attempt_sync_with_remote");
+    return await invoke("attempt_sync_with_remote") === true;
+}
+
+export async function cancelSync(): Promise<boolean>
+{
+    return await invoke("cancel_sync") === true;
+}
+
+export async function getSyncStatus(): Promise<SyncReport>
+{
+    return await invoke("get_sync_status");
+}
+
+export async function confirmHeldDeletions(syncAccountId: number): Promise<number>
+{
+    try {
+        return await invoke("confirm_held_deletions", {syncAccountId});
+    } catch {
+        return 0;
+    }
+}
+
+export async function listenForSyncEvents(
+    onStarted: (report: SyncReport) => void,
+    onProgress: (progress: SyncProgress) => void,
+    onCompleted: (report: SyncReport) => void,
+): Promise<UnlistenFn>
+{
+    const listeners = await Promise.all([
+        listen<SyncReport>(SYNC_STARTED_EVENT, (event) => onStarted(event.payload)),
+        listen<SyncProgress>(SYNC_PROGRESS_EVENT, (event) => onProgress(event.payload)),
+        listen<SyncReport>(SYNC_COMPLETED_EVENT, (event) => onCompleted(event.payload)),
+    ]);
 
-    return true;
+    return () => listeners.forEach(unlisten => unlisten());
 }
 
 export async function getSettings(): Promise<SettingResponse>
@@ -382,6 +692,62 @@ export async function saveSettings(theme: THEME_MODES): Promise<SettingResponse>
     }
 }
 
+export async function saveTrashRetention(trashRetentionDays: number): Promise<SettingResponse>
+{
+    const result: SettingsResult = await invoke("save_trash_retention", {trashRetentionDays});
+
+    return {
+        response: ResponseType.SUCCESS,
+        settings: SettingResultToSetting(result),
+    }
+}
+
+export async function saveSyncInterval(syncIntervalMinutes: number): Promise<SettingResponse>
+{
+    const result: SettingsResult = await invoke("save_sync_interval", {syncIntervalMinutes});
+
+    return {
+        response: ResponseType.SUCCESS,
+        settings: SettingResultToSetting(result),
+    }
+}
+
+export async function saveLocalApiEnabled(localApiEnabled: boolean): Promise<SettingResponse>
+{
+    const result: SettingsResult = await invoke("save_local_api_enabled", {localApiEnabled});
+
+    return {
+        response: ResponseType.SUCCESS,
+        settings: SettingResultToSetting(result),
+    }
+}
+
+export async function getLocalApiError(): Promise<string | null>
+{
+    return await invoke("get_local_api_error");
+}
+
+export async function getLocalApiClients(): Promise<LocalApiClient[]>
+{
+    const result = await invoke("get_local_api_clients");
+
+    if (!Array.isArray(result)) {
+        return [];
+    }
+
+    return result;
+}
+
+export async function removeLocalApiClient(id: number): Promise<boolean>
+{
+    return await invoke("remove_local_api_client", {id}) === true;
+}
+
+export async function setSyncOnline(online: boolean): Promise<void>
+{
+    await invoke("set_sync_online", {online});
+}
+
 function SettingResultToSetting(settings: SettingsResult): Setting {
     const id = settings.id;
     let theme = THEME_MODES.DEFAULT;
@@ -397,5 +763,40 @@ function SettingResultToSetting(settings: SettingsResult): Setting {
     return {
         id,
         theme,
+        trashRetentionDays: settings.trash_retention_days ?? 30,
+        syncIntervalMinutes: settings.sync_interval_minutes ?? 15,
+        localApiEnabled: settings.local_api_enabled ?? false,
+    }
+}
+
+function SyncAccountResultToSyncAccount(syncAccount: SyncAccountResult): SyncAccount {
+    let syncType = SYNC_TYPES.PHOENIX;
+
+    if (syncAccount.sync_type === 'FOLDER') {
+        syncType = SYNC_TYPES.FOLDER;
+    }
+
+    if (syncAccount.sync_type === 'WEBDAV') {
+        syncType = SYNC_TYPES.WEBDAV;
+    }
+
+    return {
+        id: syncAccount.id,
+        username: syncAccount.username,
+        password: syncAccount.password,
+        url: syncAccount.url,
+        syncType,
+        authType: syncAccount.auth_type === 'BEARER' ? SYNC_AUTH_TYPES.BEARER : SYNC_AUTH_TYPES.BASIC,
+    }
+}
+
+function SyncAccountToDetails(syncAccount: SyncAccount) {
+    return {
+        host: syncAccount.url,
+        username: syncAccount.username,
+        password: syncAccount.password,
+        syncType: syncAccount.syncType,
+        passphrase: syncAccount.syncType === SYNC_TYPES.PHOENIX ? null : syncAccount.passphrase ?? null,
+        authType: syncAccount.authType,
     }
-}
\ No newline at end of file
+}
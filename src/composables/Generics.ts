@@ -1,8 +1,14 @@
-import {generateToken} from "./Commands.ts";
+import {generateTokens} from "./Commands.ts";
 
 export async function copyOtpToClipboard(accountId: number): Promise<void> {
     try {
-        await navigator.clipboard.writeText((await generateToken(accountId)).token);
+        const [token] = await generateTokens([accountId]);
+
+        if (!token || token.error) {
+            return;
+        }
+
+        await navigator.clipboard.writeText(token.token);
     } catch (e) {
         console.error(e)
     }
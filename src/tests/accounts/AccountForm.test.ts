@@ -177,4 +177,47 @@ describe('Endpoint handling', async () => {
 
         expect(wrapper.vm.message).toBe('Updated Account')
     })
-})
\ No newline at end of file
+
+    test('Update Existing Account Sync Target', async () => {
+        let syncTarget: unknown = 'UNCHANGED'
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_editable_account' ) {
+                return JSON.stringify({id: 1, name: "Hello World", secret: "encrypted", otp_digits: 6, totp_step: 60, algorithm: "SHA512", sync_account_id: 1})
+            }
+
+            if (cmd === 'get_sync_accounts') {
+                return [
+                    {id: 1, username: 'work', password: '', url: 'https://work.com', sync_type: 'PHOENIX'},
+                    {id: 2, username: '', password: '', url: '/home/test/phoenix', sync_type: 'FOLDER'},
+                ]
+            }
+
+            if (cmd === 'edit_account') {
+                return 'Updated Account';
+            }
+
+            if (cmd === 'set_account_sync_target' && args.accountId === 1) {
+                syncTarget = args.syncAccountId
+                return true
+            }
+
+            return 'INVALID'
+        })
+
+        const wrapper = mount(AccountForm, {props: {accountId: 1}})
+        await flushPromises()
+
+        expect(wrapper.vm.syncAccountId).toBe(1)
+        expect(wrapper.vm.syncAccounts.length).toBe(2)
+
+        wrapper.vm.syncAccountId = 2
+
+        wrapper.vm.submitForm()
+
+        await flushPromises()
+
+        expect(wrapper.vm.message).toBe('Updated Account')
+        expect(syncTarget).toBe(2)
+    })
+})
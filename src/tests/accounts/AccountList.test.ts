@@ -1,4 +1,4 @@
-import {afterEach, describe, expect, test} from "vitest";
+import {afterEach, describe, expect, test, vi} from "vitest";
 import {clearMocks, mockIPC} from "@tauri-apps/api/mocks";
 import {flushPromises, shallowMount} from "@vue/test-utils";
 import AccountList from "../../components/accounts/AccountList.vue";
@@ -85,4 +85,124 @@ describe('Display Results', async () => {
 
         expect(emit).toHaveLength(1)
     })
-})
\ No newline at end of file
+})
+
+describe('Account Codes', async () => {
+    afterEach(() => {
+        clearMocks()
+    })
+
+    test('Codes fetched in one batch', async () => {
+        const requests: number[][] = [];
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_all_accounts') {
+                return '[{"id": 1, "name": "Account One", "secret": null}, {"id": 2, "name": "Account Two", "secret": null}]';
+            }
+
+            if (cmd === 'get_codes_for_accounts') {
+                requests.push(args.accountIds as number[]);
+
+                return [
+                    {account_id: 1, code: "456908", next_code: "120574", period: 30, period_start: 1700000010, seconds_remaining: 12},
+                    {account_id: 2, code: "803412", next_code: "551029", period: 30, period_start: 1700000010, seconds_remaining: 12},
+                ];
+            }
+
+            return 'INVALID'
+        })
+
+        const wrapper = shallowMount(AccountList)
+
+        await flushPromises();
+
+        expect(requests).toEqual([[1, 2]])
+        expect(wrapper.html()).toContain('<account-item-stub accountid="1" accountname="Account One" token="456908" manage="false"></account-item-stub>')
+        expect(wrapper.html()).toContain('<account-item-stub accountid="2" accountname="Account Two" token="803412" manage="false"></account-item-stub>')
+    })
+
+    test('Account without a code shows the reason', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_all_accounts') {
+                return '[{"id": 1, "name": "Account One", "secret": null}, {"id": 2, "name": "Account Two", "secret": null}]';
+            }
+
+            if (cmd === 'get_codes_for_accounts') {
+                return [
+                    {account_id: 1, code: "456908", next_code: "120574", period: 30, period_start: 1700000010, seconds_remaining: 12},
+                    {account_id: 2, error: "Could not decrypt value"},
+                ];
+            }
+
+            return 'INVALID'
+        })
+
+        const wrapper = shallowMount(AccountList)
+
+        await flushPromises();
+
+        expect(wrapper.vm.tokens[1].error).toBe(null)
+        expect(wrapper.vm.tokens[2].error).toBe('Could not decrypt value')
+        expect(wrapper.html()).toContain('error="Could not decrypt value"')
+
+        wrapper.unmount()
+    })
+
+    test('Codes refreshed when period ends', async () => {
+        vi.useFakeTimers()
+
+        let calls = 0;
+
+        mockIPC((cmd) => {
+            if (cmd === 'get_all_accounts') {
+                return '[{"id": 1, "name": "Account One", "secret": null}]';
+            }
+
+            if (cmd === 'get_codes_for_accounts') {
+                calls++;
+
+                return [
+                    {account_id: 1, code: calls === 1 ? "456908" : "120574", next_code: "120574", period: 30, period_start: 1700000010, seconds_remaining: 12},
+                ];
+            }
+
+            return 'INVALID'
+        })
+
+        const wrapper = shallowMount(AccountList)
+
+        await flushPromises();
+        expect(wrapper.html()).toContain('token="456908"')
+
+        await vi.advanceTimersByTimeAsync(12000);
+        await flushPromises();
+
+        expect(calls).toBe(2)
+        expect(wrapper.html()).toContain('token="120574"')
+
+        wrapper.unmount()
+        vi.useRealTimers()
+    })
+
+    test('Codes not fetched when managing', async () => {
+        let calls = 0;
+
+        mockIPC((cmd) => {
+            if (cmd === 'get_all_accounts') {
+                return '[{"id": 1, "name": "Account One", "secret": null}]';
+            }
+
+            if (cmd === 'get_codes_for_accounts') {
+                calls++;
+            }
+
+            return 'INVALID'
+        })
+
+        shallowMount(AccountList, {props: {manage: true}})
+
+        await flushPromises();
+
+        expect(calls).toBe(0)
+    })
+})
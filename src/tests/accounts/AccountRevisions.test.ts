@@ -0,0 +1,81 @@
+import {afterEach, describe, expect, test} from "vitest";
+import {flushPromises, shallowMount} from "@vue/test-utils";
+import {clearMocks, mockIPC} from "@tauri-apps/api/mocks";
+import AccountRevisions from "../../components/accounts/AccountRevisions.vue";
+
+describe('Account Revisions', async () => {
+    afterEach(() => {
+        clearMocks()
+    })
+
+    test('Get Revisions', async () => {
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_account_revisions' && args.accountId === 1) {
+                return [
+                    {id: 2, account_id: 1, name: 'Synced Name', colour: 'ffffff', totp_step: 30, otp_digits: 6, algorithm: 'SHA1', origin: 'SYNC', created_at: 1725483734},
+                    {id: 1, account_id: 1, name: 'Original Name', colour: 'ffffff', totp_step: 30, otp_digits: 6, algorithm: null, origin: 'LOCAL', created_at: 1725483730},
+                ]
+            }
+
+            return 'INVALID'
+        })
+
+        const wrapper = shallowMount(AccountRevisions, {props: {accountId: 1}});
+
+        await flushPromises();
+
+        expect(wrapper.vm.revisions.length).toBe(2)
+        expect(wrapper.vm.formatOrigin(wrapper.vm.revisions[0])).toBe('Sync')
+        expect(wrapper.vm.formatOrigin(wrapper.vm.revisions[1])).toBe('Edit')
+    });
+
+    test('Restore Revision', async () => {
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_account_revisions') {
+                return [{id: 1, account_id: 1, name: 'Original Name', colour: 'ffffff', totp_step: 30, otp_digits: 6, algorithm: null, origin: 'LOCAL', created_at: 1725483730}]
+            }
+
+            if (cmd === 'restore_account_revision' && args.revisionId === 1) {
+                return true
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(AccountRevisions, {props: {accountId: 1}});
+
+        await flushPromises();
+
+        wrapper.vm.restore(wrapper.vm.revisions[0])
+
+        await flushPromises();
+
+        expect(wrapper.vm.message).toBe('Revision Restored')
+        expect(wrapper.emitted().restored).toBeTruthy()
+    });
+
+    test('Restore Revision - Name Taken', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_account_revisions') {
+                return [{id: 1, account_id: 1, name: 'Original Name', colour: 'ffffff', totp_step: 30, otp_digits: 6, algorithm: null, origin: 'LOCAL', created_at: 1725483730}]
+            }
+
+            if (cmd === 'restore_account_revision') {
+                throw 'Account already exists: Original Name'
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(AccountRevisions, {props: {accountId: 1}});
+
+        await flushPromises();
+
+        wrapper.vm.restore(wrapper.vm.revisions[0])
+
+        await flushPromises();
+
+        expect(wrapper.vm.message).toBe('Account already exists: Original Name')
+        expect(wrapper.emitted().restored).toBeFalsy()
+    });
+});
@@ -1,57 +1,40 @@
-import {expect, test, afterEach, beforeEach} from 'vitest'
-import { mockIPC, clearMocks } from "@tauri-apps/api/mocks"
-import { flushPromises, mount} from "@vue/test-utils";
+import {expect, test} from 'vitest'
+import {mount} from "@vue/test-utils";
 
 import OneTimePassword from "../../components/accounts/OneTimePassword.vue";
 
-beforeEach(() => {
-    mockIPC((cmd, args) => {
-        if (cmd !== "get_one_time_password_for_account") {
-            return 'INVALID'
-        }
-
-        if (args.account === 12) {
-            return "456908";
-        }
-
-        return "";
-    })
-})
-
-afterEach(() => {
-    clearMocks()
-})
-
-test('Fetch Valid 2FA', async () => {
+test('Display 2FA', async () => {
     const wrapper = mount(
         OneTimePassword,
         {
             propsData: {
-                accountId: 12
+                token: "456908"
             }
         }
     )
-    wrapper.vm.getOneTimePassword();
-
-    await flushPromises();
 
     expect(wrapper.vm.otp).toBe("456908")
     expect(wrapper.html()).toBe("<div class=\"d-grid gap-2\"><span class=\"list-item-text align-middle\">456908</span></div>")
 })
 
-test('Invalid Account', async () => {
+test('Missing Token', async () => {
+    const wrapper = mount(OneTimePassword)
+
+    expect(wrapper.vm.otp).toBe("------")
+    expect(wrapper.html()).toBe("<div class=\"d-grid gap-2\"><span class=\"list-item-text align-middle\">------</span></div>")
+})
+
+test('Update When Token Changes', async () => {
     const wrapper = mount(
         OneTimePassword,
         {
             propsData: {
-                accountId: 1
+                token: "456908"
             }
         }
     )
-    wrapper.vm.getOneTimePassword();
 
-    await flushPromises();
+    await wrapper.setProps({token: "120574"})
 
-    expect(wrapper.vm.otp).toBe("")
-    expect(wrapper.html()).toBe("<div class=\"d-grid gap-2\"><span class=\"list-item-text align-middle\"></span></div>")
-})
\ No newline at end of file
+    expect(wrapper.vm.otp).toBe("120574")
+})
@@ -0,0 +1,131 @@
+import {afterEach, describe, expect, test} from "vitest";
+import {flushPromises, shallowMount} from "@vue/test-utils";
+import {clearMocks, mockIPC} from "@tauri-apps/api/mocks";
+import LocalAccess from "../../components/settings/LocalAccess.vue";
+
+describe('Local Access', async () => {
+    afterEach(() => {
+        clearMocks()
+    })
+
+    test('Get Local Access Settings', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', local_api_enabled: true}
+            }
+
+            if (cmd === 'get_local_api_error') {
+                return null
+            }
+
+            if (cmd === 'get_local_api_clients') {
+                return [{id: 1, name: 'Firefox', created_at: 1700000000}]
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(LocalAccess);
+
+        await flushPromises();
+
+        expect(wrapper.vm.localApiEnabled).toBe(true)
+        expect(wrapper.vm.clients.length).toBe(1)
+        expect(wrapper.text()).toContain('Firefox')
+    });
+
+    test('Enable Local Access', async () => {
+        let savedEnabled = false;
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', local_api_enabled: false}
+            }
+
+            if (cmd === 'get_local_api_error') {
+                return null
+            }
+
+            if (cmd === 'get_local_api_clients') {
+                return []
+            }
+
+            if (cmd === 'save_local_api_enabled') {
+                savedEnabled = args.localApiEnabled as boolean;
+                return {id: 1, theme: 'DARK', local_api_enabled: args.localApiEnabled}
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(LocalAccess);
+
+        await flushPromises();
+
+        wrapper.vm.localApiEnabled = true
+        await wrapper.vm.saveEnabled()
+
+        expect(savedEnabled).toBe(true)
+        expect(wrapper.vm.localApiEnabled).toBe(true)
+    });
+
+    test('Remove Clients', async () => {
+        let clients = [{id: 1, name: 'Firefox', created_at: 1700000000}, {id: 2, name: 'Alfred', created_at: 1700000000}];
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', local_api_enabled: true}
+            }
+
+            if (cmd === 'get_local_api_error') {
+                return null
+            }
+
+            if (cmd === 'get_local_api_clients') {
+                return clients
+            }
+
+            if (cmd === 'remove_local_api_client') {
+                clients = clients.filter(client => client.id !== args.id);
+                return true
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(LocalAccess);
+
+        await flushPromises();
+
+        expect(wrapper.vm.clients.map(client => client.name)).toStrictEqual(['Firefox', 'Alfred'])
+
+        await wrapper.vm.removeClient(wrapper.vm.clients[0])
+
+        expect(wrapper.vm.clients.map(client => client.name)).toStrictEqual(['Alfred'])
+    });
+
+    test('Show Start Failure', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', local_api_enabled: true}
+            }
+
+            if (cmd === 'get_local_api_error') {
+                return 'Local access could not start: Address in use'
+            }
+
+            if (cmd === 'get_local_api_clients') {
+                return []
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(LocalAccess);
+
+        await flushPromises();
+
+        expect(wrapper.vm.localApiError).toBe('Local access could not start: Address in use')
+        expect(wrapper.text()).toContain('Address in use')
+    });
+});
@@ -2,6 +2,7 @@ import {afterEach, describe, expect, test} from "vitest";
 import {flushPromises, shallowMount} from "@vue/test-utils";
 import {clearMocks, mockIPC} from "@tauri-apps/api/mocks";
 import AccountSyncPage from "../../../components/settings/sync/AccountSyncPage.vue";
+import {SYNC_AUTH_TYPES, SYNC_TYPES} from "../../../composables/Commands.ts";
 
 describe('Sync Log Table', async () => {
     afterEach(() => {
@@ -33,19 +34,40 @@ describe('Sync Log Table', async () => {
                 return 'INVALID'
             }
 
-            return {id: 1, username: 'test', password: 'password', url: 'https://test.com'};
+            return {id: 1, username: 'test', password: 'password', url: 'https://test.com', sync_type: 'PHOENIX'};
         })
 
         const wrapper = shallowMount(AccountSyncPage);
 
         await flushPromises();
 
+        expect(wrapper.vm.syncType).toBe(SYNC_TYPES.PHOENIX)
         expect(wrapper.vm.host).toBe('https://test.com')
         expect(wrapper.vm.username).toBe('test')
         expect(wrapper.vm.password).toBe('password')
         expect(wrapper.vm.lockdownForm).toBeTruthy()
     });
 
+    test('Existing WebDAV Account', async () => {
+        mockIPC((cmd) => {
+            if (cmd !== 'get_existing_sync_account') {
+                return 'INVALID'
+            }
+
+            return {id: 1, username: '', password: '', url: 'https://cloud.test.com/remote.php/dav/files/test/phoenix', sync_type: 'WEBDAV', auth_type: 'BEARER'};
+        })
+
+        const wrapper = shallowMount(AccountSyncPage);
+
+        await flushPromises();
+
+        expect(wrapper.vm.syncType).toBe(SYNC_TYPES.WEBDAV)
+        expect(wrapper.vm.authType).toBe(SYNC_AUTH_TYPES.BEARER)
+        expect(wrapper.vm.passwordLabel).toBe('Token')
+        expect(wrapper.vm.host).toBe('https://cloud.test.com/remote.php/dav/files/test/phoenix')
+        expect(wrapper.vm.lockdownForm).toBeTruthy()
+    });
+
     test('Create New Account - Invalid Details', async () => {
         mockIPC((cmd, args) => {
             if (cmd === 'get_existing_sync_account') {
@@ -53,7 +75,7 @@ describe('Sync Log Table', async () => {
             }
 
             if (cmd === 'validate_sync_account') {
-                if (args.host === 'https://invalid.com' && args.username === 'test' && args.password === 'password') {
+                if (args.details.host === 'https://invalid.com' && args.details.username === 'test' && args.details.password === 'password') {
                     throw "404 Could not be found";
                 }
 
@@ -88,7 +110,7 @@ describe('Sync Log Table', async () => {
             }
 
             if (cmd === 'validate_sync_account') {
-                if (args.host === 'https://valid.com' && args.username === 'test' && args.password === 'password') {
+                if (args.details.host === 'https://valid.com' && args.details.username === 'test' && args.details.password === 'password') {
                     return "28uwiofdjger3q9wfdghs";
                 }
 
@@ -96,7 +118,7 @@ describe('Sync Log Table', async () => {
             }
 
             if (cmd === 'save_sync_account') {
-                if (args.host === 'https://valid.com' && args.username === 'test' && args.password === 'password') {
+                if (args.details.host === 'https://valid.com' && args.details.username === 'test' && args.details.password === 'password') {
                     return {id: 1, username: 'test', password: 'password', url: 'https://valid.com'}
                 }
 
@@ -128,6 +150,129 @@ describe('Sync Log Table', async () => {
         expect(wrapper.vm.password).toBe('password')
     })
 
+    test('Create New Account - Shared Folder', async () => {
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_existing_sync_account') {
+                throw 'Sync Account does not exist'
+            }
+
+            if (cmd === 'validate_sync_account') {
+                if (args.details.host === '/home/test/Dropbox/phoenix' && args.details.password === '' && args.details.passphrase === 'passphrase' && args.details.syncType === SYNC_TYPES.FOLDER) {
+                    return "";
+                }
+
+                throw 'Test Failed!'
+            }
+
+            if (cmd === 'save_sync_account') {
+                if (args.details.host === '/home/test/Dropbox/phoenix' && args.details.passphrase === 'passphrase' && args.details.syncType === SYNC_TYPES.FOLDER) {
+                    return {id: 1, username: '', password: '', url: '/home/test/Dropbox/phoenix', sync_type: 'FOLDER'}
+                }
+
+                throw 'Invalid account provided'
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(AccountSyncPage);
+
+        await flushPromises();
+
+        wrapper.vm.syncType = SYNC_TYPES.FOLDER
+        wrapper.vm.host = '/home/test/Dropbox/phoenix'
+        wrapper.vm.passphrase = 'passphrase'
+
+        wrapper.vm.submitForm()
+
+        await flushPromises();
+
+        expect(wrapper.vm.message).toBe('Successfully Validated Account');
+        expect(wrapper.vm.lockdownForm).toBeTruthy();
+        expect(wrapper.vm.isFolderSync).toBeTruthy();
+    })
+
+    test('Create New Account - WebDAV', async () => {
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_existing_sync_account') {
+                throw 'Sync Account does not exist'
+            }
+
+            if (cmd === 'validate_sync_account' || cmd === 'save_sync_account') {
+                const details = args.details;
+
+                if (details.host === 'https://dav.test.com' && details.username === 'test' && details.passphrase === 'passphrase' && details.authType === SYNC_AUTH_TYPES.BASIC) {
+                    return cmd === 'save_sync_account'
+                        ? {id: 1, username: 'test', password: '', url: 'https://dav.test.com', sync_type: 'WEBDAV', auth_type: 'BASIC'}
+                        : "";
+                }
+
+                throw 'Test Failed!'
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(AccountSyncPage);
+
+        await flushPromises();
+
+        wrapper.vm.syncType = SYNC_TYPES.WEBDAV
+        wrapper.vm.host = 'https://dav.test.com'
+        wrapper.vm.username = 'test'
+        wrapper.vm.password = 'password'
+        wrapper.vm.passphrase = 'passphrase'
+
+        wrapper.vm.submitForm()
+
+        await flushPromises();
+
+        expect(wrapper.vm.message).toBe('Successfully Validated Account');
+        expect(wrapper.vm.lockdownForm).toBeTruthy();
+    })
+
+    test('Create New Account - WebDAV Bearer Token', async () => {
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_existing_sync_account') {
+                throw 'Sync Account does not exist'
+            }
+
+            if (cmd === 'validate_sync_account') {
+                const details = args.details;
+
+                if (details.username === '' && details.password === 'token' && details.authType === SYNC_AUTH_TYPES.BEARER) {
+                    return "";
+                }
+
+                throw 'Test Failed!'
+            }
+
+            if (cmd === 'save_sync_account') {
+                return {id: 1, username: '', password: '', url: 'https://dav.test.com', sync_type: 'WEBDAV', auth_type: 'BEARER'}
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(AccountSyncPage);
+
+        await flushPromises();
+
+        wrapper.vm.syncType = SYNC_TYPES.WEBDAV
+        wrapper.vm.authType = SYNC_AUTH_TYPES.BEARER
+        wrapper.vm.host = 'https://dav.test.com'
+        wrapper.vm.username = 'ignored'
+        wrapper.vm.password = 'token'
+        wrapper.vm.passphrase = 'passphrase'
+
+        wrapper.vm.submitForm()
+
+        await flushPromises();
+
+        expect(wrapper.vm.passwordLabel).toBe('Token');
+        expect(wrapper.vm.message).toBe('Successfully Validated Account');
+    })
+
     test('Update Existing Account', async () => {
         mockIPC((cmd, args) => {
             if (cmd === 'get_existing_sync_account') {
@@ -135,7 +280,7 @@ describe('Sync Log Table', async () => {
             }
 
             if (cmd === 'validate_sync_account') {
-                if (args.host === 'https://updated.com' && args.username === 'test' && args.password === 'password') {
+                if (args.details.host === 'https://updated.com' && args.details.username === 'test' && args.details.password === 'password') {
                     return "28uwiofdjger3q9wfdghs";
                 }
 
@@ -143,7 +288,7 @@ describe('Sync Log Table', async () => {
             }
 
             if (cmd === 'save_sync_account') {
-                if (args.host === 'https://updated.com' && args.username === 'test' && args.password === 'password') {
+                if (args.details.host === 'https://updated.com' && args.details.username === 'test' && args.details.password === 'password') {
                     return {id: 1, username: 'test', password: 'password', url: 'https://valid.com'}
                 }
 
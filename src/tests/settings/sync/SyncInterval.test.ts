@@ -0,0 +1,53 @@
+import {afterEach, describe, expect, test} from "vitest";
+import {flushPromises, shallowMount} from "@vue/test-utils";
+import {clearMocks, mockIPC} from "@tauri-apps/api/mocks";
+import SyncInterval from "../../../components/settings/sync/SyncInterval.vue";
+
+describe('Sync Interval', async () => {
+    afterEach(() => {
+        clearMocks()
+    })
+
+    test('Get Sync Interval', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', trash_retention_days: 30, sync_interval_minutes: 60}
+            }
+
+            return 'INVALID'
+        })
+
+        const wrapper = shallowMount(SyncInterval);
+
+        await flushPromises();
+
+        expect(wrapper.vm.syncIntervalMinutes).toBe(60)
+    });
+
+    test('Save Sync Interval', async () => {
+        let savedInterval = -1;
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', trash_retention_days: 30, sync_interval_minutes: 15}
+            }
+
+            if (cmd === 'save_sync_interval') {
+                savedInterval = args.syncIntervalMinutes;
+                return {id: 1, theme: 'DARK', trash_retention_days: 30, sync_interval_minutes: args.syncIntervalMinutes}
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(SyncInterval);
+
+        await flushPromises();
+
+        wrapper.vm.syncIntervalMinutes = 0
+        await wrapper.vm.saveInterval()
+
+        expect(savedInterval).toBe(0)
+        expect(wrapper.vm.syncIntervalMinutes).toBe(0)
+    });
+});
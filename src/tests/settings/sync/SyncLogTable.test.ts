@@ -15,7 +15,7 @@ describe('Sync Log Table', async () => {
                 return 'INVALID'
             }
 
-            return '[{"id": 1, "log": "Error One", "timestamp": 1726865841, "log_type": "ERROR"}, {"id": 2, "log": "Error Two", "timestamp": 1695225441, "log_type": "ERROR"}]';
+            return '{"logs": [{"id": 1, "log": "Error One", "timestamp": 1726865841, "log_type": "ERROR"}, {"id": 2, "log": "Error Two", "timestamp": 1695225441, "log_type": "ERROR"}], "total": 2, "page": 1, "per_page": 10}';
         })
 
         const wrapper = shallowMount(SyncLogTable)
@@ -35,7 +35,7 @@ describe('Sync Log Table', async () => {
                 return 'INVALID'
             }
 
-            return '[]';
+            return '{"logs": [], "total": 0, "page": 1, "per_page": 10}';
         })
 
         const wrapper = shallowMount(SyncLogTable)
@@ -51,7 +51,7 @@ describe('Sync Log Table', async () => {
                 return 'INVALID'
             }
 
-            return '[]';
+            return '{"logs": [], "total": 0, "page": 1, "per_page": 10}';
         })
 
         const wrapper = shallowMount(SyncLogTable)
@@ -74,7 +74,7 @@ describe('Sync Log Table', async () => {
                 return 'INVALID'
             }
 
-            return '[]';
+            return '{"logs": [], "total": 0, "page": 1, "per_page": 10}';
         })
 
         const wrapper = shallowMount(SyncLogTable)
@@ -90,4 +90,65 @@ describe('Sync Log Table', async () => {
 
         expect(result).toBe('table-danger')
     });
-});
\ No newline at end of file
+
+    test('Log Warning Class', async () => {
+        mockIPC((cmd) => {
+            if (cmd !== 'get_sync_logs') {
+                return 'INVALID'
+            }
+
+            return '{"logs": [], "total": 0, "page": 1, "per_page": 10}';
+        })
+
+        const wrapper = shallowMount(SyncLogTable)
+
+        const syncLog: SyncLog = {
+            id: 1,
+            log: "Hello world",
+            log_type: SyncLogType.WARN,
+            timestamp: 1726865841,
+        };
+
+        const result = wrapper.vm.getRowColour(syncLog)
+
+        expect(result).toBe('table-warning')
+    });
+
+    test('Page and Filter Logs', async () => {
+        const requests: {page: number, log_type: string|null}[] = [];
+
+        mockIPC((cmd, args) => {
+            if (cmd !== 'get_sync_logs') {
+                return 'INVALID'
+            }
+
+            const filter = args.filter as {page: number, log_type: string|null};
+            requests.push(filter);
+
+            return JSON.stringify({logs: [{id: filter.page, log: "Page " + filter.page, timestamp: 1726865841, log_type: "ERROR"}], total: 25, page: filter.page, per_page: 10});
+        })
+
+        const wrapper = shallowMount(SyncLogTable)
+
+        await flushPromises();
+
+        expect(wrapper.vm.hasPreviousPage).toBeFalsy()
+        expect(wrapper.vm.hasNextPage).toBeTruthy()
+
+        wrapper.vm.nextPage()
+        wrapper.vm.nextPage()
+
+        await flushPromises();
+
+        expect(wrapper.html()).toContain('<td>Page 3</td>')
+        expect(wrapper.vm.hasNextPage).toBeFalsy()
+
+        wrapper.vm.logType = SyncLogType.ERROR
+        wrapper.vm.changeLogType()
+
+        await flushPromises();
+
+        expect(wrapper.vm.page).toBe(1)
+        expect(requests[requests.length - 1]).toStrictEqual({page: 1, per_page: 10, log_type: 'ERROR'})
+    });
+});
@@ -0,0 +1,158 @@
+import {afterEach, describe, expect, test} from "vitest";
+import {flushPromises, shallowMount} from "@vue/test-utils";
+import {clearMocks, mockIPC} from "@tauri-apps/api/mocks";
+import SyncStatus from "../../../components/settings/sync/SyncStatus.vue";
+
+const IDLE_REPORT = {session_id: null, running: false, started_at: null, finished_at: null, success: null, created: 0, updated: 0, deleted: 0, failed: 0, cancelled: false, error: null, failures: [], held_deletions: []};
+
+describe('Sync Status', async () => {
+    afterEach(() => {
+        clearMocks()
+    })
+
+    test('Not Synced Yet', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_sync_status') {
+                return IDLE_REPORT
+            }
+        })
+
+        const wrapper = shallowMount(SyncStatus);
+
+        await flushPromises();
+
+        expect(wrapper.vm.statusText).toBe('Not synced yet')
+    });
+
+    test('Sync Progress', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_sync_status') {
+                return IDLE_REPORT
+            }
+        })
+
+        const wrapper = shallowMount(SyncStatus);
+
+        await flushPromises();
+
+        wrapper.vm.started({...IDLE_REPORT, session_id: 'abc123', running: true, started_at: 1726865841})
+
+        expect(wrapper.vm.statusText).toBe('Syncing')
+
+        wrapper.vm.updateProgress({session_id: 'abc123', sync_account_id: 1, completed: 1, total: 2, created: 0, updated: 1, deleted: 0, failed: 0, error: null})
+
+        expect(wrapper.vm.statusText).toBe('Syncing 1 of 2')
+    });
+
+    test('Sync Completed', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_sync_status') {
+                return IDLE_REPORT
+            }
+        })
+
+        const wrapper = shallowMount(SyncStatus);
+
+        await flushPromises();
+
+        wrapper.vm.completed({...IDLE_REPORT, session_id: 'abc123', started_at: 1726865841, finished_at: 1726865841, success: true})
+
+        expect(wrapper.vm.statusText).toBe('Last synced 9/20/2024, 8:57 PM')
+        expect(wrapper.emitted().completed).toBeTruthy()
+    });
+
+    test('Sync Failed', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_sync_status') {
+                return {...IDLE_REPORT, session_id: 'abc123', started_at: 1726865841, finished_at: 1726865841, success: false, error: 'Error 401 Unauthorized'}
+            }
+        })
+
+        const wrapper = shallowMount(SyncStatus);
+
+        await flushPromises();
+
+        expect(wrapper.vm.statusText).toBe('Sync failed 9/20/2024, 8:57 PM')
+        expect(wrapper.html()).toContain('Error 401 Unauthorized')
+    });
+
+    test('Cancel Sync', async () => {
+        let cancelled = false;
+
+        mockIPC((cmd) => {
+            if (cmd === 'get_sync_status') {
+                return {...IDLE_REPORT, session_id: 'abc123', running: true, started_at: 1726865841}
+            }
+
+            if (cmd === 'cancel_sync') {
+                cancelled = true;
+                return true
+            }
+        })
+
+        const wrapper = shallowMount(SyncStatus);
+
+        await flushPromises();
+
+        await wrapper.vm.stopSync()
+
+        expect(cancelled).toBeTruthy()
+
+        wrapper.vm.completed({...IDLE_REPORT, session_id: 'abc123', started_at: 1726865841, finished_at: 1726865841, success: false, cancelled: true, error: 'Sync cancelled'})
+
+        expect(wrapper.vm.statusText).toBe('Sync cancelled 9/20/2024, 8:57 PM')
+    });
+
+    test('Sync Failures Listed', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_sync_status') {
+                return {...IDLE_REPORT, session_id: 'abc123', started_at: 1726865841, finished_at: 1726865841, success: true, failed: 1, failures: [
+                    {sync_account_id: 1, account_id: 3, external_id: 12, name: 'Github', operation: 'UPDATE', error: 'Error 400 Bad Request', attempts: 1, held: false},
+                    {sync_account_id: 1, account_id: null, external_id: 14, name: null, operation: 'PULL', error: 'Invalid record', attempts: 5, held: true},
+                ]}
+            }
+        })
+
+        const wrapper = shallowMount(SyncStatus);
+
+        await flushPromises();
+
+        expect(wrapper.vm.failures.length).toBe(2)
+        expect(wrapper.html()).toContain('Github (update): Error 400 Bad Request')
+        expect(wrapper.html()).toContain('Remote account 14 (pull): Invalid record')
+        expect(wrapper.html()).toContain('paused after repeated failures')
+    });
+
+    test('Held Deletions Confirmed', async () => {
+        let confirmedSyncAccountId = null;
+        const heldReport = {...IDLE_REPORT, session_id: 'abc123', started_at: 1726865841, finished_at: 1726865841, success: true, held_deletions: [
+            {sync_account_id: 2, account_id: 3, external_id: 12, name: 'Github'},
+            {sync_account_id: 2, account_id: 4, external_id: 13, name: 'Gitlab'},
+        ]};
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_sync_status') {
+                return confirmedSyncAccountId ? {...heldReport, deleted: 2, held_deletions: []} : heldReport
+            }
+
+            if (cmd === 'confirm_held_deletions') {
+                confirmedSyncAccountId = args.syncAccountId;
+                return 2
+            }
+        })
+
+        const wrapper = shallowMount(SyncStatus);
+
+        await flushPromises();
+
+        expect(wrapper.vm.heldDeletions.length).toBe(2)
+        expect(wrapper.html()).toContain('2 accounts were removed remotely and are waiting for confirmation')
+        expect(wrapper.html()).toContain('Gitlab')
+
+        await wrapper.vm.confirmDeletions()
+
+        expect(confirmedSyncAccountId).toBe(2)
+        expect(wrapper.vm.heldDeletions.length).toBe(0)
+        expect(wrapper.emitted().completed).toBeTruthy()
+    });
+});
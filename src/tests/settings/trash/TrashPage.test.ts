@@ -0,0 +1,158 @@
+import {afterEach, describe, expect, test} from "vitest";
+import {flushPromises, shallowMount} from "@vue/test-utils";
+import {clearMocks, mockIPC} from "@tauri-apps/api/mocks";
+import TrashPage from "../../../components/settings/trash/TrashPage.vue";
+
+describe('Trash Page', async () => {
+    afterEach(() => {
+        clearMocks()
+    })
+
+    test('Get Trashed Accounts', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', trash_retention_days: 90}
+            }
+
+            if (cmd === 'get_trashed_accounts') {
+                return [{id: 1, name: 'Test Account', colour: 'ffffff', deleted_at: 1725483734}]
+            }
+
+            return 'INVALID'
+        })
+
+        const wrapper = shallowMount(TrashPage);
+
+        await flushPromises();
+
+        expect(wrapper.vm.trashRetentionDays).toBe(90)
+        expect(wrapper.vm.accounts.length).toBe(1)
+        expect(wrapper.vm.accounts[0].name).toBe('Test Account')
+    });
+
+    test('Restore Account', async () => {
+        let restored = false;
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', trash_retention_days: 30}
+            }
+
+            if (cmd === 'get_trashed_accounts') {
+                return restored ? [] : [{id: 1, name: 'Test Account', colour: 'ffffff', deleted_at: 1725483734}]
+            }
+
+            if (cmd === 'restore_account' && args.accountId === 1) {
+                restored = true;
+                return true
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(TrashPage);
+
+        await flushPromises();
+
+        wrapper.vm.restore(wrapper.vm.accounts[0])
+
+        await flushPromises();
+
+        expect(wrapper.vm.message).toBe('Account Restored')
+        expect(wrapper.vm.accounts.length).toBe(0)
+        expect(wrapper.emitted().syncRequired).toBeTruthy()
+    });
+
+    test('Restore Account - Name Taken', async () => {
+        mockIPC((cmd) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', trash_retention_days: 30}
+            }
+
+            if (cmd === 'get_trashed_accounts') {
+                return [{id: 1, name: 'Test Account', colour: 'ffffff', deleted_at: 1725483734}]
+            }
+
+            if (cmd === 'restore_account') {
+                throw 'Account already exists: Test Account'
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(TrashPage);
+
+        await flushPromises();
+
+        wrapper.vm.restore(wrapper.vm.accounts[0])
+
+        await flushPromises();
+
+        expect(wrapper.vm.message).toBe('Account already exists: Test Account')
+        expect(wrapper.vm.accounts.length).toBe(1)
+        expect(wrapper.emitted().syncRequired).toBeFalsy()
+    });
+
+    test('Purge Account', async () => {
+        let purged = false;
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', trash_retention_days: 30}
+            }
+
+            if (cmd === 'get_trashed_accounts') {
+                return purged ? [] : [{id: 1, name: 'Test Account', colour: 'ffffff', deleted_at: 1725483734}]
+            }
+
+            if (cmd === 'purge_account' && args.accountId === 1) {
+                purged = true;
+                return true
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(TrashPage);
+
+        await flushPromises();
+
+        wrapper.vm.purge(wrapper.vm.accounts[0])
+
+        await flushPromises();
+
+        expect(wrapper.vm.accounts.length).toBe(0)
+    });
+
+    test('Save Retention', async () => {
+        let savedRetention = 0;
+
+        mockIPC((cmd, args) => {
+            if (cmd === 'get_settings') {
+                return {id: 1, theme: 'DARK', trash_retention_days: 30}
+            }
+
+            if (cmd === 'get_trashed_accounts') {
+                return []
+            }
+
+            if (cmd === 'save_trash_retention') {
+                savedRetention = args.trashRetentionDays as number;
+                return {id: 1, theme: 'DARK', trash_retention_days: savedRetention}
+            }
+
+            throw 'Invalid Command'
+        })
+
+        const wrapper = shallowMount(TrashPage);
+
+        await flushPromises();
+
+        wrapper.vm.trashRetentionDays = 7
+        wrapper.vm.saveRetention()
+
+        await flushPromises();
+
+        expect(savedRetention).toBe(7)
+    });
+});